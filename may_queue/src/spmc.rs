@@ -145,6 +145,12 @@ pub struct Queue<T> {
     // use for push
     tail: CachePadded<Position<T>>,
 
+    // -----------------------------------------
+    // use for length, popped by the owner
+    local_popped: CachePadded<AtomicUsize>,
+    // popped by the other threads
+    stolen: CachePadded<AtomicUsize>,
+
     /// Indicates that dropping a `Queue<T>` may drop values of type `T`.
     _marker: PhantomData<T>,
 }
@@ -159,6 +165,8 @@ impl<T> Queue<T> {
         Queue {
             head: BlockPtr::new(init_block).into(),
             tail: Position::new(init_block).into(),
+            local_popped: AtomicUsize::new(0).into(),
+            stolen: AtomicUsize::new(0).into(),
             _marker: PhantomData,
         }
     }
@@ -237,6 +245,7 @@ impl<T> Queue<T> {
                     }
                     // get the data
                     let v = block.get(id);
+                    self.stolen.fetch_add(1, Ordering::Relaxed);
 
                     if block.mark_slots_read(1) {
                         // we need to free the old block
@@ -301,6 +310,7 @@ impl<T> Queue<T> {
                         assert_eq!(pop_index, push_index);
                         // advance the push index and this slot is ignored
                         self.tail.index.store(push_index + 1, Ordering::Relaxed);
+                        self.inc_local_popped();
                         if block.mark_slots_read(1) {
                             // we need to free the old block
                             let _unused_block = unsafe { Box::from_raw(block) };
@@ -310,6 +320,7 @@ impl<T> Queue<T> {
 
                     // get the data
                     let v = block.get(id);
+                    self.inc_local_popped();
 
                     if block.mark_slots_read(1) {
                         // we need to free the old block
@@ -389,6 +400,7 @@ impl<T> Queue<T> {
 
                     // get the data
                     let value = block.copy_to_bulk(pop_index, end);
+                    self.stolen.fetch_add(end - pop_index, Ordering::Relaxed);
 
                     if block.mark_slots_read(end - pop_index) {
                         // we need to free the old block
@@ -426,6 +438,32 @@ impl<T> Queue<T> {
         push_index.wrapping_sub(pop_index)
     }
 
+    // only the owner thread would call this
+    #[inline]
+    fn inc_local_popped(&self) {
+        let n = unsafe { self.local_popped.unsync_load() };
+        self.local_popped.store(n + 1, Ordering::Relaxed);
+    }
+
+    /// get the approximate size of queue
+    ///
+    /// it's safe to call in any thread, the result may be stale if the
+    /// queue is modified concurrently
+    pub fn approx_len(&self) -> usize {
+        let popped = self
+            .local_popped
+            .load(Ordering::Relaxed)
+            .wrapping_add(self.stolen.load(Ordering::Relaxed));
+        let push_index = self.tail.index.load(Ordering::Acquire);
+        let len = push_index.wrapping_sub(popped);
+        // the pop counters may be seen before the push index
+        if len > isize::MAX as usize {
+            0
+        } else {
+            len
+        }
+    }
+
     /// if the queue is empty
     pub fn is_empty(&self) -> bool {
         let head = self.head.0.load(Ordering::Acquire);
@@ -510,10 +548,11 @@ impl<T> Steal<T> {
         self.0.is_empty()
     }
 
-    // #[inline]
-    // pub fn len(&self) -> usize {
-    //     self.0.len()
-    // }
+    /// get the approximate number of tasks in the queue
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.approx_len()
+    }
 
     /// Steals block of tasks from self and place them into `dst`.
     #[inline]
@@ -574,11 +613,13 @@ mod test {
             q.push(i);
         }
         assert_eq!(unsafe { q.len() }, 100);
+        assert_eq!(q.approx_len(), 100);
         println!("{q:?}");
 
         for i in 0..100 {
             assert_eq!(q.pop(), Some(i));
         }
+        assert_eq!(q.approx_len(), 0);
         assert_eq!(q.pop(), None);
         assert!(q.is_empty());
     }
@@ -0,0 +1,374 @@
+//! an elastic thread pool to run the blocking work for coroutines
+//!
+//! the threads are created on demand and exit after idle for a while, the
+//! calling coroutine is parked until the work is done so that the worker
+//! thread is not blocked. the max number of threads and the idle timeout
+//! are configured by `Config`
+use std::collections::VecDeque;
+use std::fmt;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::config::config;
+use crate::coroutine_impl::{current_cancel_data, is_coroutine};
+use crate::sync::{AtomicOption, Blocker};
+use parking_lot::{Condvar, Mutex};
+
+type Job = Box<dyn FnOnce() + Send>;
+
+struct State {
+    // the jobs that are waiting for a thread
+    jobs: VecDeque<Job>,
+    // the number of alive threads
+    threads: usize,
+    // the number of idle threads
+    idle: usize,
+}
+
+struct Pool {
+    state: Mutex<State>,
+    cond: Condvar,
+}
+
+static POOL: Pool = Pool {
+    state: Mutex::new(State {
+        jobs: VecDeque::new(),
+        threads: 0,
+        idle: 0,
+    }),
+    cond: Condvar::new(),
+};
+
+impl Pool {
+    fn execute(&'static self, job: Job) {
+        let mut state = self.state.lock();
+        state.jobs.push_back(job);
+        if state.idle > 0 {
+            self.cond.notify_one();
+        }
+        // the notified threads are still counted as idle until they wake up
+        if state.jobs.len() <= state.idle {
+            return;
+        }
+        if state.threads >= config().get_blocking_threads() {
+            // the job would be picked up by a busy thread later
+            return;
+        }
+
+        state.threads += 1;
+        let spawned = thread::Builder::new()
+            .name("may-blocking".to_owned())
+            .spawn(move || self.run_worker());
+        if let Err(e) = spawned {
+            state.threads -= 1;
+            // the queued job would be run by other threads
+            error!("failed to spawn blocking thread: {e}");
+        }
+    }
+
+    fn run_worker(&self) {
+        let mut state = self.state.lock();
+        loop {
+            if let Some(job) = state.jobs.pop_front() {
+                drop(state);
+                job();
+                state = self.state.lock();
+                continue;
+            }
+
+            state.idle += 1;
+            let keep_alive = config().get_blocking_keep_alive();
+            let timeout = self.cond.wait_for(&mut state, keep_alive).timed_out();
+            state.idle -= 1;
+            if timeout && state.jobs.is_empty() {
+                state.threads -= 1;
+                return;
+            }
+        }
+    }
+}
+
+// the result of the blocking work
+struct Packet<T> {
+    ret: Mutex<Option<thread::Result<T>>>,
+    // set when the work is done
+    done: AtomicBool,
+    // the waiter that need to be waked up when done
+    to_wake: AtomicOption<Arc<Blocker>>,
+}
+
+impl<T> Packet<T> {
+    fn new() -> Self {
+        Packet {
+            ret: Mutex::new(None),
+            done: AtomicBool::new(false),
+            to_wake: AtomicOption::none(),
+        }
+    }
+
+    fn set(&self, ret: thread::Result<T>) {
+        *self.ret.lock() = Some(ret);
+        self.done.store(true, Ordering::Release);
+        if let Some(w) = self.to_wake.take() {
+            w.unpark();
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+
+    // wait until the work is done, the cancel would panic in park
+    fn wait(&self) {
+        while !self.is_done() {
+            let cur = Blocker::current();
+            // register the blocker first
+            self.to_wake.store(cur.clone());
+            // re-check the state
+            if self.is_done() {
+                self.to_wake.take();
+                break;
+            }
+            cur.park(None).ok();
+        }
+    }
+
+    fn take(&self) -> thread::Result<T> {
+        self.ret
+            .lock()
+            .take()
+            .expect("blocking work result is taken")
+    }
+}
+
+// wrap the work so that the result is sent to the packet even if it panics
+fn make_job<'a, F, T>(f: F, packet: Arc<Packet<T>>) -> Box<dyn FnOnce() + Send + 'a>
+where
+    F: FnOnce() -> T + Send + 'a,
+    T: Send + 'a,
+{
+    Box::new(move || {
+        let ret = panic::catch_unwind(AssertUnwindSafe(f));
+        packet.set(ret);
+    })
+}
+
+/// run the blocking work and wait for the result
+///
+/// in coroutine context the work is run in the blocking pool and the
+/// coroutine is parked until it's done, in thread context it's run in place.
+/// the cancel of the coroutine takes effect after the work is done since
+/// the work may borrow the data on the coroutine stack
+pub(crate) fn run<'a, F, T>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'a,
+    T: Send + 'a,
+{
+    if !is_coroutine() {
+        return f();
+    }
+
+    let packet = Arc::new(Packet::new());
+    let job = make_job(f, packet.clone());
+    // the job is always finished before we return
+    let job: Job = unsafe { mem::transmute(job) };
+
+    let cancel = current_cancel_data();
+    cancel.disable_cancel();
+    POOL.execute(job);
+    // the cancel is disabled, the park would not panic
+    packet.wait();
+    cancel.enable_cancel();
+    cancel.check_cancel();
+
+    packet.take().unwrap_or_else(|e| panic::resume_unwind(e))
+}
+
+/// A handle to the work spawned by [`spawn_blocking`]
+///
+/// [`spawn_blocking`]: fn.spawn_blocking.html
+pub struct BlockingJoinHandle<T> {
+    packet: Arc<Packet<T>>,
+}
+
+impl<T> BlockingJoinHandle<T> {
+    /// return true if the blocking work is finished
+    pub fn is_done(&self) -> bool {
+        self.packet.is_done()
+    }
+
+    /// wait for the blocking work to finish and return its result
+    ///
+    /// in coroutine context only the coroutine is parked, the worker thread
+    /// is free to run other coroutines. if the waiting coroutine is canceled
+    /// the blocking work would still run to the end
+    pub fn join(self) -> thread::Result<T> {
+        self.packet.wait();
+        self.packet.take()
+    }
+}
+
+impl<T> fmt::Debug for BlockingJoinHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BlockingJoinHandle {{ done: {} }}", self.is_done())
+    }
+}
+
+/// Run the blocking work in the blocking thread pool
+///
+/// the worker threads of the coroutines should never be blocked, use this
+/// to offload the blocking calls such as the file io or the third party
+/// sync APIs. the pool grows on demand up to `Config::get_blocking_threads`
+/// threads, and an idle thread exits after `Config::get_blocking_keep_alive`
+///
+/// # Examples
+///
+/// ```rust
+/// use may::coroutine;
+///
+/// let h = may::go!(|| {
+///     let h = coroutine::spawn_blocking(|| {
+///         std::thread::sleep(std::time::Duration::from_millis(10));
+///         42
+///     });
+///     // only the coroutine is parked here
+///     h.join().unwrap()
+/// });
+/// assert_eq!(h.join().unwrap(), 42);
+/// ```
+pub fn spawn_blocking<F, T>(f: F) -> BlockingJoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let packet = Arc::new(Packet::new());
+    POOL.execute(make_job(f, packet.clone()));
+    BlockingJoinHandle { packet }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    #[test]
+    fn run_in_pool() {
+        let data = vec![1, 2, 3];
+        let h = go!(move || {
+            // borrow the data on the coroutine stack
+            let sum = run(|| {
+                thread::sleep(Duration::from_millis(10));
+                data.iter().sum::<i32>()
+            });
+            assert_eq!(sum, 6);
+            sum
+        });
+        assert_eq!(h.join().unwrap(), 6);
+    }
+
+    #[test]
+    fn blocking_not_stall_worker() {
+        let cnt = Arc::new(AtomicUsize::new(0));
+        let mut handles = vec![];
+        for _ in 0..100 {
+            let cnt = cnt.clone();
+            handles.push(go!(move || {
+                run(|| thread::sleep(Duration::from_millis(100)));
+                cnt.fetch_add(1, Ordering::SeqCst);
+            }));
+        }
+        // all the coroutines are sleeping in the pool at the same time
+        let start = std::time::Instant::now();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert_eq!(cnt.load(Ordering::SeqCst), 100);
+    }
+
+    #[test]
+    fn queued_jobs_spawn_threads() {
+        let pool: &'static Pool = Box::leak(Box::new(Pool {
+            state: Mutex::new(State {
+                jobs: VecDeque::new(),
+                threads: 0,
+                idle: 0,
+            }),
+            cond: Condvar::new(),
+        }));
+        // leave one idle thread in the pool
+        pool.execute(Box::new(|| {}));
+        while pool.state.lock().idle != 1 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        // the two jobs only finish when they run at the same time
+        let started = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = std::sync::mpsc::channel();
+        for _ in 0..2 {
+            let (started, tx) = (started.clone(), tx.clone());
+            pool.execute(Box::new(move || {
+                started.fetch_add(1, Ordering::SeqCst);
+                let start = std::time::Instant::now();
+                while started.load(Ordering::SeqCst) < 2 {
+                    if start.elapsed() > Duration::from_secs(5) {
+                        return tx.send(false).unwrap();
+                    }
+                    thread::sleep(Duration::from_millis(1));
+                }
+                tx.send(true).unwrap();
+            }));
+        }
+        assert!(rx.recv().unwrap() && rx.recv().unwrap());
+    }
+
+    #[test]
+    fn panic_in_pool() {
+        let h = go!(|| run(|| panic!("blocking panic")));
+        let e = h.join().unwrap_err();
+        assert_eq!(e.downcast_ref::<&str>(), Some(&"blocking panic"));
+    }
+
+    #[test]
+    fn cancel_after_done() {
+        let done = Arc::new(AtomicUsize::new(0));
+        let d = done.clone();
+        let h = go!(move || {
+            run(|| {
+                thread::sleep(Duration::from_millis(50));
+                d.fetch_add(1, Ordering::SeqCst);
+            });
+            d.fetch_add(1, Ordering::SeqCst);
+        });
+        thread::sleep(Duration::from_millis(10));
+        unsafe { h.coroutine().cancel() };
+        h.join().unwrap_err();
+        // the blocking work is finished, but the rest is canceled
+        assert_eq!(done.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn spawn_blocking_join() {
+        let h = go!(|| {
+            let handles: Vec<_> = (0..10)
+                .map(|i| {
+                    spawn_blocking(move || {
+                        thread::sleep(Duration::from_millis(50));
+                        i
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum::<i32>()
+        });
+        assert_eq!(h.join().unwrap(), 45);
+
+        // join in thread context
+        let h = spawn_blocking(|| panic!("blocking panic"));
+        let e = h.join().unwrap_err();
+        assert_eq!(e.downcast_ref::<&str>(), Some(&"blocking panic"));
+    }
+}
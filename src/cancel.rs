@@ -7,7 +7,7 @@ use crate::coroutine_impl::CoroutineImpl;
 #[cfg(feature = "io_cancel")]
 use crate::io::cancel::CancelIoImpl;
 use crate::likely::unlikely;
-use crate::scheduler::get_scheduler;
+use crate::scheduler::schedule;
 use crate::sync::AtomicOption;
 use crate::yield_now::{get_co_para, set_co_para};
 use generator::Error;
@@ -136,7 +136,7 @@ impl<T: CancelIo> CancelImpl<T> {
                 // this is not safe, the kernel may still need to use the overlapped
                 // set the cancel result for the coroutine
                 set_co_para(&mut co, io::Error::new(io::ErrorKind::Other, "Canceled"));
-                get_scheduler().schedule(co);
+                schedule(co);
             }
         }
     }
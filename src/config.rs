@@ -4,15 +4,21 @@
 #[cfg(feature = "io_timeout")]
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
 
 // default stack size, in usize
 // windows has a minimal size as 0x4a8!!!!
 const DEFAULT_STACK_SIZE: usize = 0x1000;
 const DEFAULT_POOL_CAPACITY: usize = 1000;
+const DEFAULT_BLOCKING_THREADS: usize = 512;
+// in milliseconds
+const DEFAULT_BLOCKING_KEEP_ALIVE: usize = 10_000;
 
 static WORKERS: AtomicUsize = AtomicUsize::new(0);
 static STACK_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_STACK_SIZE);
 static POOL_CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_POOL_CAPACITY);
+static BLOCKING_THREADS: AtomicUsize = AtomicUsize::new(DEFAULT_BLOCKING_THREADS);
+static BLOCKING_KEEP_ALIVE: AtomicUsize = AtomicUsize::new(DEFAULT_BLOCKING_KEEP_ALIVE);
 
 // How long does the epoll wait before continuing with other tasks
 // By default, 10ms
@@ -22,6 +28,12 @@ static POLL_TIMEOUT_NS: AtomicU64 = AtomicU64::new(10_000_000);
 // Should cores be pinned?
 static PIN_WORKERS: AtomicBool = AtomicBool::new(true);
 
+// report the stack overflow of coroutines
+static STACK_OVERFLOW_REPORT: AtomicBool = AtomicBool::new(true);
+
+// track the state of the coroutines for the task dump
+static TASK_DUMP: AtomicBool = AtomicBool::new(false);
+
 /// `May` Configuration type
 pub struct Config;
 
@@ -116,4 +128,77 @@ impl Config {
     pub fn get_worker_pin(&self) -> bool {
         PIN_WORKERS.load(Ordering::Acquire)
     }
+
+    /// Enable/Disable reporting the stack overflow of coroutines
+    ///
+    /// when enabled a coroutine that overflows its stack aborts the process
+    /// with its id, name and stack size. it installs a SIGSEGV handler and
+    /// an alternate signal stack for each worker thread, only supported on
+    /// unix. it must be set before the runtime starts
+    pub fn set_stack_overflow_report(&self, enable: bool) -> &Self {
+        info!("set stack overflow report={:?}", enable);
+        STACK_OVERFLOW_REPORT.store(enable, Ordering::Release);
+        self
+    }
+
+    /// Check if the stack overflow of coroutines is reported
+    pub fn get_stack_overflow_report(&self) -> bool {
+        STACK_OVERFLOW_REPORT.load(Ordering::Acquire)
+    }
+
+    /// Enable/Disable tracking the coroutines for `coroutine::dump`
+    ///
+    /// when enabled the coroutines spawned afterwards record their spawn time,
+    /// state and last worker, which costs a few atomic stores on each context
+    /// switch
+    pub fn set_task_dump(&self, enable: bool) -> &Self {
+        info!("set task dump={:?}", enable);
+        TASK_DUMP.store(enable, Ordering::Release);
+        self
+    }
+
+    /// Check if the coroutines are tracked for `coroutine::dump`
+    pub fn get_task_dump(&self) -> bool {
+        TASK_DUMP.load(Ordering::Acquire)
+    }
+
+    /// set the max number of threads in the blocking pool
+    ///
+    /// the blocking threads are used by `spawn_blocking` and the other
+    /// blocking offload, if you pass 0 to it, will use internal default
+    pub fn set_blocking_threads(&self, threads: usize) -> &Self {
+        info!("set blocking threads={:?}", threads);
+        BLOCKING_THREADS.store(threads, Ordering::Release);
+        self
+    }
+
+    /// get the max number of threads in the blocking pool
+    pub fn get_blocking_threads(&self) -> usize {
+        let threads = BLOCKING_THREADS.load(Ordering::Acquire);
+        if threads != 0 {
+            threads
+        } else {
+            DEFAULT_BLOCKING_THREADS
+        }
+    }
+
+    /// set how long an idle blocking thread is kept alive, in ms precision
+    ///
+    /// if you pass zero duration to it, will use internal default
+    pub fn set_blocking_keep_alive(&self, dur: Duration) -> &Self {
+        info!("set blocking keep alive={:?}", dur);
+        BLOCKING_KEEP_ALIVE.store(dur.as_millis() as usize, Ordering::Release);
+        self
+    }
+
+    /// get how long an idle blocking thread is kept alive
+    pub fn get_blocking_keep_alive(&self) -> Duration {
+        let ms = BLOCKING_KEEP_ALIVE.load(Ordering::Acquire);
+        let ms = if ms != 0 {
+            ms
+        } else {
+            DEFAULT_BLOCKING_KEEP_ALIVE
+        };
+        Duration::from_millis(ms as u64)
+    }
 }
@@ -1,10 +1,16 @@
 // re-export coroutine interface
+pub use crate::blocking_pool::{spawn_blocking, BlockingJoinHandle};
 pub use crate::cancel::trigger_cancel_panic;
 pub use crate::coroutine_impl::{
-    current, is_coroutine, park, park_timeout, spawn, Builder, Coroutine,
+    current, current_id, install_panic_hook, is_coroutine, park, park_timeout, spawn, Builder,
+    Coroutine, CoroutineId,
 };
+#[cfg(unix)]
+pub use crate::dump::dump_on_signal;
+pub use crate::dump::{dump, CoroutineInfo, CoroutineState, Dump};
 pub use crate::join::JoinHandle;
 pub use crate::park::ParkError;
 pub use crate::scoped::scope;
 pub use crate::sleep::sleep;
+pub use crate::task_group::{GroupError, TaskGroup};
 pub use crate::yield_now::yield_now;
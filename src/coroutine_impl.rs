@@ -1,15 +1,19 @@
 use std::fmt;
 use std::io;
-use std::sync::Arc;
+use std::num::NonZeroU64;
+use std::panic;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Once};
 use std::time::Duration;
 
 use crate::cancel::Cancel;
-use crate::config::config;
+use crate::dump::Trace;
 use crate::join::{make_join_handle, Join, JoinHandle};
 use crate::local::get_co_local_data;
 use crate::local::CoroutineLocal;
+use crate::metrics::Stats;
 use crate::park::Park;
-use crate::scheduler::get_scheduler;
+use crate::scheduler::{current_scheduler, set_current, with_scheduler, Scheduler};
 use crate::sync::AtomicOption;
 use generator::{Generator, Gn};
 
@@ -61,21 +65,27 @@ impl Done {
         // destroy the local storage
         let local = unsafe { Box::from_raw(get_co_local(&co)) };
         let name = local.get_co().name();
+        let id = local.get_co().raw_id();
 
         // recycle the coroutine
         let (size, used) = co.stack_usage();
         if used == size {
-            eprintln!("stack overflow detected, size={size}");
-            ::std::process::exit(1);
+            crate::overflow::abort(local.get_co(), &[]);
         }
         // show the actual used stack size in debug log
         if local.get_co().stack_size() & 1 == 1 {
-            println!("coroutine name = {name:?}, stack size = {size},  used size = {used}");
+            println!(
+                "coroutine id = {id}, name = {name:?}, stack size = {size},  used size = {used}"
+            );
         }
 
-        if size == config().get_stack_size() {
-            get_scheduler().pool.put(co);
+        let sched = local.get_sched();
+        Stats::inc(&sched.stats.current(sched).finished);
+        if size == sched.pool.stack_size() {
+            sched.pool.put(co);
         }
+        // the shutdown would wait until the count drops to zero
+        sched.registry.remove(id);
     }
 }
 
@@ -99,12 +109,41 @@ fn get_co_local(co: &CoroutineImpl) -> *mut CoroutineLocal {
 /// Coroutine
 /// /////////////////////////////////////////////////////////////////////////////
 
+/// A unique identifier for a spawned coroutine
+///
+/// the ids are assigned in increasing order and never reused in the process
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct CoroutineId(NonZeroU64);
+
+impl CoroutineId {
+    // generate a new unique id
+    fn new() -> CoroutineId {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        CoroutineId(NonZeroU64::new(id).expect("coroutine id overflow"))
+    }
+
+    /// return the id as a number
+    pub fn as_u64(&self) -> NonZeroU64 {
+        self.0
+    }
+}
+
+impl fmt::Display for CoroutineId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
 /// The internal representation of a `Coroutine` handle
 struct Inner {
+    id: CoroutineId,
     name: Option<String>,
     stack_size: usize,
     park: Park,
     cancel: Cancel,
+    // only set when the task dump is enabled
+    trace: Option<Trace>,
 }
 
 #[derive(Clone)]
@@ -115,17 +154,35 @@ pub struct Coroutine {
 
 impl Coroutine {
     // Used only internally to construct a coroutine object without spawning
-    fn new(name: Option<String>, stack_size: usize) -> Coroutine {
+    fn new(id: CoroutineId, name: Option<String>, stack_size: usize, trace: bool) -> Coroutine {
         Coroutine {
             inner: Arc::new(Inner {
+                id,
                 name,
                 stack_size,
                 park: Park::new(),
                 cancel: Cancel::new(),
+                trace: trace.then(Trace::new),
             }),
         }
     }
 
+    /// Gets the coroutine's unique identifier.
+    pub fn id(&self) -> CoroutineId {
+        self.inner.id
+    }
+
+    /// the unique number of the coroutine
+    pub(crate) fn raw_id(&self) -> u64 {
+        self.inner.id.0.get()
+    }
+
+    /// the tracked info for the task dump
+    #[inline]
+    pub(crate) fn trace(&self) -> Option<&Trace> {
+        self.inner.trace.as_ref()
+    }
+
     /// Gets the coroutine stack size.
     pub fn stack_size(&self) -> usize {
         self.inner.stack_size
@@ -143,6 +200,8 @@ impl Coroutine {
     /// And would drop all the resource tha the coroutine currently holding
     /// This may have unexpected side effects if you are not fully aware it
     pub unsafe fn cancel(&self) {
+        #[cfg(feature = "tracing")]
+        crate::span::cancel(self);
         self.inner.cancel.cancel();
     }
 
@@ -161,7 +220,10 @@ impl Coroutine {
 
 impl fmt::Debug for Coroutine {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Debug::fmt(&self.name(), f)
+        f.debug_struct("Coroutine")
+            .field("id", &self.id())
+            .field("name", &self.name())
+            .finish_non_exhaustive()
     }
 }
 
@@ -260,9 +322,17 @@ impl Builder {
     {
         static DONE: Done = Done {};
 
-        let sched = get_scheduler();
+        let sched = current_scheduler();
         let name = self.name;
-        let stack_size = self.stack_size.unwrap_or_else(|| config().get_stack_size());
+        let stack_size = self.stack_size.unwrap_or_else(|| sched.pool.stack_size());
+
+        let handle = Coroutine::new(CoroutineId::new(), name, stack_size, sched.is_task_dump());
+        // register it before checking the flag so that shutdown can't miss it
+        sched.registry.insert(&handle);
+        if sched.is_shutdown() {
+            sched.registry.remove(handle.raw_id());
+            return Err(io::Error::other("runtime is shutting down"));
+        }
 
         // create a join resource, shared by waited coroutine and *this* coroutine
         let panic = Arc::new(AtomicOption::none());
@@ -276,6 +346,8 @@ impl Builder {
         };
 
         let closure = move || {
+            #[cfg(unix)]
+            crate::overflow::set_stack_guard();
             // trigger the JoinHandler
             // we must declare the variable before calling f so that stack is prepared
             // to unwind these local data. for the panic err we would set it in the
@@ -283,12 +355,17 @@ impl Builder {
 
             // set the return packet
             their_packet.store(f());
+            #[cfg(feature = "tracing")]
+            crate::span::finished();
 
             their_join.trigger();
             subscriber
         };
 
-        let mut co = if stack_size == config().get_stack_size() {
+        let stats = sched.stats.current(&sched);
+        Stats::inc(&stats.spawned);
+        let mut co = if stack_size == sched.pool.stack_size() {
+            Stats::inc(&stats.pool_gets);
             let mut co = sched.pool.get();
             co.init_code(closure);
             co
@@ -296,9 +373,15 @@ impl Builder {
             Gn::new_opt(stack_size, closure)
         };
 
-        let handle = Coroutine::new(name, stack_size);
         // create the local storage
-        let local = CoroutineLocal::new(handle.clone(), join.clone());
+        let local = CoroutineLocal::new(handle.clone(), join.clone(), sched);
+        #[cfg(unix)]
+        if crate::config::config().get_stack_overflow_report() {
+            // the real size that is rounded up by the generator
+            local.set_stack_len(co.stack_usage().0 * std::mem::size_of::<usize>());
+        }
+        #[cfg(feature = "tracing")]
+        crate::span::spawned(local.get_span());
         // attache the local storage to the coroutine
         co.set_local_data(Box::into_raw(local) as *mut u8);
 
@@ -353,12 +436,11 @@ impl Builder {
         // we will still get optimizations in spawn_impl
         let id = self.id;
         let (co, handle) = self.spawn_impl(f)?;
-        let s = get_scheduler();
 
-        match id {
+        with_scheduler(|s| match id {
             None => s.schedule_global(co),
             Some(id) => s.schedule_global_with_id(co, id),
-        }
+        });
 
         Ok(handle)
     }
@@ -460,6 +542,11 @@ pub fn current() -> Coroutine {
     }
 }
 
+/// Gets the id of the current coroutine, `None` in thread context
+pub fn current_id() -> Option<CoroutineId> {
+    get_co_local_data().map(|local| unsafe { local.as_ref() }.get_co().id())
+}
+
 /// if current context is coroutine
 #[inline]
 pub fn is_coroutine() -> bool {
@@ -469,6 +556,39 @@ pub fn is_coroutine() -> bool {
     get_co_local_data().is_some()
 }
 
+/// install a panic hook that prints the id and name of the panicking coroutine
+///
+/// the default hook only shows the worker thread. the new hook prints a line
+/// like `coroutine #3 'worker' panicked` and then calls the hook that was
+/// installed before, so it should be called after any custom hook is set.
+/// the canceled coroutines are not reported. calling it more than once has
+/// no effect
+///
+/// # Examples
+///
+/// ```rust
+/// may::coroutine::install_panic_hook();
+///
+/// let h = may::go!(|| panic!("boom"));
+/// assert!(h.join().is_err());
+/// ```
+pub fn install_panic_hook() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let prev = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            let is_cancel = info.payload().downcast_ref::<generator::Error>()
+                == Some(&generator::Error::Cancel);
+            if let Some(local) = get_co_local_data().filter(|_| !is_cancel) {
+                let co = unsafe { local.as_ref() }.get_co();
+                let name = co.name().unwrap_or("<unnamed>");
+                eprintln!("coroutine #{} '{name}' panicked", co.id());
+            }
+            prev(info)
+        }));
+    });
+}
+
 /// get current coroutine cancel registration
 /// panic in a thread context
 #[inline]
@@ -479,6 +599,22 @@ pub(crate) fn current_cancel_data() -> &'static Cancel {
     }
 }
 
+/// get the tracked info of the current coroutine
+#[inline]
+pub(crate) fn current_trace() -> Option<&'static Trace> {
+    let local = get_co_local_data()?;
+    unsafe { &*local.as_ptr() }.get_co().trace()
+}
+
+/// get the scheduler that the coroutine belongs to
+///
+/// the coroutine holds a reference of the scheduler until it's done
+#[inline]
+pub(crate) fn co_scheduler(co: &CoroutineImpl) -> &Scheduler {
+    let local = unsafe { &*get_co_local(co) };
+    local.get_sched()
+}
+
 #[inline]
 pub(crate) fn co_cancel_data(co: &CoroutineImpl) -> &'static Cancel {
     let local = unsafe { &*get_co_local(co) };
@@ -520,14 +656,34 @@ pub fn park_timeout(dur: Duration) {
 /// run the coroutine
 #[inline]
 pub(crate) fn run_coroutine(mut co: CoroutineImpl) {
+    // the coroutine and its subscriber would use the scheduler it belongs to
+    let prev = set_current(co_scheduler(&co));
+    let local = unsafe { &*get_co_local(&co) };
+    let trace = local.get_co().trace();
+    if let Some(trace) = trace {
+        trace.resume();
+    }
+    // the local would be dropped when the coroutine is done
+    #[cfg(feature = "tracing")]
+    let _span = local.get_span().clone().entered();
+    #[cfg(feature = "tracing")]
+    crate::span::resumed();
     match co.resume() {
-        Some(ev) => ev.subscribe(co),
+        Some(ev) => {
+            if let Some(trace) = trace {
+                trace.yielded();
+            }
+            ev.subscribe(co)
+        }
         None => {
             // panic happened here
             let local = unsafe { &mut *get_co_local(&co) };
             let join = local.get_join();
             // set the panic data
-            if let Some(panic) = co.get_panic_data() {
+            let panic = co.get_panic_data();
+            #[cfg(feature = "tracing")]
+            crate::span::unwound(panic.as_deref());
+            if let Some(panic) = panic {
                 join.set_panic_data(panic);
             }
             // trigger the join here
@@ -535,4 +691,5 @@ pub(crate) fn run_coroutine(mut co: CoroutineImpl) {
             Done::drop_coroutine(co);
         }
     }
+    set_current(prev);
 }
@@ -0,0 +1,481 @@
+//! dump the live coroutines
+//!
+//! all the live coroutines are listed with their id, name and stack size. when
+//! the task dump is enabled by [`Config::set_task_dump`] the coroutines spawned
+//! afterwards also record their spawn time, state and the last worker that ran
+//! them. the state is updated when the coroutine is resumed or parked, a woken
+//! coroutine keeps its parked state until it runs again.
+//!
+//! [`Config::set_task_dump`]: ../struct.Config.html#method.set_task_dump
+use std::fmt;
+use std::sync::atomic::{AtomicU8, AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::coroutine_impl::{current_trace, Coroutine, CoroutineId};
+use crate::scheduler::{for_each_live_scheduler, WORKER_ID};
+
+/// The state of a coroutine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoroutineState {
+    /// spawned or yielded, waiting to be run
+    Ready,
+    /// running on a worker
+    Running,
+    /// waiting for an io event
+    ParkedOnIo,
+    /// sleeping
+    ParkedOnTimer,
+    /// waiting for a lock, a channel, a join or an explicit unpark
+    ParkedOnLock,
+    /// waiting for other events
+    Parked,
+}
+
+impl CoroutineState {
+    fn from_u8(v: u8) -> Self {
+        match v {
+            0 => CoroutineState::Ready,
+            1 => CoroutineState::Running,
+            2 => CoroutineState::ParkedOnIo,
+            3 => CoroutineState::ParkedOnTimer,
+            4 => CoroutineState::ParkedOnLock,
+            _ => CoroutineState::Parked,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            CoroutineState::Ready => "ready",
+            CoroutineState::Running => "running",
+            CoroutineState::ParkedOnIo => "parked_on_io",
+            CoroutineState::ParkedOnTimer => "parked_on_timer",
+            CoroutineState::ParkedOnLock => "parked_on_lock",
+            CoroutineState::Parked => "parked",
+        }
+    }
+}
+
+/// the tracked info of a coroutine when the task dump is enabled
+pub(crate) struct Trace {
+    state: AtomicU8,
+    worker: AtomicUsize,
+    spawned_at: SystemTime,
+}
+
+impl Trace {
+    pub fn new() -> Self {
+        Trace {
+            state: AtomicU8::new(CoroutineState::Ready as u8),
+            worker: AtomicUsize::new(usize::MAX),
+            spawned_at: SystemTime::now(),
+        }
+    }
+
+    #[inline]
+    pub fn set_state(&self, state: CoroutineState) {
+        self.state.store(state as u8, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn state(&self) -> CoroutineState {
+        CoroutineState::from_u8(self.state.load(Ordering::Relaxed))
+    }
+
+    /// the coroutine is about to be resumed by the current thread
+    #[inline]
+    pub fn resume(&self) {
+        self.worker.store(WORKER_ID.get(), Ordering::Relaxed);
+        self.set_state(CoroutineState::Running);
+    }
+
+    /// the coroutine yields without telling what it's waiting for
+    #[inline]
+    pub fn yielded(&self) {
+        if self.state() == CoroutineState::Running {
+            self.set_state(CoroutineState::Parked);
+        }
+    }
+}
+
+/// record what the current coroutine is about to wait for
+#[inline]
+pub(crate) fn set_state(state: CoroutineState) {
+    #[cfg(feature = "tracing")]
+    crate::span::parked(state.as_str());
+    if let Some(trace) = current_trace() {
+        trace.set_state(state);
+    }
+}
+
+/// The info of a live coroutine
+#[derive(Debug, Clone)]
+pub struct CoroutineInfo {
+    /// the unique id of the coroutine
+    pub id: CoroutineId,
+    /// the name of the coroutine
+    pub name: Option<String>,
+    /// the stack size of the coroutine
+    pub stack_size: usize,
+    /// the state of the coroutine, only tracked when the task dump is enabled
+    pub state: Option<CoroutineState>,
+    /// when the coroutine is spawned, only tracked when the task dump is enabled
+    pub spawned_at: Option<SystemTime>,
+    /// the worker that ran the coroutine last time, `None` if it's not run yet
+    /// or it's run by a thread that drives a current thread runtime
+    pub last_worker: Option<usize>,
+}
+
+impl CoroutineInfo {
+    fn new(co: &Coroutine) -> Self {
+        let trace = co.trace();
+        let worker = trace.map_or(usize::MAX, |t| t.worker.load(Ordering::Relaxed));
+        CoroutineInfo {
+            id: co.id(),
+            name: co.name().map(ToOwned::to_owned),
+            stack_size: co.stack_size(),
+            state: trace.map(Trace::state),
+            spawned_at: trace.map(|t| t.spawned_at),
+            last_worker: if worker == usize::MAX {
+                None
+            } else {
+                Some(worker)
+            },
+        }
+    }
+
+    // how long the coroutine has been alive
+    fn age(&self) -> Option<Duration> {
+        self.spawned_at
+            .map(|t| SystemTime::now().duration_since(t).unwrap_or_default())
+    }
+}
+
+impl fmt::Display for CoroutineInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "coroutine #{}", self.id)?;
+        if let Some(name) = self.name.as_ref() {
+            write!(f, " {name:?}")?;
+        }
+        if let Some(state) = self.state {
+            write!(f, " {}", state.as_str())?;
+        }
+        if let Some(worker) = self.last_worker {
+            write!(f, ", worker {worker}")?;
+        }
+        write!(f, ", stack {:#x}", self.stack_size)?;
+        if let Some(age) = self.age() {
+            write!(f, ", spawned {age:.3?} ago")?;
+        }
+        Ok(())
+    }
+}
+
+/// A snapshot of the live coroutines of all the runtimes
+///
+/// the `Display` output is human-readable, use [`Dump::to_json`] for a
+/// machine-readable one.
+#[derive(Debug, Clone, Default)]
+pub struct Dump {
+    /// the live coroutines sorted by id
+    pub coroutines: Vec<CoroutineInfo>,
+}
+
+impl Dump {
+    /// format the dump as a JSON array
+    pub fn to_json(&self) -> String {
+        let mut s = String::from("[");
+        for (i, co) in self.coroutines.iter().enumerate() {
+            if i > 0 {
+                s.push(',');
+            }
+            s.push_str(&format!("{{\"id\":{}", co.id.as_u64()));
+            s.push_str(",\"name\":");
+            match co.name.as_ref() {
+                Some(name) => push_json_str(&mut s, name),
+                None => s.push_str("null"),
+            }
+            s.push_str(&format!(",\"stack_size\":{}", co.stack_size));
+            s.push_str(",\"state\":");
+            match co.state {
+                Some(state) => push_json_str(&mut s, state.as_str()),
+                None => s.push_str("null"),
+            }
+            s.push_str(",\"spawned_at_ms\":");
+            match co.spawned_at {
+                Some(t) => {
+                    let ms = t.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+                    s.push_str(&ms.to_string())
+                }
+                None => s.push_str("null"),
+            }
+            s.push_str(",\"last_worker\":");
+            match co.last_worker {
+                Some(w) => s.push_str(&w.to_string()),
+                None => s.push_str("null"),
+            }
+            s.push('}');
+        }
+        s.push(']');
+        s
+    }
+}
+
+fn push_json_str(s: &mut String, v: &str) {
+    s.push('"');
+    for c in v.chars() {
+        match c {
+            '"' => s.push_str("\\\""),
+            '\\' => s.push_str("\\\\"),
+            '\n' => s.push_str("\\n"),
+            '\r' => s.push_str("\\r"),
+            '\t' => s.push_str("\\t"),
+            c if (c as u32) < 0x20 => s.push_str(&format!("\\u{:04x}", c as u32)),
+            c => s.push(c),
+        }
+    }
+    s.push('"');
+}
+
+impl fmt::Display for Dump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} live coroutines", self.coroutines.len())?;
+        for co in self.coroutines.iter() {
+            writeln!(f, "{co}")?;
+        }
+        Ok(())
+    }
+}
+
+/// take a snapshot of the live coroutines of all the runtimes
+///
+/// # Examples
+///
+/// ```rust
+/// may::config().set_task_dump(true);
+/// let h = may::go!(|| may::coroutine::park());
+/// println!("{}", may::coroutine::dump());
+/// println!("{}", may::coroutine::dump().to_json());
+/// h.coroutine().unpark();
+/// h.join().unwrap();
+/// ```
+pub fn dump() -> Dump {
+    let mut coroutines = Vec::new();
+    for_each_live_scheduler(|s| {
+        coroutines.extend(s.registry.snapshot().iter().map(CoroutineInfo::new));
+    });
+    coroutines.sort_by_key(|co| co.id);
+    Dump { coroutines }
+}
+
+#[cfg(unix)]
+mod signal {
+    use std::io;
+    use std::os::unix::io::RawFd;
+    use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
+    use std::sync::Once;
+    use std::thread;
+
+    // the write end of the pipe that wakes up the dump thread
+    static PIPE: AtomicI32 = AtomicI32::new(-1);
+    // bit mask of the signals that trigger the dump
+    static SIGNALS: AtomicU64 = AtomicU64::new(0);
+
+    pub fn is_dump_signal(signal: libc::c_int) -> bool {
+        (1..64).contains(&signal) && SIGNALS.load(Ordering::Acquire) & (1u64 << signal) != 0
+    }
+
+    extern "C" fn on_signal(_: libc::c_int) {
+        // only async signal safe calls are allowed here
+        let fd = PIPE.load(Ordering::Relaxed);
+        let b = 0u8;
+        unsafe { libc::write(fd, &b as *const u8 as *const libc::c_void, 1) };
+    }
+
+    fn start_dump_thread() -> io::Result<()> {
+        let mut fds = [0 as RawFd; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        PIPE.store(fds[1], Ordering::Relaxed);
+        let rd = fds[0];
+        thread::Builder::new()
+            .name("may-dump".to_owned())
+            .spawn(move || {
+                let mut b = 0u8;
+                loop {
+                    let n = unsafe { libc::read(rd, &mut b as *mut u8 as *mut libc::c_void, 1) };
+                    if n == 1 {
+                        eprint!("{}", super::dump());
+                    } else if n == 0
+                        || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted
+                    {
+                        break;
+                    }
+                }
+            })?;
+        Ok(())
+    }
+
+    pub fn dump_on_signal(signal: libc::c_int) -> io::Result<()> {
+        if !(1..64).contains(&signal) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid signal {signal}"),
+            ));
+        }
+        // don't steal the signal from `Signals`
+        if crate::os::unix::signal::is_registered(signal) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("signal {signal} is registered by Signals"),
+            ));
+        }
+
+        static START: Once = Once::new();
+        let mut ret = Ok(());
+        START.call_once(|| ret = start_dump_thread());
+        ret?;
+        if PIPE.load(Ordering::Relaxed) < 0 {
+            return Err(io::Error::other("failed to start the dump thread"));
+        }
+
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            if libc::sigaction(signal, &action, std::ptr::null_mut()) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        SIGNALS.fetch_or(1u64 << signal, Ordering::AcqRel);
+        Ok(())
+    }
+}
+
+/// print the [`dump`] to stderr each time the process receives the signal
+///
+/// the dump is printed by a dedicated thread so it works even if all the
+/// workers are blocked. the previous handler of the signal is replaced, so
+/// it returns an `InvalidInput` error if the signal is already registered
+/// by [`Signals`], and `Signals` would not accept the signal afterwards.
+///
+/// [`Signals`]: ../os/unix/signal/struct.Signals.html
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// may::coroutine::dump_on_signal(libc::SIGUSR1).unwrap();
+/// ```
+#[cfg(unix)]
+pub fn dump_on_signal(signal: i32) -> std::io::Result<()> {
+    signal::dump_on_signal(signal)
+}
+
+#[cfg(unix)]
+pub(crate) use self::signal::is_dump_signal;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coroutine::Builder;
+    #[cfg(feature = "io_cancel")]
+    use crate::net::TcpListener;
+    use crate::sync::mpsc::channel;
+    use crate::RuntimeBuilder;
+
+    fn find(name: &str) -> Option<CoroutineInfo> {
+        dump()
+            .coroutines
+            .into_iter()
+            .find(|co| co.name.as_deref() == Some(name))
+    }
+
+    fn wait_state(name: &str, state: CoroutineState) -> CoroutineInfo {
+        for _ in 0..1000 {
+            if let Some(co) = find(name).filter(|co| co.state == Some(state)) {
+                return co;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        panic!("{name} is not {state:?}: {:?}", find(name));
+    }
+
+    #[test]
+    fn dump_states() {
+        let rt = RuntimeBuilder::new().task_dump(true).build().unwrap();
+        let _guard = rt.enter();
+        let spawn = |name: &str, f: fn()| unsafe {
+            Builder::new()
+                .name(name.to_owned())
+                .stack_size(0x2000)
+                .spawn(f)
+                .unwrap()
+        };
+
+        let timer = spawn("dump-timer", || {
+            crate::coroutine::sleep(Duration::from_secs(1000))
+        });
+        let lock = spawn("dump-lock", || {
+            let (_tx, rx) = channel::<()>();
+            rx.recv().ok();
+        });
+        // the io can only be canceled with io_cancel
+        #[cfg(feature = "io_cancel")]
+        let io = spawn("dump-io", || {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            listener.accept().ok();
+        });
+        let running = spawn("dump-running", || {
+            let co = find("dump-running").unwrap();
+            assert_eq!(co.state, Some(CoroutineState::Running));
+        });
+        running.join().unwrap();
+
+        let co = wait_state("dump-timer", CoroutineState::ParkedOnTimer);
+        assert_eq!(co.stack_size, 0x2000);
+        assert_eq!(co.id, timer.id());
+        assert!(co.spawned_at.is_some());
+        assert!(co.last_worker.is_some());
+        wait_state("dump-lock", CoroutineState::ParkedOnLock);
+
+        let d = dump();
+        assert!(d.to_string().contains("\"dump-timer\" parked_on_timer"));
+        assert!(d
+            .to_json()
+            .contains("\"name\":\"dump-lock\",\"stack_size\":8192,\"state\":\"parked_on_lock\""));
+
+        #[cfg(feature = "io_cancel")]
+        {
+            wait_state("dump-io", CoroutineState::ParkedOnIo);
+            assert!(dump().to_string().contains("\"dump-io\" parked_on_io"));
+            unsafe { io.coroutine().cancel() };
+            assert!(io.join().is_err());
+        }
+        for h in [timer, lock] {
+            unsafe { h.coroutine().cancel() };
+            assert!(h.join().is_err());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn dump_signal() {
+        use crate::os::unix::signal::Signals;
+
+        // the signal is only used by this test
+        let sig = libc::SIGWINCH;
+        dump_on_signal(sig).unwrap();
+        dump_on_signal(sig).unwrap();
+        assert!(Signals::new([sig]).is_err());
+        let _signals = Signals::new([libc::SIGURG]).unwrap();
+        assert!(dump_on_signal(libc::SIGURG).is_err());
+    }
+
+    #[test]
+    fn json_escape() {
+        let mut s = String::new();
+        push_json_str(&mut s, "a\"b\\c\n\u{1}");
+        assert_eq!(s, r#""a\"b\\c\n\u0001""#);
+    }
+}
@@ -0,0 +1,430 @@
+//! Filesystem manipulation operations
+//!
+//! this is the coroutine version of `std::fs`. the regular files can't be
+//! polled by the io event loop, so all the operations are offloaded to the
+//! blocking pool when called in coroutine context, the calling coroutine is
+//! parked instead of the worker thread. in thread context they are just the
+//! same as the `std::fs` ones
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::vec;
+
+use crate::blocking_pool::run;
+
+pub use std::fs::{DirEntry, FileType, Metadata, Permissions};
+
+/// A reference to an open file on the filesystem
+///
+/// # Examples
+///
+/// ```rust
+/// use std::io::{Read, Write};
+/// use may::fs::File;
+///
+/// let dir = std::env::temp_dir().join("may_fs_doc");
+/// may::fs::create_dir_all(&dir).unwrap();
+/// let path = dir.join("foo.txt");
+///
+/// let h = may::go!(move || {
+///     let mut f = File::create(&path).unwrap();
+///     f.write_all(b"hello").unwrap();
+///
+///     let mut s = String::new();
+///     File::open(&path).unwrap().read_to_string(&mut s).unwrap();
+///     s
+/// });
+/// assert_eq!(h.join().unwrap(), "hello");
+/// ```
+pub struct File {
+    sys: fs::File,
+}
+
+impl File {
+    /// Attempts to open a file in read-only mode
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<File> {
+        let path = path.as_ref();
+        run(|| fs::File::open(path)).map(File::from_std)
+    }
+
+    /// Opens a file in write-only mode, create or truncate it
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<File> {
+        let path = path.as_ref();
+        run(|| fs::File::create(path)).map(File::from_std)
+    }
+
+    /// Convert a `std::fs::File` to the coroutine version
+    pub fn from_std(sys: fs::File) -> File {
+        File { sys }
+    }
+
+    /// Return the inner `std::fs::File`
+    pub fn into_std(self) -> fs::File {
+        self.sys
+    }
+
+    /// Get a reference to the inner `std::fs::File`
+    pub fn inner(&self) -> &fs::File {
+        &self.sys
+    }
+
+    /// Attempts to sync all OS-internal metadata to disk
+    pub fn sync_all(&self) -> io::Result<()> {
+        run(|| self.sys.sync_all())
+    }
+
+    /// Attempts to sync the file content to disk without the metadata
+    pub fn sync_data(&self) -> io::Result<()> {
+        run(|| self.sys.sync_data())
+    }
+
+    /// Truncates or extends the underlying file
+    pub fn set_len(&self, size: u64) -> io::Result<()> {
+        run(|| self.sys.set_len(size))
+    }
+
+    /// Queries metadata about the underlying file
+    pub fn metadata(&self) -> io::Result<Metadata> {
+        run(|| self.sys.metadata())
+    }
+
+    /// Creates a new `File` that shares the same underlying file handle
+    pub fn try_clone(&self) -> io::Result<File> {
+        self.sys.try_clone().map(File::from_std)
+    }
+
+    /// Changes the permissions on the underlying file
+    pub fn set_permissions(&self, perm: Permissions) -> io::Result<()> {
+        run(|| self.sys.set_permissions(perm))
+    }
+}
+
+impl fmt::Debug for File {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.sys.fmt(f)
+    }
+}
+
+impl From<fs::File> for File {
+    fn from(sys: fs::File) -> Self {
+        File::from_std(sys)
+    }
+}
+
+impl Read for File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        (&*self).read(buf)
+    }
+}
+
+impl Write for File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (&*self).write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        (&*self).flush()
+    }
+}
+
+impl Seek for File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        (&*self).seek(pos)
+    }
+}
+
+impl Read for &File {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut f = &self.sys;
+        run(move || f.read(buf))
+    }
+
+    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
+        let mut f = &self.sys;
+        run(move || f.read_to_end(buf))
+    }
+
+    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
+        let mut f = &self.sys;
+        run(move || f.read_to_string(buf))
+    }
+}
+
+impl Write for &File {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut f = &self.sys;
+        run(move || f.write(buf))
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        let mut f = &self.sys;
+        run(move || f.write_all(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // there is no user space buffer in file
+        Ok(())
+    }
+}
+
+impl Seek for &File {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let mut f = &self.sys;
+        run(move || f.seek(pos))
+    }
+}
+
+#[cfg(unix)]
+impl std::os::unix::io::AsRawFd for File {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.sys.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl std::os::windows::io::AsRawHandle for File {
+    fn as_raw_handle(&self) -> std::os::windows::io::RawHandle {
+        self.sys.as_raw_handle()
+    }
+}
+
+/// Options and flags which can be used to configure how a file is opened
+///
+/// the same as `std::fs::OpenOptions`, except that `open` returns the
+/// coroutine version `File`
+#[derive(Clone, Debug)]
+pub struct OpenOptions(fs::OpenOptions);
+
+impl OpenOptions {
+    /// Creates a blank new set of options
+    pub fn new() -> Self {
+        OpenOptions(fs::OpenOptions::new())
+    }
+
+    /// Sets the option for read access
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.0.read(read);
+        self
+    }
+
+    /// Sets the option for write access
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.0.write(write);
+        self
+    }
+
+    /// Sets the option for the append mode
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.0.append(append);
+        self
+    }
+
+    /// Sets the option for truncating a previous file
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.0.truncate(truncate);
+        self
+    }
+
+    /// Sets the option to create a new file, or open it if it already exists
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.0.create(create);
+        self
+    }
+
+    /// Sets the option to create a new file, failing if it already exists
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.0.create_new(create_new);
+        self
+    }
+
+    /// Opens a file at `path` with the options specified by `self`
+    pub fn open<P: AsRef<Path>>(&self, path: P) -> io::Result<File> {
+        let path = path.as_ref();
+        run(|| self.0.open(path)).map(File::from_std)
+    }
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        OpenOptions::new()
+    }
+}
+
+/// Iterator over the entries in a directory
+///
+/// the entries are read all at once by `read_dir`
+#[derive(Debug)]
+pub struct ReadDir(vec::IntoIter<io::Result<DirEntry>>);
+
+impl Iterator for ReadDir {
+    type Item = io::Result<DirEntry>;
+
+    fn next(&mut self) -> Option<io::Result<DirEntry>> {
+        self.0.next()
+    }
+}
+
+/// Returns an iterator over the entries within a directory
+pub fn read_dir<P: AsRef<Path>>(path: P) -> io::Result<ReadDir> {
+    let path = path.as_ref();
+    run(|| fs::read_dir(path).map(|d| ReadDir(d.collect::<Vec<_>>().into_iter())))
+}
+
+/// Read the entire contents of a file into a bytes vector
+pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let path = path.as_ref();
+    run(|| fs::read(path))
+}
+
+/// Read the entire contents of a file into a string
+pub fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    let path = path.as_ref();
+    run(|| fs::read_to_string(path))
+}
+
+/// Write a slice as the entire contents of a file
+pub fn write<P: AsRef<Path>, C: AsRef<[u8]>>(path: P, contents: C) -> io::Result<()> {
+    let path = path.as_ref();
+    let contents = contents.as_ref();
+    run(|| fs::write(path, contents))
+}
+
+/// Given a path, query the file system to get information about a file
+pub fn metadata<P: AsRef<Path>>(path: P) -> io::Result<Metadata> {
+    let path = path.as_ref();
+    run(|| fs::metadata(path))
+}
+
+/// Query the metadata about a file without following symlinks
+pub fn symlink_metadata<P: AsRef<Path>>(path: P) -> io::Result<Metadata> {
+    let path = path.as_ref();
+    run(|| fs::symlink_metadata(path))
+}
+
+/// Returns `Ok(true)` if the path points at an existing entity
+pub fn try_exists<P: AsRef<Path>>(path: P) -> io::Result<bool> {
+    let path = path.as_ref();
+    run(|| path.try_exists())
+}
+
+/// Creates a new, empty directory at the provided path
+pub fn create_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    run(|| fs::create_dir(path))
+}
+
+/// Recursively create a directory and all of its parent components
+pub fn create_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    run(|| fs::create_dir_all(path))
+}
+
+/// Removes an empty directory
+pub fn remove_dir<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    run(|| fs::remove_dir(path))
+}
+
+/// Removes a directory at this path, after removing all its contents
+pub fn remove_dir_all<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    run(|| fs::remove_dir_all(path))
+}
+
+/// Removes a file from the filesystem
+pub fn remove_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    run(|| fs::remove_file(path))
+}
+
+/// Rename a file or directory to a new name
+pub fn rename<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<()> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    run(|| fs::rename(from, to))
+}
+
+/// Copies the contents of one file to another
+pub fn copy<P: AsRef<Path>, Q: AsRef<Path>>(from: P, to: Q) -> io::Result<u64> {
+    let (from, to) = (from.as_ref(), to.as_ref());
+    run(|| fs::copy(from, to))
+}
+
+/// Creates a new hard link on the filesystem
+pub fn hard_link<P: AsRef<Path>, Q: AsRef<Path>>(original: P, link: Q) -> io::Result<()> {
+    let (original, link) = (original.as_ref(), link.as_ref());
+    run(|| fs::hard_link(original, link))
+}
+
+/// Reads a symbolic link, returning the file that the link points to
+pub fn read_link<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
+    let path = path.as_ref();
+    run(|| fs::read_link(path))
+}
+
+/// Returns the canonical, absolute form of a path
+pub fn canonicalize<P: AsRef<Path>>(path: P) -> io::Result<PathBuf> {
+    let path = path.as_ref();
+    run(|| fs::canonicalize(path))
+}
+
+/// Changes the permissions found on a file or a directory
+pub fn set_permissions<P: AsRef<Path>>(path: P, perm: Permissions) -> io::Result<()> {
+    let path = path.as_ref();
+    run(|| fs::set_permissions(path, perm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_read_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        let h = go!(move || {
+            let mut f = File::create(&path).unwrap();
+            f.write_all(b"hello world").unwrap();
+            f.sync_all().unwrap();
+            assert_eq!(f.metadata().unwrap().len(), 11);
+
+            let mut f = OpenOptions::new().read(true).open(&path).unwrap();
+            f.seek(SeekFrom::Start(6)).unwrap();
+            let mut s = String::new();
+            f.read_to_string(&mut s).unwrap();
+            assert_eq!(s, "world");
+
+            let mut f = OpenOptions::new().append(true).open(&path).unwrap();
+            f.write_all(b"!").unwrap();
+            read_to_string(&path).unwrap()
+        });
+        assert_eq!(h.join().unwrap(), "hello world!");
+    }
+
+    #[test]
+    fn dir_ops() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().to_owned();
+        let h = go!(move || {
+            let sub = root.join("a/b");
+            create_dir_all(&sub).unwrap();
+            write(sub.join("x"), b"1").unwrap();
+            copy(sub.join("x"), sub.join("y")).unwrap();
+            rename(sub.join("y"), sub.join("z")).unwrap();
+            assert!(metadata(&sub).unwrap().is_dir());
+
+            let mut names: Vec<_> = read_dir(&sub)
+                .unwrap()
+                .map(|e| e.unwrap().file_name().into_string().unwrap())
+                .collect();
+            names.sort();
+            assert_eq!(names, vec!["x", "z"]);
+
+            remove_file(sub.join("x")).unwrap();
+            assert!(!try_exists(sub.join("x")).unwrap());
+            remove_dir_all(root.join("a")).unwrap();
+            try_exists(root.join("a")).unwrap()
+        });
+        assert!(!h.join().unwrap());
+    }
+}
@@ -1,7 +1,10 @@
 use std::io;
+use std::time::Instant;
 
 use super::sys::{Selector, SysEvent};
-use crate::scheduler::{get_scheduler, WORKER_ID};
+use crate::metrics::Stats;
+use crate::overflow;
+use crate::scheduler::{Scheduler, WORKER_ID, WORKER_SCHED};
 
 const IO_POLLS_MAX: usize = 1024;
 
@@ -15,30 +18,59 @@ impl EventLoop {
         Selector::new(io_workers).map(|selector| EventLoop { selector })
     }
 
-    /// Keep spinning the event loop indefinitely, and notify the handler whenever
-    /// any of the registered handles are ready.
-    pub fn run(&self, id: usize) {
-        WORKER_ID.set(id);
+    /// Keep spinning the event loop until the scheduler is stopped, and notify
+    /// the handler whenever any of the registered handles are ready.
+    pub fn run(&self, scheduler: &Scheduler, id: usize) {
+        self.run_until(scheduler, id, None, || scheduler.is_stopped());
+    }
+
+    /// Keep spinning the event loop in the current thread until `stop` returns true
+    ///
+    /// `stop` is checked at least once the `deadline` is reached
+    pub fn run_until<F: Fn() -> bool>(
+        &self,
+        scheduler: &Scheduler,
+        id: usize,
+        deadline: Option<Instant>,
+        stop: F,
+    ) {
+        let prev_id = WORKER_ID.replace(id);
+        let prev_sched = WORKER_SCHED.replace(scheduler);
+        // the coroutines need it to report stack overflow
+        overflow::init_thread();
 
         let mut events_buf: [SysEvent; IO_POLLS_MAX] = unsafe { std::mem::zeroed() };
-        let mut next_expire = None;
         let selector = &self.selector;
-        let scheduler = get_scheduler();
 
+        // without the io timers the selector waits until an event comes
         #[cfg(feature = "io_timeout")]
-        let timeout_ns = crate::config().get_timeout_ns();
+        let timeout_ns = Some(crate::config().get_timeout_ns());
         #[cfg(not(feature = "io_timeout"))]
-        let timeout_ns = 1_000_000_000; // 1s
+        let timeout_ns: Option<u64> = None;
+        let mut next_expire = timeout_ns;
 
-        loop {
-            next_expire = match selector.select(scheduler, id, &mut events_buf, next_expire) {
-                Ok(t) => t.or(Some(timeout_ns)),
+        let stats = scheduler.stats.worker(id);
+        while !stop() {
+            Stats::inc(&stats.poll_wakeups);
+            // don't wait past the deadline
+            let timeout = match deadline {
+                Some(d) => {
+                    let left = d.saturating_duration_since(Instant::now()).as_nanos() as u64;
+                    Some(next_expire.map_or(left, |t| t.min(left)))
+                }
+                None => next_expire,
+            };
+            next_expire = match selector.select(scheduler, id, &mut events_buf, timeout) {
+                Ok(t) => t.or(timeout_ns),
                 Err(e) => {
                     error!("select error = {:?}", e);
                     continue;
                 }
             }
         }
+
+        WORKER_ID.set(prev_id);
+        WORKER_SCHED.set(prev_sched);
     }
 
     // get the internal selector
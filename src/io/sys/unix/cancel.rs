@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use super::EventData;
 use crate::cancel::CancelIo;
-use crate::scheduler::get_scheduler;
+use crate::scheduler::schedule;
 use crate::sync::AtomicOption;
 
 pub struct CancelIoImpl(AtomicOption<Arc<EventData>>);
@@ -24,8 +24,13 @@ impl CancelIo for CancelIoImpl {
 
     unsafe fn cancel(&self) -> Option<std::io::Result<()>> {
         if let Some(e) = self.0.take() {
+            // the coroutine is resumed by the completion of the request
+            #[cfg(all(feature = "io_uring", target_os = "linux"))]
+            if e.req.cancel() {
+                return Some(Ok(()));
+            }
             if let Some(co) = e.co.take() {
-                get_scheduler().schedule(co);
+                schedule(co);
                 return Some(Ok(()));
             }
         }
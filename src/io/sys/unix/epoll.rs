@@ -12,7 +12,8 @@ use super::{from_nix_error, EventData, IoData};
 use super::{timeout_handler, TimerList};
 use crate::scheduler::Scheduler;
 #[cfg(feature = "io_timeout")]
-use crate::timeout_list::{now, ns_to_ms};
+use crate::timeout_list::now;
+use crate::timeout_list::ns_to_ms;
 
 use may_queue::mpsc::Queue;
 use nix::sys::epoll::*;
@@ -76,14 +77,11 @@ impl Selector {
         scheduler: &Scheduler,
         id: usize,
         events: &mut [SysEvent],
-        _timeout: Option<u64>,
+        timeout: Option<u64>,
     ) -> io::Result<Option<u64>> {
-        #[cfg(feature = "io_timeout")]
-        let timeout_ms = _timeout
-            .map(|to| EpollTimeout::try_from(ns_to_ms(to)).unwrap())
+        let timeout_ms = timeout
+            .map(|to| EpollTimeout::try_from(ns_to_ms(to)).unwrap_or(EpollTimeout::MAX))
             .unwrap_or(EpollTimeout::NONE);
-        #[cfg(not(feature = "io_timeout"))]
-        let timeout_ms = EpollTimeout::NONE;
         // info!("select; timeout={:?}", timeout_ms);
 
         let single_selector = unsafe { self.vec.get_unchecked(id) };
@@ -108,6 +106,7 @@ impl Selector {
             let events = event.events().bits() as usize;
             // info!("select got event, data={:p}, events={}", data, events);
             data.io_flag.fetch_or(events, Ordering::Release);
+            data.wake_watcher();
 
             // first check the atomic co, this may be grab by the worker first
             let co = match data.co.take() {
@@ -129,7 +128,7 @@ impl Selector {
             #[cfg(feature = "work_steal")]
             scheduler.schedule_with_id(co, id);
             #[cfg(not(feature = "work_steal"))]
-            crate::coroutine_impl::run_coroutine(co);
+            scheduler.run_local(co);
         }
 
         // run all the local tasks
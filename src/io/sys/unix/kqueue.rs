@@ -10,7 +10,8 @@ use super::{timeout_handler, TimerList};
 use super::{EventData, IoData};
 use crate::scheduler::Scheduler;
 #[cfg(feature = "io_timeout")]
-use crate::timeout_list::{now, ns_to_dur};
+use crate::timeout_list::now;
+use crate::timeout_list::ns_to_dur;
 
 use may_queue::mpsc::Queue;
 use smallvec::SmallVec;
@@ -102,10 +103,9 @@ impl Selector {
         scheduler: &Scheduler,
         id: usize,
         events: &mut [SysEvent],
-        _timeout: Option<u64>,
+        timeout: Option<u64>,
     ) -> io::Result<Option<u64>> {
-        #[cfg(feature = "io_timeout")]
-        let timeout_spec = _timeout.map(|to| {
+        let timeout_spec = timeout.map(|to| {
             let dur = ns_to_dur(to);
             libc::timespec {
                 tv_sec: dur.as_secs() as libc::time_t,
@@ -113,13 +113,10 @@ impl Selector {
             }
         });
 
-        #[cfg(feature = "io_timeout")]
         let timeout = timeout_spec
             .as_ref()
             .map(|s| s as *const _)
             .unwrap_or(ptr::null_mut());
-        #[cfg(not(feature = "io_timeout"))]
-        let timeout = ptr::null_mut();
         // info!("select; timeout={:?}", timeout_ms);
 
         let single_selector = unsafe { self.vec.get_unchecked(id) };
@@ -157,6 +154,7 @@ impl Selector {
             // info!("select got event, data={:p}", data);
             data.io_flag
                 .fetch_or(event.flags as usize, Ordering::Release);
+            data.wake_watcher();
 
             // first check the atomic co, this may be grab by the worker first
             let co = match data.co.take() {
@@ -178,7 +176,7 @@ impl Selector {
             #[cfg(feature = "work_steal")]
             scheduler.schedule_with_id(co, id);
             #[cfg(not(feature = "work_steal"))]
-            crate::coroutine_impl::run_coroutine(co);
+            scheduler.run_local(co);
         }
 
         // run all the local tasks
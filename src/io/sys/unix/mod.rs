@@ -1,7 +1,18 @@
-#[cfg(any(target_os = "linux", target_os = "android"))]
+#[cfg(all(
+    any(target_os = "linux", target_os = "android"),
+    not(all(feature = "io_uring", target_os = "linux"))
+))]
 #[path = "epoll.rs"]
 mod select;
 
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+mod epoll;
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+#[path = "uring.rs"]
+mod select;
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+use self::select as uring;
+
 #[cfg(any(
     target_os = "dragonfly",
     target_os = "freebsd",
@@ -31,7 +42,8 @@ use std::{fmt, io};
 use crate::coroutine_impl::{run_coroutine, CoroutineImpl};
 use crate::io::thread::ASSOCIATED_IO_RET;
 use crate::likely::likely;
-use crate::scheduler::get_scheduler;
+use crate::scheduler::{current_scheduler, schedule, Scheduler};
+use crate::select::Watchers;
 use crate::sync::AtomicOption;
 #[cfg(feature = "io_timeout")]
 use crate::timeout_list::{TimeOutList, TimeoutHandle};
@@ -43,18 +55,29 @@ pub use self::select::{Selector, SysEvent};
 
 #[inline]
 pub fn add_socket<T: AsRawFd + ?Sized>(t: &T) -> io::Result<IoData> {
-    get_scheduler().get_selector().add_fd(IoData::new(t))
+    // the io is pinned to the runtime of the current context
+    let s = current_scheduler();
+    let io = s.get_selector().add_fd(IoData::new(t, s.clone()))?;
+    s.stats.registered_fds.fetch_add(1, Ordering::Relaxed);
+    Ok(io)
 }
 
 #[inline]
 pub fn mod_socket(io: &IoData, is_read: bool) -> io::Result<()> {
-    get_scheduler().get_selector().mod_fd(io, is_read)
+    if io.sched.is_stopped() {
+        return Err(io::Error::other("the runtime of the io is shutdown"));
+    }
+    io.selector().mod_fd(io, is_read)
 }
 
 #[inline]
 fn del_socket(io: &IoData) {
     // transfer the io to the selector
-    get_scheduler().get_selector().del_fd(io);
+    io.selector().del_fd(io);
+    io.sched
+        .stats
+        .registered_fds
+        .fetch_sub(1, Ordering::Relaxed);
 }
 
 // deal with the io result
@@ -120,6 +143,11 @@ pub struct EventData {
     #[cfg(feature = "io_timeout")]
     pub timer: RefCell<Option<TimerHandle>>,
     pub co: AtomicOption<CoroutineImpl>,
+    // the selects that are watching the io
+    pub watchers: Watchers,
+    // the completion request of the io
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    pub req: uring::Request,
 }
 
 unsafe impl Send for EventData {}
@@ -133,6 +161,9 @@ impl EventData {
             #[cfg(feature = "io_timeout")]
             timer: RefCell::new(None),
             co: AtomicOption::none(),
+            watchers: Watchers::new(),
+            #[cfg(all(feature = "io_uring", target_os = "linux"))]
+            req: uring::Request::new(),
         }
     }
 
@@ -143,6 +174,12 @@ impl EventData {
         }
     }
 
+    /// wake up the selects that are watching the io
+    #[inline]
+    pub fn wake_watcher(&self) {
+        self.watchers.wakeup();
+    }
+
     #[inline]
     pub fn schedule(&self) {
         let co = match self.co.take() {
@@ -162,7 +199,7 @@ impl EventData {
         });
 
         // schedule the coroutine
-        get_scheduler().schedule(co);
+        schedule(co);
     }
 
     /// used by local re-schedule that in `subscribe`
@@ -190,13 +227,23 @@ impl EventData {
 }
 
 // each file associated data
-pub struct IoData(Arc<EventData>);
+pub struct IoData {
+    data: Arc<EventData>,
+    // the scheduler that the io is registered to, it's kept alive by the io
+    sched: Arc<Scheduler>,
+}
 
 impl IoData {
-    pub fn new<T: AsRawFd + ?Sized>(t: &T) -> Self {
+    pub fn new<T: AsRawFd + ?Sized>(t: &T, sched: Arc<Scheduler>) -> Self {
         let fd = t.as_raw_fd();
-        let event_data = Arc::new(EventData::new(fd));
-        IoData(event_data)
+        let data = Arc::new(EventData::new(fd));
+        IoData { data, sched }
+    }
+
+    /// the selector that the io is registered to
+    #[inline]
+    pub fn selector(&self) -> &Selector {
+        self.sched.get_selector()
     }
 
     // clear the io flag
@@ -210,7 +257,7 @@ impl Deref for IoData {
     type Target = Arc<EventData>;
 
     fn deref(&self) -> &Arc<EventData> {
-        &self.0
+        &self.data
     }
 }
 
@@ -228,8 +275,9 @@ impl Drop for IoData {
 
 impl AsFd for IoData {
     fn as_fd(&self) -> BorrowedFd<'_> {
-        unsafe { BorrowedFd::borrow_raw(self.0.fd) }
+        unsafe { BorrowedFd::borrow_raw(self.data.fd) }
     }
 }
 
 unsafe impl Send for IoData {}
+unsafe impl Sync for IoData {}
@@ -72,9 +72,7 @@ impl<'a> EventSource for SocketPeek<'a> {
 
         #[cfg(feature = "io_timeout")]
         if let Some(dur) = self.timeout {
-            crate::scheduler::get_scheduler()
-                .get_selector()
-                .add_io_timer(self.io_data, dur);
+            self.io_data.selector().add_io_timer(self.io_data, dur);
         }
 
         // after register the coroutine, it's possible that other thread run it immediately
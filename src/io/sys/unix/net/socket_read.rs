@@ -3,12 +3,16 @@ use std::sync::atomic::Ordering;
 #[cfg(feature = "io_timeout")]
 use std::time::Duration;
 
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+use super::super::uring;
 use super::super::{co_io_result, from_nix_error, IoData};
 #[cfg(feature = "io_cancel")]
 use crate::coroutine_impl::co_cancel_data;
 use crate::coroutine_impl::{is_coroutine, CoroutineImpl, EventSource};
 use crate::io::AsIoData;
 use crate::yield_now::yield_with_io;
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+use io_uring::{opcode, types};
 use nix::unistd::read;
 
 pub struct SocketRead<'a> {
@@ -16,6 +20,9 @@ pub struct SocketRead<'a> {
     buf: &'a mut [u8],
     #[cfg(feature = "io_timeout")]
     timeout: Option<Duration>,
+    // submit the read to io_uring instead of waiting for the readiness
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    uring: bool,
     pub(crate) is_coroutine: bool,
 }
 
@@ -25,16 +32,30 @@ impl<'a> SocketRead<'a> {
         buf: &'a mut [u8],
         #[cfg(feature = "io_timeout")] timeout: Option<Duration>,
     ) -> Self {
+        let io_data = s.as_io_data();
+        let is_coroutine = is_coroutine();
         SocketRead {
-            io_data: s.as_io_data(),
+            io_data,
             buf,
             #[cfg(feature = "io_timeout")]
             timeout,
-            is_coroutine: is_coroutine(),
+            #[cfg(all(feature = "io_uring", target_os = "linux"))]
+            uring: is_coroutine && io_data.selector().is_uring(),
+            is_coroutine,
         }
     }
 
     pub fn done(&mut self) -> io::Result<usize> {
+        #[cfg(all(feature = "io_uring", target_os = "linux"))]
+        if self.uring {
+            // wait for the readiness if the request would block
+            self.uring = false;
+            match uring::result(self.io_data) {
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                ret => return ret,
+            }
+        }
+
         loop {
             co_io_result(self.is_coroutine)?;
 
@@ -65,15 +86,28 @@ impl<'a> SocketRead<'a> {
 
 impl<'a> EventSource for SocketRead<'a> {
     fn subscribe(&mut self, co: CoroutineImpl) {
+        #[cfg(all(feature = "io_uring", target_os = "linux"))]
+        if self.uring {
+            let len = self.buf.len().min(u32::MAX as usize) as u32;
+            let sqe = opcode::Read::new(types::Fd(self.io_data.fd), self.buf.as_mut_ptr(), len)
+                .offset(u64::MAX)
+                .build();
+            return uring::submit(
+                self.io_data,
+                sqe,
+                #[cfg(feature = "io_timeout")]
+                self.timeout,
+                co,
+            );
+        }
+
         #[cfg(feature = "io_cancel")]
         let cancel = co_cancel_data(&co);
         let io_data = self.io_data;
 
         #[cfg(feature = "io_timeout")]
         if let Some(dur) = self.timeout {
-            crate::scheduler::get_scheduler()
-                .get_selector()
-                .add_io_timer(self.io_data, dur);
+            self.io_data.selector().add_io_timer(self.io_data, dur);
         }
 
         // after register the coroutine, it's possible that other thread run it immediately
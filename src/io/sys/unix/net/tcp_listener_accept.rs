@@ -1,7 +1,11 @@
 use std::io;
 use std::net::SocketAddr;
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+use std::os::unix::io::FromRawFd;
 use std::sync::atomic::Ordering;
 
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+use super::super::uring;
 use super::super::{add_socket, co_io_result, IoData};
 #[cfg(feature = "io_cancel")]
 use crate::coroutine_impl::co_cancel_data;
@@ -9,23 +13,59 @@ use crate::coroutine_impl::{is_coroutine, CoroutineImpl, EventSource};
 use crate::io::AsIoData;
 use crate::net::{TcpListener, TcpStream};
 use crate::yield_now::yield_with_io;
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+use io_uring::{opcode, types};
 
 pub struct TcpListenerAccept<'a> {
     io_data: &'a IoData,
     socket: &'a std::net::TcpListener,
+    // submit the accept to io_uring instead of waiting for the readiness
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    uring: bool,
+    // the peer address written by the accept request
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    addr: libc::sockaddr_storage,
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    addr_len: libc::socklen_t,
     pub(crate) is_coroutine: bool,
 }
 
 impl<'a> TcpListenerAccept<'a> {
     pub fn new(socket: &'a TcpListener) -> io::Result<Self> {
+        let io_data = socket.as_io_data();
+        let is_coroutine = is_coroutine();
         Ok(TcpListenerAccept {
-            io_data: socket.as_io_data(),
+            io_data,
             socket: socket.inner(),
-            is_coroutine: is_coroutine(),
+            #[cfg(all(feature = "io_uring", target_os = "linux"))]
+            uring: is_coroutine && io_data.selector().is_uring(),
+            #[cfg(all(feature = "io_uring", target_os = "linux"))]
+            addr: unsafe { std::mem::zeroed() },
+            #[cfg(all(feature = "io_uring", target_os = "linux"))]
+            addr_len: 0,
+            is_coroutine,
         })
     }
 
     pub fn done(&mut self) -> io::Result<(TcpStream, SocketAddr)> {
+        #[cfg(all(feature = "io_uring", target_os = "linux"))]
+        if self.uring {
+            // wait for the readiness if the request would block
+            self.uring = false;
+            match uring::result(self.io_data) {
+                Ok(fd) => {
+                    let s = unsafe { std::net::TcpStream::from_raw_fd(fd as i32) };
+                    let addr = unsafe { socket2::SockAddr::new(self.addr, self.addr_len) };
+                    let a = addr.as_socket().ok_or_else(|| {
+                        io::Error::new(io::ErrorKind::InvalidData, "invalid peer address")
+                    })?;
+                    return add_socket(&s).map(|io| (TcpStream::from_stream(s, io), a));
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        }
+
         loop {
             co_io_result(self.is_coroutine)?;
 
@@ -60,6 +100,22 @@ impl<'a> TcpListenerAccept<'a> {
 
 impl<'a> EventSource for TcpListenerAccept<'a> {
     fn subscribe(&mut self, co: CoroutineImpl) {
+        #[cfg(all(feature = "io_uring", target_os = "linux"))]
+        if self.uring {
+            self.addr_len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+            let addr = &mut self.addr as *mut _ as *mut libc::sockaddr;
+            let sqe = opcode::Accept::new(types::Fd(self.io_data.fd), addr, &mut self.addr_len)
+                .flags(libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK)
+                .build();
+            return uring::submit(
+                self.io_data,
+                sqe,
+                #[cfg(feature = "io_timeout")]
+                None,
+                co,
+            );
+        }
+
         #[cfg(feature = "io_cancel")]
         let cancel = co_cancel_data(&co);
         let io_data = self.io_data;
@@ -4,6 +4,8 @@ use std::sync::atomic::Ordering;
 #[cfg(feature = "io_timeout")]
 use std::time::Duration;
 
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+use super::super::uring;
 use super::super::{add_socket, co_io_result, IoData};
 #[cfg(feature = "io_cancel")]
 use crate::coroutine_impl::co_cancel_data;
@@ -11,6 +13,8 @@ use crate::coroutine_impl::{is_coroutine, CoroutineImpl, EventSource};
 use crate::io::OptionCell;
 use crate::net::TcpStream;
 use crate::yield_now::yield_with_io;
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+use io_uring::{opcode, types};
 use socket2::Socket;
 
 pub struct TcpStreamConnect {
@@ -19,6 +23,12 @@ pub struct TcpStreamConnect {
     #[cfg(feature = "io_timeout")]
     timeout: Option<Duration>,
     addr: SocketAddr,
+    // the address read by the connect request
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    sock_addr: socket2::SockAddr,
+    // submit the connect to io_uring instead of waiting for the readiness
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    uring: bool,
     is_connected: bool,
     pub(crate) is_coroutine: bool,
 }
@@ -45,14 +55,19 @@ impl TcpStreamConnect {
                 // before yield we must set the socket to nonblocking mode and register to selector
                 stream.set_nonblocking(true)?;
 
+                let is_coroutine = is_coroutine();
                 add_socket(&stream).map(|io| TcpStreamConnect {
+                    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+                    uring: is_coroutine && io.selector().is_uring(),
                     io_data: OptionCell::new(io),
                     stream: OptionCell::new(stream),
                     #[cfg(feature = "io_timeout")]
                     timeout,
                     addr,
+                    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+                    sock_addr: addr.into(),
                     is_connected: false,
-                    is_coroutine: is_coroutine(),
+                    is_coroutine,
                 })
             })
     }
@@ -60,6 +75,12 @@ impl TcpStreamConnect {
     #[inline]
     // return true if it's connected
     pub fn check_connected(&mut self) -> io::Result<bool> {
+        // the connect request is submitted to io_uring
+        #[cfg(all(feature = "io_uring", target_os = "linux"))]
+        if self.uring {
+            return Ok(false);
+        }
+
         // unix connect is some like completion mode
         // we must give the connect request first to the system
         match self.stream.connect(&self.addr.into()) {
@@ -83,6 +104,17 @@ impl TcpStreamConnect {
             return Ok(convert_to_stream(self));
         }
 
+        #[cfg(all(feature = "io_uring", target_os = "linux"))]
+        if self.uring {
+            // wait for the readiness if the request would block
+            self.uring = false;
+            match uring::result(&self.io_data) {
+                Ok(_) => return Ok(convert_to_stream(self)),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e),
+            }
+        }
+
         loop {
             co_io_result(self.is_coroutine)?;
 
@@ -111,15 +143,27 @@ impl TcpStreamConnect {
 
 impl EventSource for TcpStreamConnect {
     fn subscribe(&mut self, co: CoroutineImpl) {
+        #[cfg(all(feature = "io_uring", target_os = "linux"))]
+        if self.uring {
+            let fd = types::Fd(self.io_data.fd);
+            let sqe =
+                opcode::Connect::new(fd, self.sock_addr.as_ptr(), self.sock_addr.len()).build();
+            return uring::submit(
+                &self.io_data,
+                sqe,
+                #[cfg(feature = "io_timeout")]
+                self.timeout,
+                co,
+            );
+        }
+
         #[cfg(feature = "io_cancel")]
         let cancel = co_cancel_data(&co);
         let io_data = &self.io_data;
 
         #[cfg(feature = "io_timeout")]
         if let Some(dur) = self.timeout {
-            crate::scheduler::get_scheduler()
-                .get_selector()
-                .add_io_timer(&self.io_data, dur);
+            self.io_data.selector().add_io_timer(&self.io_data, dur);
         }
         unsafe { io_data.co.unsync_store(co) };
 
@@ -71,9 +71,7 @@ impl<'a> EventSource for UnixRecvFrom<'a> {
 
         #[cfg(feature = "io_timeout")]
         if let Some(dur) = self.timeout {
-            crate::scheduler::get_scheduler()
-                .get_selector()
-                .add_io_timer(self.io_data, dur);
+            self.io_data.selector().add_io_timer(self.io_data, dur);
         }
         unsafe { io_data.co.unsync_store(co) };
 
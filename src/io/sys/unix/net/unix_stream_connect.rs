@@ -95,8 +95,8 @@ impl EventSource for UnixStreamConnect {
         let io_data = &self.io_data;
 
         #[cfg(feature = "io_timeout")]
-        crate::scheduler::get_scheduler()
-            .get_selector()
+        self.io_data
+            .selector()
             .add_io_timer(&self.io_data, Duration::from_secs(2));
         unsafe { io_data.co.unsync_store(co) };
 
@@ -0,0 +1,766 @@
+//! io_uring based selector
+//!
+//! each registered fd is watched by an edge triggered multishot poll request,
+//! the completions are dispatched in the same way as the epoll events so that
+//! all the io objects are shared with the epoll backend. the ring is only
+//! touched by the event loop thread that owns it, other threads send the
+//! register requests through a queue and wake up the event loop.
+//!
+//! the read, write, accept and connect of a coroutine are submitted as
+//! completion requests once the optimistic syscall would block. the coroutine
+//! is only resumed by the completion, the io timeout is a linked timeout and
+//! the cancel is an async cancel request, so the buffers are never released
+//! while the kernel is still using them. the io of threads still waits for
+//! the readiness.
+//!
+//! if the kernel doesn't support the needed io_uring features the epoll
+//! selector is used instead
+#[cfg(feature = "io_timeout")]
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::io;
+use std::mem;
+use std::os::fd::AsRawFd;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "io_timeout")]
+use std::time::Duration;
+
+use super::epoll;
+use super::{co_io_result, EventData, IoData};
+#[cfg(feature = "io_timeout")]
+use super::{timeout_handler, TimerList};
+#[cfg(feature = "io_cancel")]
+use crate::coroutine_impl::co_cancel_data;
+use crate::coroutine_impl::CoroutineImpl;
+use crate::scheduler::{Scheduler, WORKER_ID, WORKER_SCHED};
+use crate::sync::AtomicOption;
+#[cfg(feature = "io_timeout")]
+use crate::timeout_list::now;
+
+use io_uring::{cqueue, opcode, squeue, types, IoUring};
+use may_queue::mpsc::Queue;
+use nix::sys::eventfd::*;
+use nix::unistd::{read, write};
+use parking_lot::Mutex;
+use smallvec::SmallVec;
+
+pub use super::epoll::SysEvent;
+
+// the ring size of each selector
+const RING_ENTRIES: u32 = 1024;
+// user data for the eventfd poll
+const WAKEUP_TOKEN: u64 = 0;
+// user data for the poll remove, async cancel and linked timeout requests,
+// the completion is ignored
+const IGNORE_TOKEN: u64 = u64::MAX;
+
+const POLL_ALL: u32 = (libc::POLLIN | libc::POLLOUT | libc::POLLRDHUP) as u32;
+const POLL_READ: u32 = (libc::POLLIN | libc::POLLRDHUP) as u32;
+const POLL_WRITE: u32 = (libc::POLLOUT | libc::POLLHUP) as u32;
+
+// the register requests from other threads
+enum Op {
+    Add(Arc<EventData>),
+    Mod(Arc<EventData>, u32),
+    Del(Arc<EventData>),
+    // submit a completion request with the token, and a linked timeout if set
+    Submit(Arc<EventData>, squeue::Entry, u64, bool),
+    // cancel the completion request of the token
+    Cancel(u64),
+}
+
+// the token of the next completion request, the tokens are odd so that they
+// never collide with the poll keys which are the aligned event data pointers
+static NEXT_TOKEN: AtomicU64 = AtomicU64::new(1);
+
+/// the completion request of an io object
+///
+/// an io object has at most one request in flight, the coroutine is resumed
+/// only by its completion
+pub struct Request {
+    // the selector and the token of the request in flight
+    pending: Mutex<Option<(*const SingleSelector, u64)>>,
+    co: AtomicOption<CoroutineImpl>,
+    res: AtomicI32,
+    canceled: AtomicBool,
+    // read by the kernel when the linked timeout is submitted
+    #[cfg(feature = "io_timeout")]
+    timeout: UnsafeCell<types::Timespec>,
+}
+
+impl Request {
+    pub fn new() -> Self {
+        Request {
+            pending: Mutex::new(None),
+            co: AtomicOption::none(),
+            res: AtomicI32::new(0),
+            canceled: AtomicBool::new(false),
+            #[cfg(feature = "io_timeout")]
+            timeout: UnsafeCell::new(types::Timespec::new()),
+        }
+    }
+
+    // the request is done, return the waiting coroutine
+    fn complete(&self, res: i32) -> Option<CoroutineImpl> {
+        let mut pending = self.pending.lock();
+        *pending = None;
+        self.res.store(res, Ordering::Relaxed);
+        self.co.take()
+    }
+
+    /// cancel the request in flight, return false if there is none
+    ///
+    /// the coroutine is resumed by the completion of the request
+    pub fn cancel(&self) -> bool {
+        // the request can't complete while the lock is held, so the
+        // selector is still alive
+        let pending = self.pending.lock();
+        let Some((ss, token)) = *pending else {
+            return false;
+        };
+        self.canceled.store(true, Ordering::Relaxed);
+        let ss = unsafe { &*ss };
+        ss.ops.push(Op::Cancel(token));
+        ss.wakeup();
+        true
+    }
+}
+
+// a registered fd
+struct Reg {
+    data: Arc<EventData>,
+    mask: u32,
+    // the poll is being removed, drop it after the last completion
+    removing: bool,
+}
+
+struct Ring {
+    ring: IoUring,
+    polls: HashMap<u64, Reg>,
+    // the completion requests in flight
+    reqs: HashMap<u64, Arc<EventData>>,
+    // reused buffer for the completions
+    cqes: Vec<(u64, i32, u32)>,
+}
+
+impl Ring {
+    fn push(&mut self, sqe: squeue::Entry) {
+        // the submission queue is full, flush it to the kernel first
+        while unsafe { self.ring.submission().push(&sqe) }.is_err() {
+            if let Err(e) = self.ring.submit() {
+                error!("io_uring submit error = {:?}", e);
+            }
+        }
+    }
+
+    fn poll_add(&mut self, fd: i32, mask: u32, user_data: u64) {
+        let sqe = opcode::PollAdd::new(types::Fd(fd), mask)
+            .multi(true)
+            .build()
+            .user_data(user_data);
+        self.push(sqe);
+    }
+
+    fn poll_remove(&mut self, user_data: u64) {
+        let sqe = opcode::PollRemove::new(user_data)
+            .build()
+            .user_data(IGNORE_TOKEN);
+        self.push(sqe);
+    }
+
+    // push the linked entries, they must be in the same submission
+    fn push_linked(&mut self, sqes: &[squeue::Entry]) {
+        while unsafe { self.ring.submission().push_multiple(sqes) }.is_err() {
+            if let Err(e) = self.ring.submit() {
+                error!("io_uring submit error = {:?}", e);
+            }
+        }
+    }
+
+    fn apply(&mut self, op: Op) {
+        match op {
+            Op::Add(data) => {
+                let key = Arc::as_ptr(&data) as u64;
+                self.poll_add(data.fd, POLL_ALL, key);
+                let reg = Reg {
+                    data,
+                    mask: POLL_ALL,
+                    removing: false,
+                };
+                self.polls.insert(key, reg);
+            }
+            Op::Mod(data, mask) => {
+                let key = Arc::as_ptr(&data) as u64;
+                if let Some(reg) = self.polls.get_mut(&key) {
+                    // the poll is re-armed with the new mask after removed
+                    reg.mask = mask;
+                    if !reg.removing {
+                        self.poll_remove(key);
+                    }
+                }
+            }
+            Op::Del(data) => {
+                let key = Arc::as_ptr(&data) as u64;
+                if let Some(reg) = self.polls.get_mut(&key) {
+                    reg.removing = true;
+                    self.poll_remove(key);
+                }
+            }
+            Op::Submit(data, sqe, token, _timed) => {
+                let sqe = sqe.user_data(token);
+                #[cfg(feature = "io_timeout")]
+                if _timed {
+                    let link = opcode::LinkTimeout::new(data.req.timeout.get())
+                        .build()
+                        .user_data(IGNORE_TOKEN);
+                    self.push_linked(&[sqe.flags(squeue::Flags::IO_LINK), link]);
+                    self.reqs.insert(token, data);
+                    return;
+                }
+                self.push(sqe);
+                self.reqs.insert(token, data);
+            }
+            Op::Cancel(token) => {
+                // the request may be already done
+                if self.reqs.contains_key(&token) {
+                    let sqe = opcode::AsyncCancel::new(token)
+                        .build()
+                        .user_data(IGNORE_TOKEN);
+                    self.push(sqe);
+                }
+            }
+        }
+    }
+}
+
+struct SingleSelector {
+    // only locked by the event loop thread
+    ring: Mutex<Ring>,
+    evfd: EventFd,
+    ops: Queue<Op>,
+    #[cfg(feature = "io_timeout")]
+    timer_list: TimerList,
+}
+
+impl SingleSelector {
+    fn new() -> io::Result<Self> {
+        let ring = IoUring::new(RING_ENTRIES)?;
+        if !ring.params().is_feature_ext_arg() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "io_uring EXT_ARG is not supported",
+            ));
+        }
+
+        let evfd = EventFd::from_flags(EfdFlags::EFD_NONBLOCK | EfdFlags::EFD_CLOEXEC)?;
+        let mut ring = Ring {
+            ring,
+            polls: HashMap::new(),
+            reqs: HashMap::new(),
+            cqes: Vec::new(),
+        };
+
+        // make sure the multishot poll works by a wakeup
+        ring.poll_add(evfd.as_raw_fd(), libc::POLLIN as u32, WAKEUP_TOKEN);
+        write(&evfd, &1u64.to_le_bytes())?;
+        ring.ring.submit_and_wait(1)?;
+        let cqe = ring.ring.completion().next();
+        match cqe {
+            Some(cqe) if cqe.result() > 0 && cqueue::more(cqe.flags()) => {}
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "io_uring multishot poll is not supported",
+                ))
+            }
+        }
+        let mut buf = [0u8; 8];
+        read(evfd.as_raw_fd(), &mut buf).ok();
+
+        Ok(SingleSelector {
+            ring: Mutex::new(ring),
+            evfd,
+            ops: Queue::new(),
+            #[cfg(feature = "io_timeout")]
+            timer_list: TimerList::new(),
+        })
+    }
+
+    fn wakeup(&self) {
+        let buf = 1u64.to_le_bytes();
+        let ret = write(&self.evfd, &buf);
+        trace!("wakeup ret={:?}", ret);
+    }
+}
+
+pub struct UringSelector {
+    // 128 should be fine for max io threads
+    vec: SmallVec<[SingleSelector; 128]>,
+}
+
+impl UringSelector {
+    fn new(io_workers: usize) -> io::Result<Self> {
+        let mut s = UringSelector {
+            vec: SmallVec::new(),
+        };
+
+        for _ in 0..io_workers {
+            let ss = SingleSelector::new()?;
+            s.vec.push(ss);
+        }
+
+        Ok(s)
+    }
+
+    fn select(
+        &self,
+        scheduler: &Scheduler,
+        id: usize,
+        timeout: Option<u64>,
+    ) -> io::Result<Option<u64>> {
+        let single_selector = unsafe { self.vec.get_unchecked(id) };
+        let mut ring = single_selector.ring.lock();
+
+        // register the pending requests
+        while let Some(op) = single_selector.ops.pop() {
+            ring.apply(op);
+        }
+
+        let timeout = timeout.map(|to| {
+            types::Timespec::new()
+                .sec(to / 1_000_000_000)
+                .nsec((to % 1_000_000_000) as u32)
+        });
+
+        // Wait for the completions for at most timeout
+        let ret = match timeout {
+            Some(ref ts) => {
+                let args = types::SubmitArgs::new().timespec(ts);
+                ring.ring.submitter().submit_with_args(1, &args)
+            }
+            None => ring.ring.submit_and_wait(1),
+        };
+        if let Err(e) = ret {
+            match e.raw_os_error() {
+                Some(libc::ETIME) | Some(libc::EINTR) | Some(libc::EBUSY) => {}
+                _ => return Err(e),
+            }
+        }
+
+        let mut cqes = mem::take(&mut ring.cqes);
+        cqes.extend(
+            ring.ring
+                .completion()
+                .map(|c| (c.user_data(), c.result(), c.flags())),
+        );
+
+        // collect coroutines
+        for &(user_data, result, flags) in cqes.iter() {
+            if user_data == IGNORE_TOKEN {
+                continue;
+            }
+
+            if user_data == WAKEUP_TOKEN {
+                // this is just a wakeup event, ignore it
+                let mut buf = [0u8; 8];
+                // clear the eventfd, ignore the result
+                read(single_selector.evfd.as_raw_fd(), &mut buf).ok();
+                if !cqueue::more(flags) {
+                    let fd = single_selector.evfd.as_raw_fd();
+                    ring.poll_add(fd, libc::POLLIN as u32, WAKEUP_TOKEN);
+                }
+                scheduler.collect_global(id);
+                continue;
+            }
+
+            if user_data & 1 == 1 {
+                // a completion request is done
+                let co = match ring.reqs.remove(&user_data) {
+                    Some(data) => data.req.complete(result),
+                    None => None,
+                };
+                if let Some(co) = co {
+                    #[cfg(feature = "work_steal")]
+                    scheduler.schedule_with_id(co, id);
+                    #[cfg(not(feature = "work_steal"))]
+                    scheduler.run_local(co);
+                }
+                continue;
+            }
+
+            if !cqueue::more(flags) {
+                // the poll is terminated, re-arm it if the fd is still registered
+                let reg = ring.polls.get(&user_data).expect("unknown io_uring poll");
+                if reg.removing {
+                    ring.polls.remove(&user_data);
+                    continue;
+                }
+                let (fd, mask) = (reg.data.fd, reg.mask);
+                ring.poll_add(fd, mask, user_data);
+                if result == -libc::ECANCELED {
+                    continue;
+                }
+            }
+
+            let data = unsafe { &*(user_data as *const EventData) };
+            // the error result is also an event, the io would get the error by itself
+            let events = if result > 0 { result as usize } else { 1 };
+            data.io_flag.fetch_or(events, Ordering::Release);
+            data.wake_watcher();
+
+            // first check the atomic co, this may be grab by the worker first
+            let co = match data.co.take() {
+                Some(co) => co,
+                None => continue,
+            };
+
+            // it's safe to remove the timer since we are running the timer_list in the same thread
+            #[cfg(feature = "io_timeout")]
+            data.timer.borrow_mut().take().map(|h| {
+                unsafe {
+                    // tell the timer handler not to cancel the io
+                    // it's not always true that you can really remove the timer entry
+                    h.with_mut_data(|value| value.data.event_data = std::ptr::null_mut());
+                }
+                h.remove()
+            });
+
+            #[cfg(feature = "work_steal")]
+            scheduler.schedule_with_id(co, id);
+            #[cfg(not(feature = "work_steal"))]
+            scheduler.run_local(co);
+        }
+        cqes.clear();
+        ring.cqes = cqes;
+        drop(ring);
+
+        // run all the local tasks
+        scheduler.run_queued_tasks(id);
+
+        // deal with the timer list
+        #[cfg(feature = "io_timeout")]
+        let next_expire = single_selector
+            .timer_list
+            .schedule_timer(now(), &timeout_handler);
+        #[cfg(not(feature = "io_timeout"))]
+        let next_expire = None;
+        Ok(next_expire)
+    }
+
+    fn wakeup(&self, id: usize) {
+        trace!("wakeup id={:?}", id);
+        unsafe { self.vec.get_unchecked(id) }.wakeup();
+    }
+
+    // send the request to the event loop that owns the fd
+    fn send_op(&self, fd: i32, op: Op) {
+        let id = fd as usize % self.vec.len();
+        unsafe { self.vec.get_unchecked(id) }.ops.push(op);
+        self.wakeup(id);
+    }
+
+    fn add_fd(&self, io_data: IoData) -> io::Result<IoData> {
+        let fd = io_data.fd;
+        // the io_uring poll accepts any fd, keep the same behavior as epoll
+        let mut stat: libc::stat = unsafe { mem::zeroed() };
+        if unsafe { libc::fstat(fd, &mut stat) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let file_type = stat.st_mode & libc::S_IFMT;
+        if file_type == libc::S_IFREG || file_type == libc::S_IFDIR {
+            return Err(io::Error::from_raw_os_error(libc::EPERM));
+        }
+
+        info!("add fd to io_uring select, fd={:?}", fd);
+        self.send_op(fd, Op::Add((*io_data).clone()));
+        Ok(io_data)
+    }
+
+    fn mod_fd(&self, io_data: &IoData, is_read: bool) -> io::Result<()> {
+        let mask = if is_read { POLL_READ } else { POLL_WRITE };
+        let fd = io_data.fd;
+        info!(
+            "mod fd to io_uring select, fd={:?}, is_read={}",
+            fd, is_read
+        );
+        self.send_op(fd, Op::Mod((*io_data).clone(), mask));
+        Ok(())
+    }
+
+    fn del_fd(&self, io_data: &IoData) {
+        #[cfg(feature = "io_timeout")]
+        if let Some(h) = io_data.timer.borrow_mut().take() {
+            unsafe {
+                // mark the timer as removed if any, this only happened
+                // when cancel an IO. what if the timer expired at the same time?
+                // because we run this func in the user space, so the timer handler
+                // will not got the coroutine
+                h.with_mut_data(|value| value.data.event_data = std::ptr::null_mut());
+            }
+        }
+
+        let fd = io_data.fd;
+        info!("del fd from io_uring select, fd={:?}", fd);
+        // the event data is freed after the poll is removed
+        self.send_op(fd, Op::Del((*io_data).clone()));
+    }
+
+    #[cfg(feature = "io_timeout")]
+    fn add_io_timer(&self, io: &IoData, timeout: Duration) {
+        let id = io.fd as usize % self.vec.len();
+        let (h, b_new) = unsafe { self.vec.get_unchecked(id) }
+            .timer_list
+            .add_timer(timeout, io.timer_data());
+        if b_new {
+            // wake up the event loop thread to recall the next wait timeout
+            self.wakeup(id);
+        }
+        io.timer.borrow_mut().replace(h);
+    }
+}
+
+/// submit the completion request of the io for the coroutine
+///
+/// the io must be registered to an io_uring selector, the result is got by
+/// [`result`] after the coroutine is resumed
+pub fn submit(
+    io: &IoData,
+    sqe: squeue::Entry,
+    #[cfg(feature = "io_timeout")] timeout: Option<Duration>,
+    co: CoroutineImpl,
+) {
+    let Selector::Uring(s) = io.selector() else {
+        unreachable!("the io is not registered to io_uring");
+    };
+    #[cfg(feature = "io_cancel")]
+    let cancel = co_cancel_data(&co);
+    let data = (**io).clone();
+
+    // submit to the event loop of the current worker, it would pick up the
+    // request before waiting again, so no wakeup is needed
+    let cur = WORKER_ID.get();
+    let local = cur < s.vec.len() && ptr::eq(WORKER_SCHED.get(), &*io.sched);
+    let id = if local {
+        cur
+    } else {
+        io.fd as usize % s.vec.len()
+    };
+    let ss = &s.vec[id];
+
+    #[cfg(feature = "io_timeout")]
+    let timed = match timeout {
+        Some(dur) => {
+            unsafe { *data.req.timeout.get() = types::Timespec::from(dur) };
+            true
+        }
+        None => false,
+    };
+    #[cfg(not(feature = "io_timeout"))]
+    let timed = false;
+
+    let token = NEXT_TOKEN.fetch_add(2, Ordering::Relaxed);
+    let req = &data.req;
+    {
+        // the completion can't resume the coroutine before the lock is
+        // released, so the io and the selector are still alive here
+        let mut pending = req.pending.lock();
+        req.canceled.store(false, Ordering::Relaxed);
+        req.co.store(co);
+        *pending = Some((ss as *const _, token));
+        ss.ops.push(Op::Submit(data.clone(), sqe, token, timed));
+        if !local {
+            ss.wakeup();
+        }
+    }
+
+    #[cfg(feature = "io_cancel")]
+    {
+        // register the cancel io data
+        cancel.set_io(data);
+        // re-check the cancel status
+        if cancel.is_canceled() {
+            unsafe { cancel.cancel() };
+        }
+    }
+}
+
+/// get the result of the completion request of the io
+///
+/// an `EAGAIN` result means the io should wait for the readiness instead
+pub fn result(io: &IoData) -> io::Result<usize> {
+    co_io_result(true)?;
+    let req = &io.req;
+    match req.res.load(Ordering::Relaxed) {
+        res if res >= 0 => Ok(res as usize),
+        res if res == -libc::ECANCELED && !req.canceled.load(Ordering::Relaxed) => {
+            // canceled by the linked timeout
+            Err(io::Error::new(io::ErrorKind::TimedOut, "timeout"))
+        }
+        res => Err(io::Error::from_raw_os_error(-res)),
+    }
+}
+
+// the selector is created only once
+#[allow(clippy::large_enum_variant)]
+pub enum Selector {
+    Uring(UringSelector),
+    Epoll(epoll::Selector),
+}
+
+impl Selector {
+    pub fn new(io_workers: usize) -> io::Result<Self> {
+        match UringSelector::new(io_workers) {
+            Ok(s) => Ok(Selector::Uring(s)),
+            Err(e) => {
+                warn!("io_uring is not available, fallback to epoll: {e}");
+                epoll::Selector::new(io_workers).map(Selector::Epoll)
+            }
+        }
+    }
+
+    // return true if the io_uring backend is used
+    #[inline]
+    pub fn is_uring(&self) -> bool {
+        matches!(self, Selector::Uring(_))
+    }
+
+    #[inline]
+    pub fn select(
+        &self,
+        scheduler: &Scheduler,
+        id: usize,
+        events: &mut [SysEvent],
+        timeout: Option<u64>,
+    ) -> io::Result<Option<u64>> {
+        match self {
+            Selector::Uring(s) => s.select(scheduler, id, timeout),
+            Selector::Epoll(s) => s.select(scheduler, id, events, timeout),
+        }
+    }
+
+    // this will post an os event so that we can wake up the event loop
+    #[inline]
+    pub fn wakeup(&self, id: usize) {
+        match self {
+            Selector::Uring(s) => s.wakeup(id),
+            Selector::Epoll(s) => s.wakeup(id),
+        }
+    }
+
+    // register io event to the selector
+    #[inline]
+    pub fn add_fd(&self, io_data: IoData) -> io::Result<IoData> {
+        match self {
+            Selector::Uring(s) => s.add_fd(io_data),
+            Selector::Epoll(s) => s.add_fd(io_data),
+        }
+    }
+
+    #[inline]
+    pub fn mod_fd(&self, io_data: &IoData, is_read: bool) -> io::Result<()> {
+        match self {
+            Selector::Uring(s) => s.mod_fd(io_data, is_read),
+            Selector::Epoll(s) => s.mod_fd(io_data, is_read),
+        }
+    }
+
+    #[inline]
+    pub fn del_fd(&self, io_data: &IoData) {
+        match self {
+            Selector::Uring(s) => s.del_fd(io_data),
+            Selector::Epoll(s) => s.del_fd(io_data),
+        }
+    }
+
+    // register the io request to the timeout list
+    #[inline]
+    #[cfg(feature = "io_timeout")]
+    pub fn add_io_timer(&self, io: &IoData, timeout: Duration) {
+        match self {
+            Selector::Uring(s) => s.add_io_timer(io, timeout),
+            Selector::Epoll(s) => s.add_io_timer(io, timeout),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::{TcpListener, TcpStream};
+    use crate::scheduler::with_scheduler;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn uring_echo() {
+        // io_uring must be used if the kernel supports it
+        let supported = super::SingleSelector::new().is_ok();
+        assert_eq!(with_scheduler(|s| s.get_selector().is_uring()), supported);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let h = go!(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 5];
+            s.read_exact(&mut buf).unwrap();
+            s.write_all(&buf).unwrap();
+        });
+
+        let h1 = go!(move || {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write_all(b"hello").unwrap();
+            let mut buf = [0u8; 5];
+            s.read_exact(&mut buf).unwrap();
+            buf
+        });
+        h.join().unwrap();
+        assert_eq!(&h1.join().unwrap(), b"hello");
+    }
+
+    #[test]
+    #[cfg(feature = "io_timeout")]
+    fn uring_read_timeout() {
+        use std::io;
+        use std::time::{Duration, Instant};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let h = go!(move || {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.set_read_timeout(Some(Duration::from_millis(100)))
+                .unwrap();
+            let mut buf = [0u8; 5];
+            let now = Instant::now();
+            let err = s.read(&mut buf).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+            assert!(now.elapsed() >= Duration::from_millis(100));
+
+            // the stream still works after the timeout
+            s.write_all(b"hello").unwrap();
+        });
+        let (mut s, _) = listener.accept().unwrap();
+        h.join().unwrap();
+        let mut buf = [0u8; 5];
+        s.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hello");
+    }
+
+    #[test]
+    #[cfg(feature = "io_cancel")]
+    fn uring_cancel_read() {
+        use std::time::Duration;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let h = go!(move || {
+            let mut s = TcpStream::connect(addr).unwrap();
+            let mut buf = [0u8; 5];
+            let _ = s.read(&mut buf);
+            unreachable!("the read should be canceled");
+        });
+        let (_s, _) = listener.accept().unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        unsafe { h.coroutine().cancel() };
+        assert!(h.join().is_err());
+    }
+}
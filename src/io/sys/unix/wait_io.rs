@@ -64,6 +64,7 @@ impl WaitIoWaker {
         self.io_data
             .io_flag
             .fetch_or(0x8000_0000, Ordering::Release);
+        self.io_data.wake_watcher();
         self.io_data.schedule();
     }
 }
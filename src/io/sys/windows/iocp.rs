@@ -179,7 +179,7 @@ impl Selector {
             #[cfg(feature = "work_steal")]
             scheduler.schedule_with_id(co, id);
             #[cfg(not(feature = "work_steal"))]
-            crate::coroutine_impl::run_coroutine(co);
+            scheduler.run_local(co);
         }
 
         // run all the local tasks
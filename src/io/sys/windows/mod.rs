@@ -1,11 +1,11 @@
 macro_rules! co_try {
-    ($s:expr, $co:expr, $e:expr) => {
+    ($co:expr, $e:expr) => {
         match $e {
             Ok(val) => val,
             Err(err) => {
                 let mut co = $co;
                 crate::yield_now::set_co_para(&mut co, err);
-                $s.schedule(co);
+                crate::scheduler::schedule(co);
                 return;
             }
         }
@@ -25,7 +25,7 @@ use std::{fmt, io};
 
 use super::thread::ASSOCIATED_IO_RET;
 use crate::likely::likely;
-use crate::scheduler::get_scheduler;
+use crate::scheduler::with_scheduler;
 use crate::yield_now::get_co_para;
 
 pub use self::iocp::{EventData, Selector, SysEvent};
@@ -55,7 +55,7 @@ unsafe impl Send for IoData {}
 // register the socket to the system selector
 #[inline]
 pub fn add_socket<T: AsRawSocket + ?Sized>(t: &T) -> io::Result<IoData> {
-    get_scheduler().get_selector().add_socket(t).map(|_| IoData)
+    with_scheduler(|s| s.get_selector().add_socket(t)).map(|_| IoData)
 }
 
 // deal with the io result
@@ -6,7 +6,8 @@ use std::time::Duration;
 use super::super::miow::socket_write;
 use super::super::{co_io_result, EventData};
 use crate::coroutine_impl::{is_coroutine, CoroutineImpl, EventSource};
-use crate::scheduler::get_scheduler;
+#[cfg(feature = "io_timeout")]
+use crate::scheduler::with_scheduler;
 use windows_sys::Win32::Foundation::*;
 
 pub struct SocketWrite<'a> {
@@ -43,16 +44,15 @@ impl<'a> SocketWrite<'a> {
 impl<'a> EventSource for SocketWrite<'a> {
     #[allow(clippy::needless_return)]
     fn subscribe(&mut self, co: CoroutineImpl) {
-        let s = get_scheduler();
         #[cfg(feature = "io_timeout")]
         if let Some(dur) = self.timeout {
-            s.get_selector().add_io_timer(&mut self.io_data, dur);
+            with_scheduler(|s| s.get_selector().add_io_timer(&mut self.io_data, dur));
         }
 
         // prepare the co first
         self.io_data.co = Some(co);
         // call the overlapped write API
-        co_try!(s, self.io_data.co.take().expect("can't get co"), unsafe {
+        co_try!(self.io_data.co.take().expect("can't get co"), unsafe {
             socket_write(self.socket, self.buf, self.io_data.get_overlapped())
         });
     }
@@ -11,7 +11,6 @@ use crate::coroutine_impl::{is_coroutine, CoroutineImpl, EventSource};
 use crate::io::cancel::CancelIoData;
 use crate::io::OptionCell;
 use crate::net::{TcpListener, TcpStream};
-use crate::scheduler::get_scheduler;
 use crate::sync::delay_drop::DelayDrop;
 use windows_sys::Win32::Foundation::*;
 
@@ -67,7 +66,6 @@ impl<'a> TcpListenerAccept<'a> {
 impl<'a> EventSource for TcpListenerAccept<'a> {
     fn subscribe(&mut self, co: CoroutineImpl) {
         let _g = self.can_drop.delay_drop();
-        let s = get_scheduler();
         #[cfg(feature = "io_cancel")]
         let cancel = co_cancel_data(&co);
         // we don't need to register the timeout here,
@@ -75,7 +73,7 @@ impl<'a> EventSource for TcpListenerAccept<'a> {
         self.io_data.co = Some(co);
 
         // call the overlapped read API
-        co_try!(s, self.io_data.co.take().expect("can't get co"), unsafe {
+        co_try!(self.io_data.co.take().expect("can't get co"), unsafe {
             accept_overlapped(
                 self.socket.as_raw_socket(),
                 &self.ret,
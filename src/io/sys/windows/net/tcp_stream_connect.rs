@@ -13,7 +13,8 @@ use crate::coroutine_impl::{is_coroutine, CoroutineImpl, EventSource};
 use crate::io::cancel::CancelIoData;
 use crate::io::OptionCell;
 use crate::net::TcpStream;
-use crate::scheduler::get_scheduler;
+#[cfg(feature = "io_timeout")]
+use crate::scheduler::with_scheduler;
 use crate::sync::delay_drop::DelayDrop;
 use windows_sys::Win32::Foundation::*;
 
@@ -91,17 +92,16 @@ impl TcpStreamConnect {
 impl EventSource for TcpStreamConnect {
     fn subscribe(&mut self, co: CoroutineImpl) {
         let _g = self.can_drop.delay_drop();
-        let s = get_scheduler();
         #[cfg(feature = "io_cancel")]
         let cancel = co_cancel_data(&co);
         #[cfg(feature = "io_timeout")]
         if let Some(dur) = self.timeout {
-            s.get_selector().add_io_timer(&mut self.io_data, dur);
+            with_scheduler(|s| s.get_selector().add_io_timer(&mut self.io_data, dur));
         }
         self.io_data.co = Some(co);
 
         // call the overlapped connect API
-        co_try!(s, self.io_data.co.take().expect("can't get co"), unsafe {
+        co_try!(self.io_data.co.take().expect("can't get co"), unsafe {
             connect_overlapped(
                 self.stream.as_raw_socket(),
                 &self.addr,
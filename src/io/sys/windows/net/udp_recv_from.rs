@@ -12,7 +12,8 @@ use crate::coroutine_impl::{is_coroutine, CoroutineImpl, EventSource};
 #[cfg(feature = "io_cancel")]
 use crate::io::cancel::CancelIoData;
 use crate::net::UdpSocket;
-use crate::scheduler::get_scheduler;
+#[cfg(feature = "io_timeout")]
+use crate::scheduler::with_scheduler;
 use crate::sync::delay_drop::DelayDrop;
 use windows_sys::Win32::Foundation::*;
 
@@ -53,17 +54,16 @@ impl<'a> UdpRecvFrom<'a> {
 impl<'a> EventSource for UdpRecvFrom<'a> {
     fn subscribe(&mut self, co: CoroutineImpl) {
         let _g = self.can_drop.delay_drop();
-        let s = get_scheduler();
         #[cfg(feature = "io_cancel")]
         let cancel = co_cancel_data(&co);
         #[cfg(feature = "io_timeout")]
         if let Some(dur) = self.timeout {
-            s.get_selector().add_io_timer(&mut self.io_data, dur);
+            with_scheduler(|s| s.get_selector().add_io_timer(&mut self.io_data, dur));
         }
         // prepare the co first
         self.io_data.co = Some(co);
         // call the overlapped read API
-        co_try!(s, self.io_data.co.take().expect("can't get co"), unsafe {
+        co_try!(self.io_data.co.take().expect("can't get co"), unsafe {
             recv_from_overlapped(
                 self.socket.as_raw_socket(),
                 self.buf,
@@ -8,7 +8,8 @@ use super::super::miow::send_to_overlapped;
 use super::super::{co_io_result, EventData};
 use crate::coroutine_impl::{is_coroutine, CoroutineImpl, EventSource};
 use crate::net::UdpSocket;
-use crate::scheduler::get_scheduler;
+#[cfg(feature = "io_timeout")]
+use crate::scheduler::with_scheduler;
 use windows_sys::Win32::Foundation::*;
 
 pub struct UdpSendTo<'a> {
@@ -49,15 +50,14 @@ impl<'a> UdpSendTo<'a> {
 impl<'a> EventSource for UdpSendTo<'a> {
     #[allow(clippy::needless_return)]
     fn subscribe(&mut self, co: CoroutineImpl) {
-        let s = get_scheduler();
         #[cfg(feature = "io_timeout")]
         if let Some(dur) = self.timeout {
-            s.get_selector().add_io_timer(&mut self.io_data, dur);
+            with_scheduler(|s| s.get_selector().add_io_timer(&mut self.io_data, dur));
         }
         // prepare the co first
         self.io_data.co = Some(co);
         // call the overlapped read API
-        co_try!(s, self.io_data.co.take().expect("can't get co"), unsafe {
+        co_try!(self.io_data.co.take().expect("can't get co"), unsafe {
             send_to_overlapped(
                 self.socket.as_raw_socket(),
                 self.buf,
@@ -6,7 +6,8 @@ use std::time::Duration;
 use super::super::miow::pipe_write_overlapped;
 use super::super::{co_io_result, EventData};
 use crate::coroutine_impl::{is_coroutine, CoroutineImpl, EventSource};
-use crate::scheduler::get_scheduler;
+#[cfg(feature = "io_timeout")]
+use crate::scheduler::with_scheduler;
 
 pub struct PipeWrite<'a> {
     io_data: EventData,
@@ -42,15 +43,14 @@ impl<'a> PipeWrite<'a> {
 impl<'a> EventSource for PipeWrite<'a> {
     #[allow(clippy::needless_return)]
     fn subscribe(&mut self, co: CoroutineImpl) {
-        let s = get_scheduler();
         #[cfg(feature = "io_timeout")]
         if let Some(dur) = self.timeout {
-            s.get_selector().add_io_timer(&mut self.io_data, dur);
+            with_scheduler(|s| s.get_selector().add_io_timer(&mut self.io_data, dur));
         }
         // prepare the co first
         self.io_data.co = Some(co);
         // call the overlapped write API
-        co_try!(s, self.io_data.co.take().expect("can't get co"), unsafe {
+        co_try!(self.io_data.co.take().expect("can't get co"), unsafe {
             pipe_write_overlapped(self.pipe, self.buf, self.io_data.get_overlapped())
         });
     }
@@ -4,13 +4,16 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread::Result;
 
-use crate::coroutine_impl::Coroutine;
+use crate::coroutine_impl::{Coroutine, CoroutineId};
+use crate::select::{SelectWait, Watch, Watchers};
 use crate::sync::{AtomicOption, Blocker};
 use generator::Error;
 
 pub struct Join {
     // the coroutine that waiting for this join handler
     to_wake: AtomicOption<Arc<Blocker>>,
+    // the selects that are watching the join
+    watchers: Watchers,
     // the flag indicate if the host coroutine is not finished
     // when set to false, the coroutine is done
     state: AtomicBool,
@@ -27,6 +30,7 @@ impl Join {
     pub fn new(panic: Arc<AtomicOption<Box<dyn Any + Send>>>) -> Self {
         Join {
             to_wake: AtomicOption::none(),
+            watchers: Watchers::new(),
             state: AtomicBool::new(true),
             panic,
         }
@@ -42,6 +46,7 @@ impl Join {
         if let Some(w) = self.to_wake.take() {
             w.unpark();
         }
+        self.watchers.wakeup();
     }
 
     fn wait(&self) {
@@ -92,6 +97,11 @@ impl<T> JoinHandle<T> {
         &self.co
     }
 
+    /// Gets the id of the underlying coroutine
+    pub fn id(&self) -> CoroutineId {
+        self.co.id()
+    }
+
     /// return true if the coroutine is finished
     pub fn is_done(&self) -> bool {
         !self.join.state.load(Ordering::Acquire)
@@ -115,6 +125,24 @@ impl<T> JoinHandle<T> {
 
 impl<T> fmt::Debug for JoinHandle<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.pad("JoinHandle { .. }")
+        f.debug_struct("JoinHandle")
+            .field("id", &self.id())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Watch for JoinHandle<T> {
+    fn watch(&self, blocker: &Arc<Blocker>) {
+        self.join.watchers.watch(blocker);
+    }
+
+    fn unwatch(&self, blocker: &Arc<Blocker>) {
+        self.join.watchers.unwatch(blocker);
+    }
+}
+
+impl<T> SelectWait for JoinHandle<T> {
+    fn is_ready(&self) -> bool {
+        self.is_done()
     }
 }
@@ -32,12 +32,21 @@ mod likely;
 mod local;
 mod park;
 mod pool;
+mod registry;
 mod sleep;
 #[macro_use]
 mod macros;
+mod blocking_pool;
 mod coroutine_impl;
+mod dump;
+mod metrics;
+mod overflow;
+mod runtime;
 mod scheduler;
 mod scoped;
+#[cfg(feature = "tracing")]
+mod span;
+mod task_group;
 mod timeout_list;
 mod yield_now;
 
@@ -46,11 +55,18 @@ mod crossbeam_queue_shim;
 
 pub mod coroutine;
 pub mod cqueue;
+pub mod fs;
 pub mod io;
 pub mod net;
 pub mod os;
+#[cfg(unix)]
+pub mod process;
+pub mod select;
 pub mod sync;
 pub use crate::config::{config, Config};
 pub use crate::local::LocalKey;
+pub use crate::metrics::{metrics, Metrics, WorkerMetrics};
+pub use crate::runtime::{run, EnterGuard, Runtime, RuntimeBuilder};
+pub use crate::scheduler::shutdown;
 // re-export may_queue
 pub use may_queue as queue;
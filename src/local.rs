@@ -1,4 +1,6 @@
 use std::any::TypeId;
+#[cfg(unix)]
+use std::cell::Cell;
 use std::cell::RefCell;
 use std::collections::HashMap;
 use std::hash::{BuildHasherDefault, Hasher};
@@ -7,6 +9,7 @@ use std::sync::Arc;
 
 use crate::coroutine_impl::Coroutine;
 use crate::join::Join;
+use crate::scheduler::Scheduler;
 use generator::get_local_data;
 
 // thread local map storage
@@ -18,16 +21,34 @@ pub struct CoroutineLocal {
     co: Coroutine,
     // when panic happens, we need to trigger the join here
     join: Arc<Join>,
+    // the runtime that the coroutine belongs to
+    sched: Arc<Scheduler>,
+    // entered each time the coroutine is resumed
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    // the stack size in bytes and the start of its guard page, used to
+    // detect stack overflow
+    #[cfg(unix)]
+    stack_len: Cell<usize>,
+    #[cfg(unix)]
+    stack_guard: Cell<usize>,
     // real local data hash map
     local_data: LocalMap,
 }
 
 impl CoroutineLocal {
     /// create coroutine local storage
-    pub fn new(co: Coroutine, join: Arc<Join>) -> Box<Self> {
+    pub fn new(co: Coroutine, join: Arc<Join>, sched: Arc<Scheduler>) -> Box<Self> {
         Box::new(CoroutineLocal {
-            co,
             join,
+            sched,
+            #[cfg(feature = "tracing")]
+            span: crate::span::new_span(&co),
+            co,
+            #[cfg(unix)]
+            stack_len: Cell::new(0),
+            #[cfg(unix)]
+            stack_guard: Cell::new(0),
             local_data: RefCell::new(HashMap::default()),
         })
     }
@@ -41,6 +62,41 @@ impl CoroutineLocal {
     pub fn get_join(&self) -> Arc<Join> {
         self.join.clone()
     }
+
+    // get the scheduler of the coroutine
+    pub fn get_sched(&self) -> &Scheduler {
+        &self.sched
+    }
+
+    // set the size of the coroutine stack in bytes
+    #[cfg(unix)]
+    pub fn set_stack_len(&self, len: usize) {
+        self.stack_len.set(len);
+    }
+
+    // get the size of the coroutine stack in bytes, 0 if it's not tracked
+    #[cfg(unix)]
+    pub fn get_stack_len(&self) -> usize {
+        self.stack_len.get()
+    }
+
+    // set the start of the guard page below the coroutine stack
+    #[cfg(unix)]
+    pub fn set_stack_guard(&self, guard: usize) {
+        self.stack_guard.set(guard);
+    }
+
+    // get the start of the guard page, 0 if it's not started
+    #[cfg(unix)]
+    pub fn get_stack_guard(&self) -> usize {
+        self.stack_guard.get()
+    }
+
+    // get the span of the coroutine
+    #[cfg(feature = "tracing")]
+    pub fn get_span(&self) -> &tracing::Span {
+        &self.span
+    }
 }
 
 #[inline]
@@ -116,9 +116,54 @@ macro_rules! cqueue_add_oneshot {
 }
 
 /// macro used to select for only one event
-/// it will return the index of which event happens first
+///
+/// there are two kinds of select, the native select and the coroutine select
+///
+/// the native select polls the arms in place without spawning coroutines,
+/// it evaluates to the value of the selected arm body. see the [`select`]
+/// module for the supported arms
+///
+/// ```rust
+/// use std::time::Duration;
+/// use may::sync::mpsc::channel;
+///
+/// let (tx, rx) = channel();
+/// tx.send(1).unwrap();
+/// let v = may::select! {
+///     recv(rx) -> v => v.unwrap(),
+///     timeout(Duration::from_secs(1)) => 0,
+/// };
+/// assert_eq!(v, 1);
+/// ```
+///
+/// the coroutine select accepts any blocking expression as the arm, each
+/// arm runs in a spawned coroutine, it will return the index of which event
+/// happens first
+///
+/// [`select`]: select/index.html
 #[macro_export]
 macro_rules! select {
+    (biased; $($arms:tt)+) => {
+        $crate::__select_native!(true, $($arms)+)
+    };
+    (recv $($arms:tt)+) => {
+        $crate::__select_native!(false, recv $($arms)+)
+    };
+    (send $($arms:tt)+) => {
+        $crate::__select_native!(false, send $($arms)+)
+    };
+    (wait $($arms:tt)+) => {
+        $crate::__select_native!(false, wait $($arms)+)
+    };
+    (io $($arms:tt)+) => {
+        $crate::__select_native!(false, io $($arms)+)
+    };
+    (timeout $($arms:tt)+) => {
+        $crate::__select_native!(false, timeout $($arms)+)
+    };
+    (default $($arms:tt)+) => {
+        $crate::__select_native!(false, default $($arms)+)
+    };
     (
         $($name:pat = $top:expr => $bottom:expr),+
     ) => ({
@@ -138,9 +183,33 @@ macro_rules! select {
 }
 
 /// macro used to select in a infinite loop
-/// it never returns, and will run forever
+///
+/// for the coroutine select it never returns, and will run forever.
+/// for the native select it runs the select in a loop, use `break` in
+/// the arm body to exit the loop
 #[macro_export]
 macro_rules! loop_select {
+    (biased; $($arms:tt)+) => {
+        loop { $crate::__select_native!(true, $($arms)+) }
+    };
+    (recv $($arms:tt)+) => {
+        loop { $crate::__select_native!(false, recv $($arms)+) }
+    };
+    (send $($arms:tt)+) => {
+        loop { $crate::__select_native!(false, send $($arms)+) }
+    };
+    (wait $($arms:tt)+) => {
+        loop { $crate::__select_native!(false, wait $($arms)+) }
+    };
+    (io $($arms:tt)+) => {
+        loop { $crate::__select_native!(false, io $($arms)+) }
+    };
+    (timeout $($arms:tt)+) => {
+        loop { $crate::__select_native!(false, timeout $($arms)+) }
+    };
+    (default $($arms:tt)+) => {
+        loop { $crate::__select_native!(false, default $($arms)+) }
+    };
     (
         $($name:pat = $top:expr => $bottom:expr),+
     ) => ({
@@ -161,6 +230,108 @@ macro_rules! loop_select {
     })
 }
 
+/// parse the native select arms
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __select_native {
+    ($biased:expr, $($arms:tt)+) => {
+        $crate::__select_native!(
+            @parse $biased, [] [] (
+                __a0 __a1 __a2 __a3 __a4 __a5 __a6 __a7
+                __a8 __a9 __a10 __a11 __a12 __a13 __a14 __a15
+                __a16 __a17 __a18 __a19 __a20 __a21 __a22 __a23
+                __a24 __a25 __a26 __a27 __a28 __a29 __a30 __a31
+            ) $($arms)+
+        )
+    };
+
+    // all the arms are parsed
+    (@parse $biased:expr, [$($arms:tt)*] [$($dflt:tt)*] ($($pool:ident)*)) => {
+        $crate::__select_native!(@run $biased, [$($arms)*] [$($dflt)*])
+    };
+    (@parse $biased:expr, $arms:tt $dflt:tt () $($rest:tt)+) => {
+        compile_error!("too many arms in select!")
+    };
+    (@parse $biased:expr, [$($arms:tt)*] $dflt:tt ($id:ident $($pool:ident)*)
+        recv($rx:expr) -> $res:pat => $body:expr $(, $($rest:tt)*)?
+    ) => {
+        $crate::__select_native!(
+            @parse $biased, [$($arms)* ($id [$crate::select::RecvArm::new(&$rx)] [let $res = $id.take();] => $body)]
+            $dflt ($($pool)*) $($($rest)*)?
+        )
+    };
+    (@parse $biased:expr, [$($arms:tt)*] $dflt:tt ($id:ident $($pool:ident)*)
+        send($tx:expr, $msg:expr) -> $res:pat => $body:expr $(, $($rest:tt)*)?
+    ) => {
+        $crate::__select_native!(
+            @parse $biased, [$($arms)* ($id [$crate::select::SendArm::new(&$tx, $msg)] [let $res = $id.take();] => $body)]
+            $dflt ($($pool)*) $($($rest)*)?
+        )
+    };
+    (@parse $biased:expr, [$($arms:tt)*] $dflt:tt ($id:ident $($pool:ident)*)
+        wait($w:expr) => $body:expr $(, $($rest:tt)*)?
+    ) => {
+        $crate::__select_native!(
+            @parse $biased, [$($arms)* ($id [$crate::select::WaitArm::new(&$w)] [] => $body)]
+            $dflt ($($pool)*) $($($rest)*)?
+        )
+    };
+    (@parse $biased:expr, [$($arms:tt)*] $dflt:tt ($id:ident $($pool:ident)*)
+        io($io:expr) -> $res:pat => $body:expr $(, $($rest:tt)*)?
+    ) => {
+        $crate::__select_native!(
+            @parse $biased, [$($arms)* ($id [$crate::select::IoArm::new(&$io)] [let $res = $id.take();] => $body)]
+            $dflt ($($pool)*) $($($rest)*)?
+        )
+    };
+    (@parse $biased:expr, $arms:tt [$($dflt:tt)+] $pool:tt timeout $($rest:tt)*) => {
+        compile_error!("select! can have at most one `default` or `timeout` arm")
+    };
+    (@parse $biased:expr, $arms:tt [$($dflt:tt)+] $pool:tt default $($rest:tt)*) => {
+        compile_error!("select! can have at most one `default` or `timeout` arm")
+    };
+    (@parse $biased:expr, $arms:tt [] $pool:tt
+        timeout($dur:expr) => $body:expr $(, $($rest:tt)*)?
+    ) => {
+        $crate::__select_native!(
+            @parse $biased, $arms [(Some($dur)) => $body] $pool $($($rest)*)?
+        )
+    };
+    (@parse $biased:expr, $arms:tt [] $pool:tt
+        default => $body:expr $(, $($rest:tt)*)?
+    ) => {
+        $crate::__select_native!(
+            @parse $biased, $arms [(Some(::std::time::Duration::ZERO)) => $body] $pool $($($rest)*)?
+        )
+    };
+    (@parse $biased:expr, $arms:tt $dflt:tt $pool:tt $($rest:tt)+) => {
+        compile_error!("invalid arm in select!, expect `recv`, `send`, `wait`, `io`, `timeout` or `default`")
+    };
+
+    // run the select and dispatch the arm body
+    (@run $biased:expr, [$(($id:ident [$arm:expr] $bind:tt => $body:expr))*] []) => {
+        $crate::__select_native!(@run $biased, [$(($id [$arm] $bind => $body))*]
+            [(None) => unreachable!("select! without timeout returns nothing")])
+    };
+    (@run $biased:expr, [$(($id:ident [$arm:expr] [$($bind:tt)*] => $body:expr))*]
+        [($dur:expr) => $dflt:expr]
+    ) => {{
+        $( let mut $id = $arm; )*
+        let _ = $crate::select::select_arms(
+            &mut [$(&mut $id as &mut dyn $crate::select::Arm),*],
+            $biased,
+            $dur,
+        );
+        match () {
+            $( _ if $id.is_selected() => {
+                $($bind)*
+                $body
+            } )*
+            _ => $dflt,
+        }
+    }};
+}
+
 /// macro used to join all scoped sub coroutines
 #[macro_export]
 macro_rules! join {
@@ -0,0 +1,210 @@
+//! runtime metrics
+//!
+//! the counters are sharded by worker so that updating them on the hot path
+//! is just an uncontended atomic add, the threads that are not workers of the
+//! runtime share an extra shard. the gauges are read from the underlying
+//! data structures when the metrics is collected.
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use crate::scheduler::{with_default_scheduler, Scheduler, WORKER_ID, WORKER_SCHED};
+use crossbeam::utils::CachePadded;
+
+#[derive(Default)]
+pub struct Shard {
+    pub spawned: AtomicU64,
+    pub finished: AtomicU64,
+    pub steal_attempts: AtomicU64,
+    pub steal_successes: AtomicU64,
+    pub poll_wakeups: AtomicU64,
+    pub pool_gets: AtomicU64,
+}
+
+/// the counters of a runtime
+pub struct Stats {
+    // one for each worker, the last one is for the other threads
+    shards: Vec<CachePadded<Shard>>,
+    // the number of fds registered to the selector
+    pub registered_fds: AtomicUsize,
+}
+
+impl Stats {
+    pub fn new(workers: usize) -> Self {
+        Stats {
+            shards: (0..=workers).map(|_| Default::default()).collect(),
+            registered_fds: AtomicUsize::new(0),
+        }
+    }
+
+    /// get the shard of the worker
+    #[inline]
+    pub fn worker(&self, id: usize) -> &Shard {
+        &self.shards[id]
+    }
+
+    /// get the shard of the current thread
+    #[inline]
+    pub fn current(&self, sched: &Scheduler) -> &Shard {
+        let id = WORKER_ID.get();
+        if id != usize::MAX && std::ptr::eq(WORKER_SCHED.get(), sched) {
+            &self.shards[id]
+        } else {
+            &self.shards[self.shards.len() - 1]
+        }
+    }
+
+    #[inline]
+    pub fn inc(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn sum<F: Fn(&Shard) -> &AtomicU64>(&self, f: F) -> u64 {
+        self.shards
+            .iter()
+            .map(|s| f(s).load(Ordering::Relaxed))
+            .sum()
+    }
+}
+
+/// Metrics of a worker thread
+#[derive(Debug, Clone, Default)]
+pub struct WorkerMetrics {
+    /// number of coroutines in the local run queue
+    pub local_queue_depth: usize,
+    /// number of coroutines in the global run queue
+    pub global_queue_depth: usize,
+    /// number of times the worker tried to steal from the others
+    pub steal_attempts: u64,
+    /// number of times the worker stole some coroutines
+    pub steal_successes: u64,
+    /// number of times the worker returned from polling the selector
+    pub poll_wakeups: u64,
+}
+
+/// Metrics of a runtime
+///
+/// the counters are totals since the runtime started, the others are gauges
+/// sampled when the metrics is collected
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    /// number of coroutines that are not finished
+    pub live_coroutines: usize,
+    /// number of spawned coroutines
+    pub spawned: u64,
+    /// number of finished coroutines
+    pub finished: u64,
+    /// metrics of each worker
+    pub workers: Vec<WorkerMetrics>,
+    /// number of pending timers of the timer thread
+    pub timer_list_size: usize,
+    /// number of cached coroutines in the pool
+    pub pool_size: usize,
+    /// number of coroutines reused from the pool
+    pub pool_hits: u64,
+    /// number of coroutines created because the pool is empty
+    pub pool_misses: u64,
+    /// number of fds registered to the selector
+    pub registered_fds: usize,
+}
+
+impl Metrics {
+    pub(crate) fn collect(sched: &Scheduler) -> Self {
+        let stats = &sched.stats;
+        let workers = (0..sched.workers)
+            .map(|id| {
+                let shard = stats.worker(id);
+                let (local_queue_depth, global_queue_depth) = sched.queue_depth(id);
+                WorkerMetrics {
+                    local_queue_depth,
+                    global_queue_depth,
+                    steal_attempts: shard.steal_attempts.load(Ordering::Relaxed),
+                    steal_successes: shard.steal_successes.load(Ordering::Relaxed),
+                    poll_wakeups: shard.poll_wakeups.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        let pool_gets = stats.sum(|s| &s.pool_gets);
+        let pool_misses = sched.pool.misses();
+        Metrics {
+            live_coroutines: sched.registry.len(),
+            spawned: stats.sum(|s| &s.spawned),
+            finished: stats.sum(|s| &s.finished),
+            workers,
+            timer_list_size: sched.timer_list_size(),
+            pool_size: sched.pool.size(),
+            pool_hits: pool_gets.saturating_sub(pool_misses),
+            pool_misses,
+            registered_fds: stats.registered_fds.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// get the metrics of the default runtime
+///
+/// it returns the default value if the runtime is not started
+///
+/// # Examples
+///
+/// ```rust
+/// may::go!(|| {}).join().unwrap();
+/// let m = may::metrics();
+/// assert!(m.spawned >= 1);
+/// println!("{m:?}");
+/// ```
+pub fn metrics() -> Metrics {
+    with_default_scheduler(Metrics::collect).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::TcpListener;
+    use crate::runtime::{Runtime, RuntimeBuilder};
+    use std::time::Duration;
+
+    // the counters of the finished coroutines are updated after join returns
+    fn wait_for<F: Fn(&super::Metrics) -> bool>(rt: &Runtime, f: F) -> super::Metrics {
+        for _ in 0..1000 {
+            let m = rt.metrics();
+            if f(&m) {
+                return m;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        panic!("metrics not matched: {:?}", rt.metrics());
+    }
+
+    #[test]
+    fn runtime_metrics() {
+        let rt = RuntimeBuilder::new()
+            .workers(1)
+            .worker_pin(false)
+            .build()
+            .unwrap();
+        assert_eq!(rt.metrics().workers.len(), 1);
+
+        rt.block_on(|| {
+            let v: Vec<_> = (0..10).map(|i| go!(move || i)).collect();
+            for h in v {
+                h.join().unwrap();
+            }
+        });
+        let m = wait_for(&rt, |m| m.live_coroutines == 0 && m.finished == 11);
+        assert_eq!(m.spawned, 11);
+        assert_eq!(m.pool_hits + m.pool_misses, 11);
+        assert!(m.pool_size > 0);
+        assert!(m.workers[0].poll_wakeups > 0);
+
+        let listener = {
+            let _guard = rt.enter();
+            TcpListener::bind("127.0.0.1:0").unwrap()
+        };
+        assert_eq!(rt.metrics().registered_fds, 1);
+        drop(listener);
+        assert_eq!(rt.metrics().registered_fds, 0);
+
+        // canceled by the shutdown
+        let _h = unsafe { rt.spawn(|| crate::coroutine::sleep(Duration::from_secs(1000))) };
+        let m = wait_for(&rt, |m| m.timer_list_size == 1);
+        assert_eq!(m.live_coroutines, 1);
+        rt.shutdown(Duration::from_millis(10)).unwrap();
+    }
+}
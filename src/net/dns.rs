@@ -0,0 +1,128 @@
+use std::any::type_name;
+use std::io;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::vec;
+
+use crate::blocking_pool;
+use crate::coroutine_impl::is_coroutine;
+
+// the address is moved to the blocking thread while the coroutine is parked,
+// it's the same as the coroutine is resumed on that thread
+struct SendAddr<A>(A);
+
+unsafe impl<A> Send for SendAddr<A> {}
+
+impl<A> SendAddr<A> {
+    fn into_inner(self) -> A {
+        self.0
+    }
+}
+
+// cast the address to the std type if they are the same type
+//
+// the names of the std types are unique
+unsafe fn cast<A, B>(addr: &A) -> Option<&B> {
+    (type_name::<A>() == type_name::<B>()).then(|| &*(addr as *const A as *const B))
+}
+
+// return true if the address is converted without a name lookup
+fn is_literal<A: ToSocketAddrs>(addr: &A) -> bool {
+    // only the string impls of std may look up the name, they return a vec
+    if type_name::<A::Iter>() != type_name::<vec::IntoIter<SocketAddr>>() {
+        return true;
+    }
+    unsafe {
+        if let Some(s) = cast::<A, &str>(addr) {
+            return s.parse::<SocketAddr>().is_ok();
+        }
+        if let Some(s) = cast::<A, String>(addr) {
+            return s.parse::<SocketAddr>().is_ok();
+        }
+        if let Some((host, _)) = cast::<A, (&str, u16)>(addr) {
+            return host.parse::<IpAddr>().is_ok();
+        }
+        if let Some((host, _)) = cast::<A, (String, u16)>(addr) {
+            return host.parse::<IpAddr>().is_ok();
+        }
+    }
+    false
+}
+
+/// Resolve the address to a list of socket addresses
+///
+/// this is the same as `ToSocketAddrs::to_socket_addrs` except that when
+/// called in a coroutine the name lookup is offloaded to the blocking pool,
+/// so that a slow resolver would not stall the worker thread. the socket
+/// addresses and the ip literals are converted in place
+///
+/// # Examples
+///
+/// ```rust
+/// let addrs = may::net::lookup_host("localhost:80").unwrap();
+/// for addr in addrs {
+///     println!("{addr}");
+/// }
+/// ```
+pub fn lookup_host<A: ToSocketAddrs>(addr: A) -> io::Result<vec::IntoIter<SocketAddr>> {
+    fn resolve<A: ToSocketAddrs>(addr: &A) -> io::Result<vec::IntoIter<SocketAddr>> {
+        addr.to_socket_addrs()
+            .map(|addrs| addrs.collect::<Vec<_>>().into_iter())
+    }
+
+    if !is_coroutine() || is_literal(&addr) {
+        return resolve(&addr);
+    }
+
+    // the coroutine is parked until the resolve is done, the address is
+    // moved back so that it's dropped in the coroutine
+    let addr = SendAddr(addr);
+    let (addr, ret) = blocking_pool::run(move || {
+        let addr = addr.into_inner();
+        let ret = resolve(&addr);
+        (SendAddr(addr), ret)
+    });
+    drop(addr.into_inner());
+    ret
+}
+
+// resolve the address to the first socket address
+pub(crate) fn resolve_one<A: ToSocketAddrs>(addr: A) -> io::Result<SocketAddr> {
+    lookup_host(addr)?.next().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "could not resolve to any addresses",
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_in_coroutine() {
+        let h = go!(|| {
+            let addrs: Vec<_> = lookup_host("localhost:8080").unwrap().collect();
+            assert!(!addrs.is_empty());
+            assert!(addrs.iter().all(|a| a.port() == 8080));
+
+            let addr = resolve_one(("127.0.0.1", 80)).unwrap();
+            assert_eq!(addr, "127.0.0.1:80".parse().unwrap());
+            lookup_host("not a valid address").is_err()
+        });
+        assert!(h.join().unwrap());
+    }
+
+    #[test]
+    fn literal_addrs() {
+        let addr: SocketAddr = "127.0.0.1:80".parse().unwrap();
+        assert!(is_literal(&addr));
+        assert!(is_literal(&(addr.ip(), 80)));
+        assert!(is_literal(&"[::1]:80"));
+        assert!(is_literal(&"127.0.0.1:80".to_owned()));
+        assert!(is_literal(&("::1", 80)));
+        assert!(is_literal(&("127.0.0.1".to_owned(), 80)));
+        assert!(!is_literal(&"localhost:80"));
+        assert!(!is_literal(&("localhost", 80)));
+    }
+}
@@ -1,8 +1,11 @@
 //! Networking primitives
 //!
 
+mod dns;
 mod tcp;
 mod udp;
 
+pub use self::dns::lookup_host;
+pub(crate) use self::dns::resolve_one;
 pub use self::tcp::{TcpListener, TcpStream};
 pub use self::udp::UdpSocket;
@@ -1,8 +1,8 @@
 use std::io::{self, Read, Write};
 use std::net::{self, Shutdown, SocketAddr, ToSocketAddrs};
-#[cfg(feature = "io_timeout")]
 use std::time::Duration;
 
+use crate::coroutine_impl::spawn;
 use crate::io as io_impl;
 use crate::io::net as net_impl;
 use crate::io::split_io::{SplitIo, SplitReader, SplitWriter};
@@ -12,6 +12,7 @@ use crate::io::sys::mod_socket;
 use crate::io::AsIoData;
 #[cfg(feature = "io_timeout")]
 use crate::sync::atomic_dur::AtomicDuration;
+use crate::sync::mpsc;
 use crate::yield_now::yield_with_io;
 
 // ===== TcpStream =====
@@ -55,7 +56,8 @@ impl TcpStream {
         &mut self.sys
     }
 
-    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
+    // connect to a single address
+    fn connect_addr(addr: SocketAddr) -> io::Result<TcpStream> {
         let mut c = net_impl::TcpStreamConnect::new(
             addr,
             #[cfg(feature = "io_timeout")]
@@ -73,6 +75,25 @@ impl TcpStream {
         c.done()
     }
 
+    /// Opens a TCP connection to a remote host.
+    ///
+    /// if the address is resolved to multiple addresses, they are tried in
+    /// the RFC 8305 (happy eyeballs) style. the IPv6 and IPv4 addresses are
+    /// interleaved and a new attempt is started in a new coroutine every
+    /// 250ms or once the previous one failed, the first established stream
+    /// is returned and the other attempts are canceled.
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<TcpStream> {
+        let addrs: Vec<_> = crate::net::lookup_host(addr)?.collect();
+        match addrs.len() {
+            0 => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any addresses",
+            )),
+            1 => TcpStream::connect_addr(addrs[0]),
+            _ => happy_eyeballs_connect(addrs),
+        }
+    }
+
     #[cfg(feature = "io_timeout")]
     pub fn connect_timeout(addr: &SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
         let mut c = net_impl::TcpStreamConnect::new(addr, Some(timeout))?;
@@ -339,6 +360,127 @@ impl io_impl::AsIoData for TcpStream {
     }
 }
 
+// ===== Happy Eyeballs =====
+//
+//
+
+// the delay before starting the next connection attempt, RFC 8305 section 5
+const CONNECTION_ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+// interleave the address families, starting with the first resolved one
+// RFC 8305 section 4
+fn interleave_addrs(addrs: Vec<SocketAddr>) -> Vec<SocketAddr> {
+    let first_v6 = addrs[0].is_ipv6();
+    let (mut preferred, mut others): (Vec<_>, Vec<_>) =
+        addrs.into_iter().partition(|a| a.is_ipv6() == first_v6);
+    preferred.reverse();
+    others.reverse();
+
+    let mut ret = Vec::with_capacity(preferred.len() + others.len());
+    loop {
+        match (preferred.pop(), others.pop()) {
+            (None, None) => return ret,
+            (a, b) => ret.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+type AttemptResult = (SocketAddr, io::Result<TcpStream>);
+
+// report the result of a connection attempt exactly once, an attempt that
+// is canceled or panicked is reported as an error when it's dropped
+struct Attempt {
+    addr: SocketAddr,
+    tx: Option<mpsc::Sender<AttemptResult>>,
+}
+
+impl Attempt {
+    fn finish(mut self, ret: io::Result<TcpStream>) {
+        if let Some(tx) = self.tx.take() {
+            tx.send((self.addr, ret)).ok();
+        }
+    }
+}
+
+impl Drop for Attempt {
+    fn drop(&mut self) {
+        if let Some(tx) = self.tx.take() {
+            let err = io::Error::other("connection attempt aborted");
+            tx.send((self.addr, Err(err))).ok();
+        }
+    }
+}
+
+fn happy_eyeballs_connect(addrs: Vec<SocketAddr>) -> io::Result<TcpStream> {
+    let (tx, rx) = mpsc::channel();
+    let mut pending = interleave_addrs(addrs).into_iter();
+    let mut attempts = Vec::new();
+    let mut errors = Vec::new();
+    let mut last_err = None;
+
+    // start the next attempt in a new coroutine, return false if no more
+    let mut start_next = |attempts: &mut Vec<_>| match pending.next() {
+        Some(addr) => {
+            let attempt = Attempt {
+                addr,
+                tx: Some(tx.clone()),
+            };
+            let h = unsafe { spawn(move || attempt.finish(TcpStream::connect_addr(addr))) };
+            attempts.push(h);
+            true
+        }
+        None => false,
+    };
+
+    let mut has_pending = start_next(&mut attempts);
+    let mut running = 1;
+    let ret = loop {
+        if running == 0 {
+            break None;
+        }
+
+        let ret = if has_pending {
+            rx.recv_timeout(CONNECTION_ATTEMPT_DELAY).ok()
+        } else {
+            rx.recv().ok()
+        };
+
+        match ret {
+            Some((_, Ok(s))) => break Some(s),
+            Some((addr, Err(e))) => {
+                // the attempt failed, start the next one right now
+                running -= 1;
+                errors.push(format!("{addr}: {e}"));
+                last_err = Some(e);
+            }
+            // the attempt delay passed without any result
+            None => {}
+        }
+
+        if has_pending {
+            has_pending = start_next(&mut attempts);
+            if has_pending {
+                running += 1;
+            }
+        }
+    };
+
+    // cancel the other attempts
+    for h in attempts.iter() {
+        if !h.is_done() {
+            unsafe { h.coroutine().cancel() };
+        }
+    }
+
+    ret.ok_or_else(|| {
+        let kind = last_err.map_or(io::ErrorKind::Other, |e| e.kind());
+        io::Error::new(
+            kind,
+            format!("failed to connect to any address: {}", errors.join(", ")),
+        )
+    })
+}
+
 // ===== TcpListener =====
 //
 //
@@ -365,9 +507,23 @@ impl TcpListener {
     }
 
     pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<TcpListener> {
+        let mut last_err = None;
+        for addr in crate::net::lookup_host(addr)? {
+            match TcpListener::bind_addr(addr) {
+                Ok(l) => return Ok(l),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any addresses",
+            )
+        }))
+    }
+
+    fn bind_addr(addr: SocketAddr) -> io::Result<TcpListener> {
         use socket2::{Domain, Socket, Type};
-        let mut addrs = addr.to_socket_addrs()?;
-        let addr = addrs.next().unwrap();
         let listener = match &addr {
             SocketAddr::V4(_) => Socket::new(Domain::IPV4, Type::STREAM, None)?,
             SocketAddr::V6(_) => Socket::new(Domain::IPV6, Type::STREAM, None)?,
@@ -380,9 +536,6 @@ impl TcpListener {
         listener.set_reuse_port(true)?;
 
         listener.bind(&addr.into())?;
-        // for addr in addrs {
-        //     listener.bind(&addr.into())?;
-        // }
         listener.listen(1024)?;
 
         let s = listener.into();
@@ -584,3 +737,54 @@ impl FromRawSocket for TcpListener {
             .unwrap_or_else(|e| panic!("from_raw_socket for TcpListener, err = {e:?}"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interleave_families() {
+        let addrs: Vec<SocketAddr> = ["[::1]:1", "[::1]:2", "[::1]:3", "127.0.0.1:4"]
+            .iter()
+            .map(|a| a.parse().unwrap())
+            .collect();
+        let ports: Vec<_> = interleave_addrs(addrs).iter().map(|a| a.port()).collect();
+        assert_eq!(ports, vec![1, 4, 2, 3]);
+    }
+
+    #[test]
+    fn connect_fallback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let good = listener.local_addr().unwrap();
+        // nothing is listening on the port of the dropped listener
+        let bad = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let h = go!(move || {
+            let s = TcpStream::connect(&[bad, good][..]).unwrap();
+            assert_eq!(s.peer_addr().unwrap(), good);
+            TcpStream::connect(&[bad, bad][..]).unwrap_err()
+        });
+        let (_s, _) = listener.accept().unwrap();
+        let e = h.join().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::ConnectionRefused);
+        assert!(e.to_string().contains(&bad.to_string()));
+    }
+
+    #[test]
+    fn aborted_attempt() {
+        let (tx, rx) = mpsc::channel();
+        let addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let h = go!(move || {
+            let _attempt = Attempt { addr, tx: Some(tx) };
+            panic!("attempt panicked");
+        });
+        h.join().unwrap_err();
+        // the panicked attempt is still reported
+        let (a, ret) = rx.recv().unwrap();
+        assert_eq!(a, addr);
+        assert!(ret.is_err());
+    }
+}
@@ -41,13 +41,15 @@ impl UdpSocket {
     }
 
     pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<UdpSocket> {
-        net::UdpSocket::bind(addr).and_then(UdpSocket::new)
+        let addrs = crate::net::lookup_host(addr)?;
+        net::UdpSocket::bind(addrs.as_slice()).and_then(UdpSocket::new)
     }
 
     pub fn connect<A: ToSocketAddrs>(&self, addr: A) -> io::Result<()> {
         // for udp connect it's a nonblocking operation
         // so we just use the system call
-        self.sys.connect(addr)
+        let addrs = crate::net::lookup_host(addr)?;
+        self.sys.connect(addrs.as_slice())
     }
 
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
@@ -81,11 +83,12 @@ impl UdpSocket {
     }
 
     pub fn send_to<A: ToSocketAddrs>(&self, buf: &[u8], addr: A) -> io::Result<usize> {
+        let addr = crate::net::resolve_one(addr)?;
         #[cfg(unix)]
         {
             self._io.reset();
             // this is an earlier return try for nonblocking read
-            match self.sys.send_to(buf, &addr) {
+            match self.sys.send_to(buf, addr) {
                 Ok(n) => return Ok(n),
                 Err(e) => {
                     // raw_os_error is faster than kind
@@ -1,3 +1,4 @@
 #![cfg(unix)]
 
 pub mod net;
+pub mod signal;
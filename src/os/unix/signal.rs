@@ -0,0 +1,334 @@
+//! Unix signal handling for coroutines
+//!
+//! the signals are caught by a handler that writes the signal number to a
+//! self-pipe, the read end is registered to the selector and a dispatcher
+//! coroutine forwards the signals to all the interested `Signals` instances.
+//!
+//! once a signal is registered the default action of it is replaced, even if
+//! all the `Signals` instances are dropped the signal would be ignored
+use std::fmt;
+use std::io::{self, Read};
+use std::mem;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
+use std::ptr;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::mpsc::RecvError;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::coroutine_impl::spawn;
+use crate::io::CoIo;
+use crate::select::{SelectRecv, Watch};
+use crate::sync::mpsc::{channel, Receiver, Sender};
+use crate::sync::Blocker;
+use parking_lot::Mutex;
+
+pub use libc::{SIGALRM, SIGCHLD, SIGHUP, SIGINT, SIGPIPE, SIGQUIT, SIGTERM, SIGUSR1, SIGUSR2};
+
+// the signals that can't or shouldn't be caught
+const FORBIDDEN: &[i32] = &[
+    libc::SIGKILL,
+    libc::SIGSTOP,
+    libc::SIGILL,
+    libc::SIGFPE,
+    libc::SIGSEGV,
+    libc::SIGBUS,
+];
+
+// the write end of the self-pipe used in the signal handler
+static WRITE_FD: AtomicI32 = AtomicI32::new(-1);
+
+static REGISTRY: Mutex<Option<Registry>> = Mutex::new(None);
+
+struct Subscriber {
+    id: usize,
+    // bit mask of the interested signals
+    mask: u64,
+    tx: Sender<i32>,
+}
+
+struct Registry {
+    // keep the write end of the self-pipe alive
+    _writer: UnixStream,
+    subscribers: Vec<Subscriber>,
+    // bit mask of the signals that the handler is installed
+    installed: u64,
+    next_id: usize,
+}
+
+impl Registry {
+    fn new() -> io::Result<Self> {
+        let (reader, writer) = UnixStream::pair()?;
+        // the signal handler must never block
+        writer.set_nonblocking(true)?;
+        let reader = CoIo::new(reader)?;
+        WRITE_FD.store(writer.as_raw_fd(), Ordering::Release);
+        unsafe { spawn(move || dispatch(reader)) };
+        Ok(Registry {
+            _writer: writer,
+            subscribers: Vec::new(),
+            installed: 0,
+            next_id: 0,
+        })
+    }
+}
+
+// reset the registry when the dispatcher is canceled by the runtime shutdown
+struct ResetGuard;
+
+impl Drop for ResetGuard {
+    fn drop(&mut self) {
+        WRITE_FD.store(-1, Ordering::Release);
+        REGISTRY.lock().take();
+    }
+}
+
+// forward the caught signals to the subscribers
+fn dispatch(mut reader: CoIo<UnixStream>) {
+    let _guard = ResetGuard;
+    let mut buf = [0u8; 64];
+    loop {
+        let n = match reader.read(&mut buf) {
+            // the write end is closed
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) => match e.kind() {
+                io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock => continue,
+                _ => {
+                    error!("signal pipe read error: {e}");
+                    break;
+                }
+            },
+        };
+        let registry = REGISTRY.lock();
+        let registry = registry.as_ref().expect("no signal registry");
+        for &signo in &buf[..n] {
+            let bit = 1u64 << signo;
+            for s in registry.subscribers.iter().filter(|s| s.mask & bit != 0) {
+                s.tx.send(signo as i32).ok();
+            }
+        }
+    }
+}
+
+extern "C" fn handler(signo: libc::c_int) {
+    // only async-signal-safe functions are allowed here
+    let errno = nix::errno::Errno::last_raw();
+    let fd = WRITE_FD.load(Ordering::Acquire);
+    if fd >= 0 {
+        let b = signo as u8;
+        // the pipe may be full, the signal is coalesced in that case
+        unsafe { libc::write(fd, &b as *const u8 as *const libc::c_void, 1) };
+    }
+    nix::errno::Errno::set_raw(errno);
+}
+
+// return true if the signal is delivered to `Signals`
+pub(crate) fn is_registered(signo: i32) -> bool {
+    let registry = REGISTRY.lock();
+    let installed = registry.as_ref().map_or(0, |r| r.installed);
+    (1..64).contains(&signo) && installed & (1u64 << signo) != 0
+}
+
+fn install(signo: i32) -> io::Result<()> {
+    unsafe {
+        let mut sa: libc::sigaction = mem::zeroed();
+        sa.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        sa.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut sa.sa_mask);
+        if libc::sigaction(signo, &sa, ptr::null_mut()) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// A set of signals that are delivered to coroutines
+///
+/// `recv` parks the coroutine until one of the registered signals arrives,
+/// it can also be used as a `recv` arm in the `select!` macro. the same
+/// signal could be registered by multiple instances, each of them would
+/// receive a copy. signals that arrive in a burst may be coalesced
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use may::os::unix::signal::{Signals, SIGHUP, SIGTERM};
+///
+/// let signals = Signals::new([SIGTERM, SIGHUP]).unwrap();
+/// let h = may::go!(move || {
+///     for sig in signals {
+///         if sig == SIGTERM {
+///             break;
+///         }
+///         println!("reload config");
+///     }
+/// });
+/// h.join().unwrap();
+/// ```
+pub struct Signals {
+    id: usize,
+    rx: Receiver<i32>,
+}
+
+impl Signals {
+    /// register the signals and return the receiving handle
+    ///
+    /// return an `InvalidInput` error for the signals that can't be caught
+    /// such as `SIGKILL` or `SIGSEGV`, or the ones that are already used by
+    /// [`dump_on_signal`]
+    ///
+    /// [`dump_on_signal`]: ../../../coroutine/fn.dump_on_signal.html
+    pub fn new<I: IntoIterator<Item = i32>>(signals: I) -> io::Result<Signals> {
+        let mut mask = 0;
+        for signo in signals {
+            if !(1..64).contains(&signo) || FORBIDDEN.contains(&signo) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("signal {signo} can't be registered"),
+                ));
+            }
+            if crate::dump::is_dump_signal(signo) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("signal {signo} is used by dump_on_signal"),
+                ));
+            }
+            mask |= 1u64 << signo;
+        }
+
+        let mut registry = REGISTRY.lock();
+        if registry.is_none() {
+            *registry = Some(Registry::new()?);
+        }
+        let registry = registry.as_mut().unwrap();
+
+        for signo in 1..64 {
+            let bit = 1u64 << signo;
+            if mask & bit != 0 && registry.installed & bit == 0 {
+                install(signo)?;
+                registry.installed |= bit;
+            }
+        }
+
+        let (tx, rx) = channel();
+        let id = registry.next_id;
+        registry.next_id += 1;
+        registry.subscribers.push(Subscriber { id, mask, tx });
+        Ok(Signals { id, rx })
+    }
+
+    /// wait for the next signal and return the signal number
+    ///
+    /// return `None` if the signal dispatcher is gone, which happens when
+    /// the runtime is shutdown
+    pub fn recv(&self) -> Option<i32> {
+        self.rx.recv().ok()
+    }
+
+    /// wait for the next signal with a timeout
+    ///
+    /// return `None` if timeout
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<i32> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+
+    /// return the pending signal without blocking
+    pub fn try_recv(&self) -> Option<i32> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl Iterator for Signals {
+    type Item = i32;
+
+    fn next(&mut self) -> Option<i32> {
+        self.recv()
+    }
+}
+
+impl Drop for Signals {
+    fn drop(&mut self) {
+        if let Some(registry) = REGISTRY.lock().as_mut() {
+            registry.subscribers.retain(|s| s.id != self.id);
+        }
+    }
+}
+
+impl fmt::Debug for Signals {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Signals {{ .. }}")
+    }
+}
+
+impl Watch for Signals {
+    fn watch(&self, blocker: &Arc<Blocker>) {
+        self.rx.watch(blocker)
+    }
+
+    fn unwatch(&self, blocker: &Arc<Blocker>) {
+        self.rx.unwatch(blocker)
+    }
+}
+
+impl SelectRecv for Signals {
+    type Item = i32;
+
+    fn try_select_recv(&self) -> Option<Result<i32, RecvError>> {
+        self.rx.try_select_recv()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raise(signo: i32) {
+        unsafe { libc::kill(libc::getpid(), signo) };
+    }
+
+    #[test]
+    fn recv_signal() {
+        let signals = Signals::new([SIGUSR1]).unwrap();
+        let other = Signals::new([SIGUSR1]).unwrap();
+        let h = go!(move || signals.recv());
+        raise(SIGUSR1);
+        assert_eq!(h.join().unwrap(), Some(SIGUSR1));
+        // every instance get a copy
+        assert_eq!(other.recv_timeout(Duration::from_secs(5)), Some(SIGUSR1));
+    }
+
+    #[test]
+    fn select_signal() {
+        let signals = Signals::new([SIGUSR2]).unwrap();
+        let h = go!(move || {
+            let mut got = None;
+            select! {
+                recv(signals) -> sig => got = sig.ok(),
+                timeout(Duration::from_secs(5)) => {}
+            }
+            got
+        });
+        crate::coroutine::sleep(Duration::from_millis(10));
+        raise(SIGUSR2);
+        assert_eq!(h.join().unwrap(), Some(SIGUSR2));
+    }
+
+    #[test]
+    fn registry_closed() {
+        // the sender is dropped with the registry
+        let (tx, rx) = channel();
+        drop(tx);
+        let mut signals = Signals { id: usize::MAX, rx };
+        assert_eq!(signals.recv(), None);
+        assert_eq!(signals.next(), None);
+    }
+
+    #[test]
+    fn invalid_signal() {
+        let e = Signals::new([libc::SIGKILL]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+        assert!(Signals::new([0]).is_err());
+    }
+}
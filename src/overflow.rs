@@ -0,0 +1,386 @@
+//! report the stack overflow of coroutines
+//!
+//! each coroutine stack is allocated with a guard page below it. on unix when
+//! a coroutine runs into the guard page the SIGSEGV handler, which runs on an
+//! alternate signal stack of the thread, prints the id, name and stack size of
+//! the coroutine and then aborts the process. the faults that are not caused
+//! by a coroutine stack are passed to the previous handler.
+//!
+//! the backtrace in the report is a raw list of the return addresses, symbols
+//! can't be resolved in a signal handler. the first one is the faulting
+//! instruction, the others are collected by walking the frame pointers on the
+//! coroutine stack, so they are only complete when the program is built with
+//! `-C force-frame-pointers=yes`. the image base is printed along with them,
+//! the addresses can be resolved by `addr2line -e <exe> <addr - base>`
+//!
+//! the stack is located by the page that the coroutine entry frame is in, if
+//! the closure of the coroutine takes more than a page the overflow is not
+//! recognized and the fault goes to the previous handler.
+//!
+//! it's enabled by default, see [`Config::set_stack_overflow_report`].
+//!
+//! [`Config::set_stack_overflow_report`]: ../struct.Config.html#method.set_stack_overflow_report
+use std::fmt::{self, Write};
+use std::mem;
+
+use crate::coroutine_impl::Coroutine;
+
+// the report is formatted on the stack, it's truncated if too long
+struct Report {
+    buf: [u8; 1024],
+    len: usize,
+}
+
+impl Write for Report {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let n = s.len().min(self.buf.len() - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&s.as_bytes()[..n]);
+        self.len += n;
+        Ok(())
+    }
+}
+
+/// print the report of the overflowed coroutine and abort the process
+///
+/// it's called in the signal handler, so there is no allocation or lock.
+/// the message is written to stderr by a raw `write`
+pub(crate) fn abort(co: &Coroutine, frames: &[usize]) -> ! {
+    let size = co.stack_size();
+    let mut report = Report {
+        buf: [0; 1024],
+        len: 0,
+    };
+    let _ = write!(
+        report,
+        "\ncoroutine #{} '{}' has overflowed its stack, stack size = {size:#x} words ({} bytes)\n\
+         increase it by `coroutine::Builder::stack_size` or `Config::set_stack_size`\n",
+        co.id(),
+        co.name().unwrap_or("<unnamed>"),
+        size * mem::size_of::<usize>()
+    );
+    if !frames.is_empty() {
+        #[cfg(unix)]
+        let _ = writeln!(
+            report,
+            "backtrace (image base = {:#x}):",
+            self::unix::image_base()
+        );
+        #[cfg(not(unix))]
+        let _ = writeln!(report, "backtrace:");
+        for (i, addr) in frames.iter().enumerate() {
+            let _ = writeln!(report, "  #{i:<2} {addr:#018x}");
+        }
+    }
+    let msg = &report.buf[..report.len];
+
+    #[cfg(unix)]
+    unsafe {
+        libc::write(2, msg.as_ptr() as *const libc::c_void, msg.len());
+        libc::abort()
+    }
+    #[cfg(not(unix))]
+    {
+        use std::io::Write;
+        let _ = std::io::stderr().write_all(msg);
+        std::process::abort()
+    }
+}
+
+#[cfg(unix)]
+pub(crate) use self::unix::{init_thread, install, set_stack_guard};
+
+#[cfg(not(unix))]
+pub(crate) fn install() {}
+
+#[cfg(not(unix))]
+pub(crate) fn init_thread() {}
+
+#[cfg(unix)]
+mod unix {
+    use std::cell::RefCell;
+    use std::mem;
+    use std::ops::Range;
+    use std::ptr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Once, OnceLock};
+
+    use crate::config::config;
+    use crate::coroutine_impl::Coroutine;
+    use crate::local::get_co_local_data;
+    use generator::Gn;
+    use libc::{c_int, c_void, siginfo_t};
+
+    // big enough to format the report and run the previous handler
+    const ALT_STACK_SIZE: usize = 64 << 10;
+    // the max number of the addresses in the backtrace
+    const MAX_FRAMES: usize = 24;
+
+    // the actions of SIGSEGV and SIGBUS before the handler is installed
+    static PREV: OnceLock<[libc::sigaction; 2]> = OnceLock::new();
+    // the load address of the image that contains this crate
+    static IMAGE_BASE: AtomicUsize = AtomicUsize::new(0);
+    static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+    thread_local! {
+        static ALT_STACK: RefCell<Option<AltStack>> = const { RefCell::new(None) };
+    }
+
+    fn page_size() -> usize {
+        let page = PAGE_SIZE.load(Ordering::Relaxed);
+        if page != 0 {
+            return page;
+        }
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize };
+        PAGE_SIZE.store(page, Ordering::Relaxed);
+        page
+    }
+
+    pub fn image_base() -> usize {
+        IMAGE_BASE.load(Ordering::Relaxed)
+    }
+
+    // the alternate signal stack of a thread
+    struct AltStack {
+        // the mapping including the guard page
+        map: *mut c_void,
+        len: usize,
+        stack: libc::stack_t,
+        prev: libc::stack_t,
+    }
+
+    impl AltStack {
+        unsafe fn new() -> Option<AltStack> {
+            let page = page_size();
+            let len = ALT_STACK_SIZE + page;
+            let map = libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANON,
+                -1,
+                0,
+            );
+            if map == libc::MAP_FAILED {
+                return None;
+            }
+            libc::mprotect(map, page, libc::PROT_NONE);
+
+            let stack = libc::stack_t {
+                ss_sp: map.add(page),
+                ss_flags: 0,
+                ss_size: ALT_STACK_SIZE,
+            };
+            let mut prev: libc::stack_t = mem::zeroed();
+            if libc::sigaltstack(&stack, &mut prev) != 0 {
+                libc::munmap(map, len);
+                return None;
+            }
+            Some(AltStack {
+                map,
+                len,
+                stack,
+                prev,
+            })
+        }
+    }
+
+    impl Drop for AltStack {
+        fn drop(&mut self) {
+            unsafe {
+                // the std may have already disabled it when the thread exits
+                let mut cur: libc::stack_t = mem::zeroed();
+                libc::sigaltstack(ptr::null(), &mut cur);
+                if cur.ss_sp == self.stack.ss_sp {
+                    libc::sigaltstack(&self.prev, ptr::null_mut());
+                }
+                libc::munmap(self.map, self.len);
+            }
+        }
+    }
+
+    /// install the handler for the process
+    pub fn install() {
+        static INSTALL: Once = Once::new();
+        if !config().get_stack_overflow_report() {
+            return;
+        }
+        INSTALL.call_once(|| unsafe {
+            // the generator installs its handler when the first one runs,
+            // run one first so that it wouldn't replace ours
+            Gn::<()>::new_opt(0x400, || {}).resume();
+
+            // dladdr is not async signal safe, look up the base here
+            let mut info: libc::Dl_info = mem::zeroed();
+            if libc::dladdr(install as *const c_void, &mut info) != 0 {
+                IMAGE_BASE.store(info.dli_fbase as usize, Ordering::Relaxed);
+            }
+
+            let mut prev: [libc::sigaction; 2] = mem::zeroed();
+            for (i, &sig) in [libc::SIGSEGV, libc::SIGBUS].iter().enumerate() {
+                libc::sigaction(sig, ptr::null(), &mut prev[i]);
+            }
+            let _ = PREV.set(prev);
+
+            let mut action: libc::sigaction = mem::zeroed();
+            action.sa_flags = libc::SA_SIGINFO | libc::SA_ONSTACK;
+            action.sa_sigaction = on_fault
+                as unsafe extern "C" fn(c_int, *mut siginfo_t, *mut c_void)
+                as libc::sighandler_t;
+            libc::sigemptyset(&mut action.sa_mask);
+            for sig in [libc::SIGSEGV, libc::SIGBUS] {
+                libc::sigaction(sig, &action, ptr::null_mut());
+            }
+        });
+    }
+
+    /// set up the alternate signal stack for the thread that runs coroutines
+    pub fn init_thread() {
+        if !config().get_stack_overflow_report() {
+            return;
+        }
+        ALT_STACK.with(|s| {
+            let mut s = s.borrow_mut();
+            if s.is_none() {
+                *s = unsafe { AltStack::new() };
+            }
+        });
+    }
+
+    /// record the guard page of the current coroutine stack
+    ///
+    /// the stack top is page aligned and the entry frame is in the top page,
+    /// the guard page is right below the stack
+    #[inline(never)]
+    pub fn set_stack_guard() {
+        let top = 0u8;
+        if let Some(local) = get_co_local_data() {
+            let local = unsafe { local.as_ref() };
+            let len = local.get_stack_len();
+            if len == 0 {
+                return;
+            }
+            let page = page_size();
+            let top = (&top as *const u8 as usize | (page - 1)) + 1;
+            local.set_stack_guard(top - len - page);
+        }
+    }
+
+    // return the running coroutine and its stack if the address is in the
+    // guard page
+    unsafe fn overflowed(addr: usize) -> Option<(&'static Coroutine, Range<usize>)> {
+        let local = &*get_co_local_data()?.as_ptr();
+        let guard = local.get_stack_guard();
+        if guard == 0 {
+            return None;
+        }
+        let bottom = guard + page_size();
+        if !(guard..bottom).contains(&addr) {
+            return None;
+        }
+        Some((local.get_co(), bottom..bottom + local.get_stack_len()))
+    }
+
+    // the faulting pc and the frame pointer from the signal context
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    unsafe fn context_regs(ctx: *mut c_void) -> Option<(usize, usize)> {
+        let gregs = &(*(ctx as *const libc::ucontext_t)).uc_mcontext.gregs;
+        let pc = gregs[libc::REG_RIP as usize] as usize;
+        Some((pc, gregs[libc::REG_RBP as usize] as usize))
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+    unsafe fn context_regs(ctx: *mut c_void) -> Option<(usize, usize)> {
+        let mc = &(*(ctx as *const libc::ucontext_t)).uc_mcontext;
+        Some((mc.pc as usize, mc.regs[29] as usize))
+    }
+
+    #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+    unsafe fn context_regs(ctx: *mut c_void) -> Option<(usize, usize)> {
+        let ss = &(*(*(ctx as *const libc::ucontext_t)).uc_mcontext).__ss;
+        Some((ss.__rip as usize, ss.__rbp as usize))
+    }
+
+    #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+    unsafe fn context_regs(ctx: *mut c_void) -> Option<(usize, usize)> {
+        let ss = &(*(*(ctx as *const libc::ucontext_t)).uc_mcontext).__ss;
+        Some((ss.__pc as usize, ss.__fp as usize))
+    }
+
+    #[cfg(not(any(
+        all(
+            target_os = "linux",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        ),
+        all(
+            target_os = "macos",
+            any(target_arch = "x86_64", target_arch = "aarch64")
+        )
+    )))]
+    unsafe fn context_regs(_ctx: *mut c_void) -> Option<(usize, usize)> {
+        None
+    }
+
+    // collect the return addresses by the frame pointer chain, only the
+    // frames that are inside the coroutine stack are read
+    unsafe fn backtrace(
+        ctx: *mut c_void,
+        stack: Range<usize>,
+        frames: &mut [usize; MAX_FRAMES],
+    ) -> usize {
+        let Some((pc, mut fp)) = context_regs(ctx) else {
+            return 0;
+        };
+        frames[0] = pc;
+        let mut n = 1;
+        let word = mem::size_of::<usize>();
+        while n < MAX_FRAMES && fp >= stack.start && fp + 2 * word <= stack.end {
+            if fp % word != 0 {
+                break;
+            }
+            // the saved frame pointer and then the return address
+            let next = *(fp as *const usize);
+            let ret = *((fp + word) as *const usize);
+            if ret == 0 {
+                break;
+            }
+            frames[n] = ret;
+            n += 1;
+            // the stack grows down, the caller frame must be higher
+            if next <= fp {
+                break;
+            }
+            fp = next;
+        }
+        n
+    }
+
+    unsafe extern "C" fn on_fault(signum: c_int, info: *mut siginfo_t, ctx: *mut c_void) {
+        let addr = (*info).si_addr() as usize;
+        if let Some((co, stack)) = overflowed(addr) {
+            let mut frames = [0; MAX_FRAMES];
+            let n = backtrace(ctx, stack, &mut frames);
+            super::abort(co, &frames[..n]);
+        }
+
+        // not a coroutine stack overflow, pass it to the previous handler
+        let prev = PREV.get().map(|p| p[(signum != libc::SIGSEGV) as usize]);
+        match prev {
+            Some(p) if p.sa_sigaction != libc::SIG_DFL && p.sa_sigaction != libc::SIG_IGN => {
+                if p.sa_flags & libc::SA_SIGINFO != 0 {
+                    let f: unsafe extern "C" fn(c_int, *mut siginfo_t, *mut c_void) =
+                        mem::transmute(p.sa_sigaction);
+                    f(signum, info, ctx)
+                } else {
+                    let f: unsafe extern "C" fn(c_int) = mem::transmute(p.sa_sigaction);
+                    f(signum)
+                }
+            }
+            _ => {
+                // the fault would be raised again with the default action
+                let mut action: libc::sigaction = mem::zeroed();
+                action.sa_sigaction = libc::SIG_DFL;
+                libc::sigaction(signum, &action, ptr::null_mut());
+            }
+        }
+    }
+}
@@ -7,7 +7,8 @@ use std::time::Duration;
 
 use crate::cancel::Cancel;
 use crate::coroutine_impl::{co_cancel_data, run_coroutine, CoroutineImpl, EventSource};
-use crate::scheduler::get_scheduler;
+use crate::dump::{set_state, CoroutineState};
+use crate::scheduler::{schedule, with_scheduler};
 use crate::sync::atomic_dur::AtomicDuration;
 use crate::sync::AtomicOption;
 use crate::timeout_list::TimeoutHandle;
@@ -109,7 +110,7 @@ impl Park {
     fn remove_timeout_handle(&self) {
         if let Some(h) = self.set_timeout_handle(None) {
             if h.is_link() {
-                get_scheduler().del_timer(h);
+                with_scheduler(|s| s.del_timer(h));
             }
             // when timeout the node is unlinked
             // just drop it to release memory
@@ -122,7 +123,7 @@ impl Park {
             if b_sync {
                 run_coroutine(co);
             } else {
-                get_scheduler().schedule(co);
+                schedule(co);
             }
         }
     }
@@ -152,6 +153,7 @@ impl Park {
 
         // what if the state is set before yield?
         // the subscribe would re-check it
+        set_state(CoroutineState::ParkedOnLock);
         yield_with(self);
         // clear the trigger state
         self.check_park();
@@ -204,7 +206,7 @@ impl EventSource for Park {
         let timeout_handle = self
             .timeout
             .take()
-            .map(|dur| get_scheduler().add_timer(dur, self.wait_co.clone()));
+            .map(|dur| with_scheduler(|s| s.add_timer(dur, self.wait_co.clone())));
         self.set_timeout_handle(timeout_handle);
 
         let _g = self.delay_drop();
@@ -1,4 +1,4 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 
 use crate::config::config;
 use crate::coroutine_impl::CoroutineImpl;
@@ -11,25 +11,63 @@ pub struct CoroutinePool {
     // the pool must support mpmc operation!
     pool: SegQueue<CoroutineImpl>,
     size: AtomicUsize,
+    // the pool is empty when get
+    misses: AtomicU64,
+    // follow the global config if not set
+    stack_size: Option<usize>,
+    capacity: Option<usize>,
 }
 
 impl CoroutinePool {
-    fn create_dummy_coroutine() -> CoroutineImpl {
-        Gn::new_opt(config().get_stack_size(), move || {
+    fn create_dummy_coroutine(&self) -> CoroutineImpl {
+        Gn::new_opt(self.stack_size(), move || {
             unreachable!("dummy coroutine should never be called");
         })
     }
 
-    pub fn new() -> Self {
-        let capacity = config().get_pool_capacity();
-        let pool = SegQueue::new();
+    pub fn new(stack_size: Option<usize>, capacity: Option<usize>) -> Self {
+        let mut pool = CoroutinePool {
+            pool: SegQueue::new(),
+            size: AtomicUsize::new(0),
+            misses: AtomicU64::new(0),
+            stack_size,
+            capacity,
+        };
+        let capacity = pool.capacity();
         for _ in 0..capacity {
-            let co = Self::create_dummy_coroutine();
-            pool.push(co);
+            let co = pool.create_dummy_coroutine();
+            pool.pool.push(co);
         }
-        let size = AtomicUsize::new(capacity);
+        *pool.size.get_mut() = capacity;
+        pool
+    }
+
+    /// the stack size of the pooled coroutines
+    #[inline]
+    pub fn stack_size(&self) -> usize {
+        self.stack_size.unwrap_or_else(|| config().get_stack_size())
+    }
+
+    /// the number of cached coroutines
+    pub fn size(&self) -> usize {
+        // it could be negative transiently in get
+        let size = self.size.load(Ordering::Relaxed);
+        if size > isize::MAX as usize {
+            0
+        } else {
+            size
+        }
+    }
 
-        CoroutinePool { pool, size }
+    /// the number of times that get a new coroutine
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.capacity
+            .unwrap_or_else(|| config().get_pool_capacity())
     }
 
     /// get a raw coroutine from the pool
@@ -40,7 +78,8 @@ impl CoroutinePool {
             Some(co) => co,
             None => {
                 self.size.fetch_add(1, Ordering::AcqRel);
-                Self::create_dummy_coroutine()
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                self.create_dummy_coroutine()
             }
         }
     }
@@ -50,7 +89,7 @@ impl CoroutinePool {
     pub fn put(&self, co: CoroutineImpl) {
         // discard the co if push failed
         let m = self.size.fetch_add(1, Ordering::AcqRel);
-        if m >= config().get_pool_capacity() {
+        if m >= self.capacity() {
             self.size.fetch_sub(1, Ordering::AcqRel);
             return;
         }
@@ -0,0 +1,455 @@
+//! coroutine version of `std::process`
+//!
+//! the child stdio pipes are non-blocking `CoIo` objects, and waiting for a
+//! child parks the coroutine instead of the worker thread. on linux the exit
+//! of the child is watched by a pidfd that is registered to the selector,
+//! on other platforms the wait is offloaded to the blocking pool
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::panic;
+use std::path::Path;
+use std::process;
+
+use crate::coroutine_impl::{is_coroutine, spawn};
+use crate::io::CoIo;
+
+pub use std::process::{ExitStatus, Output, Stdio};
+
+/// A process builder, the same as `std::process::Command`
+///
+/// # Examples
+///
+/// ```rust
+/// use may::process::{Command, Stdio};
+/// use std::io::Read;
+///
+/// let h = may::go!(|| {
+///     let mut child = Command::new("echo")
+///         .arg("hello")
+///         .stdout(Stdio::piped())
+///         .spawn()
+///         .unwrap();
+///     let mut s = String::new();
+///     child.stdout.take().unwrap().read_to_string(&mut s).unwrap();
+///     assert!(child.wait().unwrap().success());
+///     s
+/// });
+/// assert_eq!(h.join().unwrap(), "hello\n");
+/// ```
+pub struct Command {
+    inner: process::Command,
+    // if the stdio is configured by user
+    stdin_set: bool,
+    stdout_set: bool,
+    stderr_set: bool,
+}
+
+impl Command {
+    /// Constructs a new `Command` for launching the program at path `program`
+    pub fn new<S: AsRef<OsStr>>(program: S) -> Command {
+        Command::from(process::Command::new(program))
+    }
+
+    /// Adds an argument to pass to the program
+    pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut Command {
+        self.inner.arg(arg);
+        self
+    }
+
+    /// Adds multiple arguments to pass to the program
+    pub fn args<I, S>(&mut self, args: I) -> &mut Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.inner.args(args);
+        self
+    }
+
+    /// Inserts or updates an environment variable
+    pub fn env<K, V>(&mut self, key: K, val: V) -> &mut Command
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.inner.env(key, val);
+        self
+    }
+
+    /// Inserts or updates multiple environment variables
+    pub fn envs<I, K, V>(&mut self, vars: I) -> &mut Command
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.inner.envs(vars);
+        self
+    }
+
+    /// Removes an environment variable
+    pub fn env_remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Command {
+        self.inner.env_remove(key);
+        self
+    }
+
+    /// Clears all the environment variables
+    pub fn env_clear(&mut self) -> &mut Command {
+        self.inner.env_clear();
+        self
+    }
+
+    /// Sets the working directory for the child process
+    pub fn current_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Command {
+        self.inner.current_dir(dir);
+        self
+    }
+
+    /// Configuration for the child process's standard input handle
+    pub fn stdin<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
+        self.inner.stdin(cfg);
+        self.stdin_set = true;
+        self
+    }
+
+    /// Configuration for the child process's standard output handle
+    pub fn stdout<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
+        self.inner.stdout(cfg);
+        self.stdout_set = true;
+        self
+    }
+
+    /// Configuration for the child process's standard error handle
+    pub fn stderr<T: Into<Stdio>>(&mut self, cfg: T) -> &mut Command {
+        self.inner.stderr(cfg);
+        self.stderr_set = true;
+        self
+    }
+
+    /// Get a reference to the inner `std::process::Command`
+    pub fn as_std(&self) -> &process::Command {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner `std::process::Command`
+    ///
+    /// this is useful for the platform extensions like `CommandExt`
+    pub fn as_std_mut(&mut self) -> &mut process::Command {
+        &mut self.inner
+    }
+
+    // apply the default stdio that is not configured by user
+    fn spawn_with(&mut self, stdin: fn() -> Stdio, out: fn() -> Stdio) -> io::Result<Child> {
+        if !self.stdin_set {
+            self.inner.stdin(stdin());
+        }
+        if !self.stdout_set {
+            self.inner.stdout(out());
+        }
+        if !self.stderr_set {
+            self.inner.stderr(out());
+        }
+        Child::new(self.inner.spawn()?)
+    }
+
+    /// Executes the command as a child process, returning a handle to it
+    ///
+    /// by default stdin, stdout and stderr are inherited from the parent
+    pub fn spawn(&mut self) -> io::Result<Child> {
+        self.spawn_with(Stdio::inherit, Stdio::inherit)
+    }
+
+    /// Executes the command as a child process, waiting for it to finish and
+    /// collecting all of its output
+    ///
+    /// by default stdout and stderr are captured, stdin is closed
+    pub fn output(&mut self) -> io::Result<Output> {
+        self.spawn_with(Stdio::null, Stdio::piped)?
+            .wait_with_output()
+    }
+
+    /// Executes a command as a child process, waiting for it to finish and
+    /// collecting its status
+    pub fn status(&mut self) -> io::Result<ExitStatus> {
+        self.spawn()?.wait()
+    }
+}
+
+impl From<process::Command> for Command {
+    fn from(inner: process::Command) -> Self {
+        Command {
+            inner,
+            stdin_set: false,
+            stdout_set: false,
+            stderr_set: false,
+        }
+    }
+}
+
+impl fmt::Debug for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+macro_rules! child_stdio {
+    ($(#[$doc:meta])* $name:ident) => {
+        $(#[$doc])*
+        #[derive(Debug)]
+        pub struct $name(CoIo<process::$name>);
+
+        impl $name {
+            fn new(io: process::$name) -> io::Result<Self> {
+                CoIo::new(io).map($name).map_err(io::Error::from)
+            }
+
+            /// Get a reference to the inner `CoIo` object
+            pub fn inner(&self) -> &CoIo<process::$name> {
+                &self.0
+            }
+
+            /// Convert to the inner `CoIo` object
+            pub fn into_inner(self) -> CoIo<process::$name> {
+                self.0
+            }
+        }
+
+        impl AsRawFd for $name {
+            fn as_raw_fd(&self) -> RawFd {
+                self.0.as_raw_fd()
+            }
+        }
+    };
+}
+
+child_stdio! {
+    /// A handle to a child process's standard input
+    ChildStdin
+}
+
+child_stdio! {
+    /// A handle to a child process's standard output
+    ChildStdout
+}
+
+child_stdio! {
+    /// A handle to a child process's standard error
+    ChildStderr
+}
+
+impl Write for ChildStdin {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl Read for ChildStdout {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Read for ChildStderr {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+// a pollable fd that is readable when the child exits
+#[cfg(target_os = "linux")]
+fn pidfd_open(pid: u32) -> Option<CoIo<std::os::fd::OwnedFd>> {
+    use std::os::fd::FromRawFd;
+
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid as libc::pid_t, 0) };
+    if fd < 0 {
+        // the kernel doesn't support pidfd
+        return None;
+    }
+    let fd = unsafe { std::os::fd::OwnedFd::from_raw_fd(fd as RawFd) };
+    CoIo::new(fd).ok()
+}
+
+/// Representation of a running or exited child process
+pub struct Child {
+    inner: process::Child,
+    #[cfg(target_os = "linux")]
+    pidfd: Option<CoIo<std::os::fd::OwnedFd>>,
+    /// The handle for writing to the child's standard input, if piped
+    pub stdin: Option<ChildStdin>,
+    /// The handle for reading from the child's standard output, if piped
+    pub stdout: Option<ChildStdout>,
+    /// The handle for reading from the child's standard error, if piped
+    pub stderr: Option<ChildStderr>,
+}
+
+impl Child {
+    fn new(mut inner: process::Child) -> io::Result<Child> {
+        let pipes = (|| {
+            let stdin = inner.stdin.take().map(ChildStdin::new).transpose()?;
+            let stdout = inner.stdout.take().map(ChildStdout::new).transpose()?;
+            let stderr = inner.stderr.take().map(ChildStderr::new).transpose()?;
+            io::Result::Ok((stdin, stdout, stderr))
+        })();
+        let (stdin, stdout, stderr) = match pipes {
+            Ok(pipes) => pipes,
+            Err(e) => {
+                // nobody could wait for the child, kill and reap it
+                inner.kill().ok();
+                inner.wait().ok();
+                return Err(e);
+            }
+        };
+        Ok(Child {
+            // the child is not reaped yet, so the pid is not reused
+            #[cfg(target_os = "linux")]
+            pidfd: pidfd_open(inner.id()),
+            inner,
+            stdin,
+            stdout,
+            stderr,
+        })
+    }
+
+    /// Returns the OS-assigned process identifier of the child process
+    pub fn id(&self) -> u32 {
+        self.inner.id()
+    }
+
+    /// Forces the child process to exit
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.inner.kill()
+    }
+
+    /// Attempts to collect the exit status of the child if it has already exited
+    pub fn try_wait(&mut self) -> io::Result<Option<ExitStatus>> {
+        self.inner.try_wait()
+    }
+
+    /// Waits for the child to exit completely, returning the status that it
+    /// exited with
+    ///
+    /// the stdin of the child is closed before waiting. in coroutine context
+    /// only the coroutine is parked
+    pub fn wait(&mut self) -> io::Result<ExitStatus> {
+        drop(self.stdin.take());
+        if !is_coroutine() {
+            return self.inner.wait();
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(pidfd) = self.pidfd.as_ref() {
+            use crate::io::WaitIo;
+            loop {
+                if let Some(status) = self.inner.try_wait()? {
+                    return Ok(status);
+                }
+                pidfd.wait_io();
+            }
+        }
+
+        let inner = &mut self.inner;
+        crate::blocking_pool::run(|| inner.wait())
+    }
+
+    /// Simultaneously waits for the child to exit and collect all remaining
+    /// output on the stdout/stderr handles
+    pub fn wait_with_output(mut self) -> io::Result<Output> {
+        drop(self.stdin.take());
+
+        fn read_all<R: Read>(r: Option<R>) -> io::Result<Vec<u8>> {
+            let mut buf = Vec::new();
+            if let Some(mut r) = r {
+                r.read_to_end(&mut buf)?;
+            }
+            Ok(buf)
+        }
+
+        // read the stderr in another coroutine to avoid dead lock
+        let stderr = self.stderr.take();
+        let h = unsafe { spawn(move || read_all(stderr)) };
+        let stdout = read_all(self.stdout.take());
+        let stderr = h.join().unwrap_or_else(|e| panic::resume_unwind(e));
+
+        let status = self.wait()?;
+        Ok(Output {
+            status,
+            stdout: stdout?,
+            stderr: stderr?,
+        })
+    }
+}
+
+impl fmt::Debug for Child {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Child")
+            .field("pid", &self.id())
+            .field("stdin", &self.stdin)
+            .field("stdout", &self.stdout)
+            .field("stderr", &self.stderr)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipe_stdio() {
+        let h = go!(|| {
+            let mut child = Command::new("cat")
+                .stdin(Stdio::piped())
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+            let mut stdin = child.stdin.take().unwrap();
+            stdin.write_all(b"hello may").unwrap();
+            drop(stdin);
+            child.wait_with_output().unwrap()
+        });
+        let output = h.join().unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"hello may");
+        assert!(output.stderr.is_empty());
+    }
+
+    #[test]
+    fn output_and_status() {
+        let h = go!(|| {
+            let output = Command::new("sh")
+                .args(["-c", "echo out; echo err >&2; exit 3"])
+                .output()
+                .unwrap();
+            assert_eq!(output.status.code(), Some(3));
+            assert_eq!(output.stdout, b"out\n");
+            assert_eq!(output.stderr, b"err\n");
+
+            Command::new("true").status().unwrap()
+        });
+        assert!(h.join().unwrap().success());
+
+        // thread context
+        let status = Command::new("sh").args(["-c", "exit 5"]).status().unwrap();
+        assert_eq!(status.code(), Some(5));
+    }
+
+    #[test]
+    fn wait_not_block_worker() {
+        use std::time::{Duration, Instant};
+
+        let start = Instant::now();
+        let handles: Vec<_> = (0..50)
+            .map(|_| go!(|| Command::new("sleep").arg("0.2").status().unwrap()))
+            .collect();
+        for h in handles {
+            assert!(h.join().unwrap().success());
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
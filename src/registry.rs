@@ -0,0 +1,95 @@
+//! the live coroutines of a scheduler
+//!
+//! each spawned coroutine is registered until it's done, so that the shutdown
+//! can cancel the remaining ones and wait for them to exit. the set is sharded
+//! by the coroutine id so that spawning from multiple threads doesn't contend
+//! on a single lock
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use crate::coroutine_impl::Coroutine;
+use parking_lot::{Condvar, Mutex};
+
+const SHARDS: usize = 64;
+
+pub struct Registry {
+    shards: Vec<Mutex<HashMap<u64, Coroutine>>>,
+    // number of the live coroutines
+    count: AtomicUsize,
+    // number of the threads waiting for the count to drop to zero
+    waiters: AtomicUsize,
+    idle_lock: Mutex<()>,
+    idle: Condvar,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry {
+            shards: (0..SHARDS).map(|_| Mutex::new(HashMap::new())).collect(),
+            count: AtomicUsize::new(0),
+            waiters: AtomicUsize::new(0),
+            idle_lock: Mutex::new(()),
+            idle: Condvar::new(),
+        }
+    }
+
+    #[inline]
+    fn shard(&self, id: u64) -> &Mutex<HashMap<u64, Coroutine>> {
+        &self.shards[id as usize % SHARDS]
+    }
+
+    /// register a new coroutine
+    pub fn insert(&self, co: &Coroutine) {
+        // pairs with the shutdown flag, see `Builder::spawn_impl`
+        self.count.fetch_add(1, Ordering::SeqCst);
+        self.shard(co.raw_id())
+            .lock()
+            .insert(co.raw_id(), co.clone());
+    }
+
+    /// unregister a done coroutine, wake up the idle waiters if it's the last
+    pub fn remove(&self, id: u64) {
+        self.shard(id).lock().remove(&id);
+        if self.count.fetch_sub(1, Ordering::SeqCst) == 1
+            && self.waiters.load(Ordering::SeqCst) != 0
+        {
+            // the waiter checks the count with the lock held
+            let _guard = self.idle_lock.lock();
+            self.idle.notify_all();
+        }
+    }
+
+    /// return the number of live coroutines
+    pub fn len(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    /// wait until all the coroutines are done, return false if timeout
+    pub fn wait_idle(&self, deadline: Option<Instant>) -> bool {
+        self.waiters.fetch_add(1, Ordering::SeqCst);
+        let mut guard = self.idle_lock.lock();
+        while self.len() != 0 {
+            match deadline {
+                Some(d) => {
+                    if self.idle.wait_until(&mut guard, d).timed_out() {
+                        break;
+                    }
+                }
+                None => self.idle.wait(&mut guard),
+            }
+        }
+        drop(guard);
+        self.waiters.fetch_sub(1, Ordering::SeqCst);
+        self.len() == 0
+    }
+
+    /// return the handles of the live coroutines
+    pub fn snapshot(&self) -> Vec<Coroutine> {
+        let mut v = Vec::with_capacity(self.len());
+        for shard in self.shards.iter() {
+            v.extend(shard.lock().values().cloned());
+        }
+        v
+    }
+}
@@ -0,0 +1,510 @@
+//! independent runtimes
+//!
+//! the free functions like `coroutine::spawn` and the `go!` macro run on the
+//! default runtime which is configured by `config()`. a `Runtime` has its own
+//! worker threads, timer thread, selector and coroutine pool, so that a latency
+//! critical pool of coroutines can be isolated from a batch pool.
+//!
+//! a coroutine always runs on the runtime that it's spawned on, the coroutines
+//! it spawns and the io objects it creates belong to the same runtime. in a
+//! thread the runtime can be selected by [`Runtime::enter`]. an io object is
+//! pinned to the runtime that it's created on, its events are always polled by
+//! that runtime even if it's used by coroutines of other runtimes. the io
+//! object keeps the selector of the runtime alive after it's dropped, but
+//! it can't wait for events anymore.
+//!
+//! a current thread runtime has no worker threads, its coroutines only run
+//! when a thread drives it by [`Runtime::block_on`], see also [`run`].
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::panic;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::config;
+use crate::coroutine_impl::{is_coroutine, spawn};
+use crate::join::JoinHandle;
+use crate::metrics::Metrics;
+use crate::scheduler::{
+    drop_scheduler, free_scheduler, set_current, start_scheduler, stop_scheduler, with_scheduler,
+    Scheduler,
+};
+
+/// Runtime factory, which can be used to configure the properties of a new
+/// runtime
+///
+/// the unset properties take the value of the global `config()` when the
+/// runtime is built
+///
+/// # Examples
+///
+/// ```rust
+/// use may::RuntimeBuilder;
+///
+/// let rt = RuntimeBuilder::new()
+///     .workers(2)
+///     .stack_size(0x2000)
+///     .pool_capacity(100)
+///     .worker_pin(false)
+///     .build()
+///     .unwrap();
+/// assert_eq!(rt.block_on(|| 1 + 1), 2);
+/// ```
+#[derive(Debug, Default)]
+pub struct RuntimeBuilder {
+    workers: Option<usize>,
+    stack_size: Option<usize>,
+    pool_capacity: Option<usize>,
+    worker_pin: Option<bool>,
+    task_dump: Option<bool>,
+    current_thread: bool,
+}
+
+impl RuntimeBuilder {
+    /// create a builder with the default configuration
+    pub fn new() -> Self {
+        RuntimeBuilder::default()
+    }
+
+    /// set the worker thread number, 0 means the number of cpus
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = Some(workers);
+        self
+    }
+
+    /// set the default stack size of the coroutines, in usize
+    pub fn stack_size(mut self, size: usize) -> Self {
+        self.stack_size = Some(size);
+        self
+    }
+
+    /// set the cached coroutine pool number
+    pub fn pool_capacity(mut self, capacity: usize) -> Self {
+        self.pool_capacity = Some(capacity);
+        self
+    }
+
+    /// set if the worker threads are pinned to the cpu cores
+    pub fn worker_pin(mut self, pin: bool) -> Self {
+        self.worker_pin = Some(pin);
+        self
+    }
+
+    /// set if the coroutines are tracked for `coroutine::dump`
+    ///
+    /// if it's not set the coroutines follow the global config when spawned,
+    /// see [`Config::set_task_dump`]
+    ///
+    /// [`Config::set_task_dump`]: struct.Config.html#method.set_task_dump
+    pub fn task_dump(mut self, enable: bool) -> Self {
+        self.task_dump = Some(enable);
+        self
+    }
+
+    /// create a current thread runtime
+    ///
+    /// it has no worker threads, the coroutines are run by the thread that
+    /// calls [`Runtime::block_on`]. the worker settings are ignored
+    ///
+    /// [`Runtime::block_on`]: struct.Runtime.html#method.block_on
+    pub fn current_thread(mut self) -> Self {
+        self.current_thread = true;
+        self
+    }
+
+    /// create the runtime and start its threads
+    pub fn build(self) -> io::Result<Runtime> {
+        let workers = match self.workers {
+            None => config().get_workers(),
+            Some(0) => num_cpus::get(),
+            Some(n) => n,
+        };
+        let stack_size = self.stack_size.unwrap_or_else(|| config().get_stack_size());
+        let pool_capacity = self
+            .pool_capacity
+            .unwrap_or_else(|| config().get_pool_capacity());
+        let pin = self.worker_pin.unwrap_or_else(|| config().get_worker_pin());
+
+        let sched = start_scheduler(
+            workers,
+            Some(stack_size),
+            Some(pool_capacity),
+            pin,
+            self.current_thread,
+            self.task_dump,
+        )?;
+        Ok(Runtime {
+            sched,
+            driving: AtomicBool::new(false),
+        })
+    }
+}
+
+/// An independent coroutine runtime
+///
+/// dropping the runtime cancels its live coroutines and waits for them to
+/// exit, use [`shutdown`] to give them a chance to finish. a coroutine blocked
+/// in io can only be canceled with the `io_cancel` feature, else the drop
+/// waits until the io is ready. if it's dropped in a coroutine the work is
+/// done by a new thread.
+///
+/// [`shutdown`]: #method.shutdown
+pub struct Runtime {
+    sched: *mut Scheduler,
+    // a thread is running `block_on` of the current thread runtime
+    driving: AtomicBool,
+}
+
+unsafe impl Send for Runtime {}
+unsafe impl Sync for Runtime {}
+
+impl Runtime {
+    /// create a runtime with the default configuration
+    pub fn new() -> io::Result<Runtime> {
+        RuntimeBuilder::new().build()
+    }
+
+    /// Spawns a new coroutine on the runtime, returning a [`JoinHandle`] for it.
+    ///
+    /// # Safety
+    ///
+    /// see [`coroutine::spawn`]
+    ///
+    /// [`JoinHandle`]: coroutine/struct.JoinHandle.html
+    /// [`coroutine::spawn`]: coroutine/fn.spawn.html
+    pub unsafe fn spawn<F, T>(&self, f: F) -> JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let _guard = self.enter();
+        spawn(f)
+    }
+
+    /// run the closure in a coroutine of the runtime and wait for the result
+    ///
+    /// the panic of the closure is propagated to the caller. for a current
+    /// thread runtime the calling thread drives the event loop until the
+    /// closure returns, the other coroutines of the runtime are paused after
+    /// that. it panics if the current thread runtime is called in a coroutine
+    /// or is already driven by another thread.
+    pub fn block_on<F, T>(&self, f: F) -> T
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let sched = unsafe { &*self.sched };
+        let h = if sched.is_current_thread() {
+            assert!(
+                !is_coroutine(),
+                "can't block_on a current thread runtime in a coroutine"
+            );
+            assert!(
+                !self.driving.swap(true, Ordering::Acquire),
+                "the current thread runtime is already driven by another thread"
+            );
+            let _driving = DrivingGuard(&self.driving);
+
+            let done = Arc::new(AtomicBool::new(false));
+            let root_done = done.clone();
+            let h = unsafe {
+                self.spawn(move || {
+                    let _done = DoneGuard(root_done);
+                    f()
+                })
+            };
+            sched.run_until(None, || done.load(Ordering::Acquire));
+            h
+        } else {
+            unsafe { self.spawn(f) }
+        };
+
+        match h.join() {
+            Ok(ret) => ret,
+            Err(panic) => panic::resume_unwind(panic),
+        }
+    }
+
+    /// enter the runtime in the current thread
+    ///
+    /// until the guard is dropped the coroutines spawned by the free functions
+    /// and the io objects created in the thread belong to this runtime. in a
+    /// coroutine the guard only takes effect till the next blocking call.
+    pub fn enter(&self) -> EnterGuard<'_> {
+        EnterGuard {
+            prev: set_current(self.sched),
+            _rt: PhantomData,
+        }
+    }
+
+    /// get the metrics of the runtime
+    pub fn metrics(&self) -> Metrics {
+        Metrics::collect(unsafe { &*self.sched })
+    }
+
+    /// Shuts down the runtime
+    ///
+    /// the coroutines are given `timeout` to finish before they are canceled,
+    /// see [`may::shutdown`] for the details. if it fails the runtime keeps
+    /// running in background.
+    ///
+    /// [`may::shutdown`]: fn.shutdown.html
+    pub fn shutdown(mut self, timeout: Duration) -> io::Result<()> {
+        let sched = std::mem::replace(&mut self.sched, ptr::null_mut());
+        stop_scheduler(unsafe { &*sched }, timeout)?;
+        unsafe { free_scheduler(sched) };
+        Ok(())
+    }
+}
+
+// notify the `block_on` caller that the root coroutine is done
+struct DoneGuard(Arc<AtomicBool>);
+
+impl Drop for DoneGuard {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Release);
+        // this runs in the root coroutine of the current thread runtime
+        with_scheduler(|s| s.get_selector().wakeup(0));
+    }
+}
+
+// release the current thread runtime for other `block_on` callers
+struct DrivingGuard<'a>(&'a AtomicBool);
+
+impl Drop for DrivingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// Runs the closure in a root coroutine on the current thread
+///
+/// a current thread runtime is created and driven by the calling thread
+/// until the closure returns, the coroutines spawned by the closure run on
+/// the same thread. the closure result is returned and its panic is
+/// propagated. the runtime is dropped before return, which cancels the
+/// coroutines left by the closure, see [`Runtime`].
+///
+/// it panics if called in a coroutine
+///
+/// # Examples
+///
+/// ```rust
+/// let ret = may::run(|| {
+///     let h = may::go!(|| 20);
+///     h.join().unwrap() + 1
+/// });
+/// assert_eq!(ret, 21);
+/// ```
+pub fn run<F, T>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let rt = RuntimeBuilder::new()
+        .current_thread()
+        .build()
+        .expect("failed to create runtime");
+    rt.block_on(f)
+}
+
+impl Drop for Runtime {
+    fn drop(&mut self) {
+        if self.sched.is_null() {
+            return;
+        }
+        unsafe { drop_scheduler(self.sched) };
+    }
+}
+
+impl fmt::Debug for Runtime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Runtime {{ .. }}")
+    }
+}
+
+/// A guard that restores the previous runtime of the thread when dropped
+///
+/// created by [`Runtime::enter`]
+///
+/// [`Runtime::enter`]: struct.Runtime.html#method.enter
+pub struct EnterGuard<'a> {
+    prev: *const Scheduler,
+    _rt: PhantomData<&'a Runtime>,
+}
+
+impl Drop for EnterGuard<'_> {
+    fn drop(&mut self) {
+        set_current(self.prev);
+    }
+}
+
+impl fmt::Debug for EnterGuard<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "EnterGuard {{ .. }}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net::{TcpListener, TcpStream};
+    use crate::sync::mpsc::channel;
+    use std::io::{Read, Write};
+    use std::thread;
+
+    fn runtime() -> Runtime {
+        RuntimeBuilder::new()
+            .workers(1)
+            .worker_pin(false)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn runtime_block_on() {
+        let rt = runtime();
+        let a = rt.block_on(|| thread::current().id());
+        let b = rt.block_on(|| thread::current().id());
+        // the only worker of the runtime
+        assert_eq!(a, b);
+        assert_ne!(a, thread::current().id());
+        // spawned in the coroutine belongs to the same runtime
+        let c = rt.block_on(|| go!(|| thread::current().id()).join().unwrap());
+        assert_eq!(a, c);
+
+        let ret = panic::catch_unwind(panic::AssertUnwindSafe(|| rt.block_on(|| panic!("oops"))));
+        assert!(ret.is_err());
+        rt.shutdown(Duration::from_secs(1)).unwrap();
+    }
+
+    #[test]
+    fn runtime_wakeup_from_other_runtime() {
+        let rt = runtime();
+        let id = rt.block_on(|| thread::current().id());
+        let (tx, rx) = channel();
+        let h = unsafe { rt.spawn(move || rx.recv().map(|_: ()| thread::current().id())) };
+        // wake it up from the default runtime
+        go!(move || tx.send(()).unwrap()).join().unwrap();
+        assert_eq!(h.join().unwrap().unwrap(), id);
+    }
+
+    #[test]
+    fn runtime_pinned_io() {
+        let rt = runtime();
+        let listener = {
+            let _guard = rt.enter();
+            TcpListener::bind("127.0.0.1:0").unwrap()
+        };
+        let addr = listener.local_addr().unwrap();
+        let h = go!(move || {
+            let (mut s, _) = listener.accept().unwrap();
+            let mut buf = [0; 5];
+            s.read_exact(&mut buf).unwrap();
+            buf
+        });
+        rt.block_on(move || {
+            let mut s = TcpStream::connect(addr).unwrap();
+            s.write_all(b"hello").unwrap();
+        });
+        assert_eq!(&h.join().unwrap(), b"hello");
+        drop(rt);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn io_outlives_runtime() {
+        use crate::io::SplitIo;
+
+        let rt = runtime();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let s = {
+            let _guard = rt.enter();
+            TcpStream::connect(addr).unwrap()
+        };
+        rt.shutdown(Duration::from_secs(1)).unwrap();
+        // the io keeps the selector of the released runtime alive
+        assert_eq!(s.peer_addr().unwrap(), addr);
+        assert!(s.split().is_err());
+    }
+
+    #[test]
+    fn run_on_current_thread() {
+        let id = thread::current().id();
+        let ret = run(move || {
+            assert_eq!(thread::current().id(), id);
+            let h = go!(move || {
+                crate::coroutine::sleep(Duration::from_millis(10));
+                thread::current().id()
+            });
+            h.join().unwrap()
+        });
+        assert_eq!(ret, id);
+
+        // io is driven by the current thread
+        let buf = run(|| {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            go!(move || {
+                let mut s = TcpStream::connect(addr).unwrap();
+                s.write_all(b"hello").unwrap();
+            });
+            let (mut s, _) = listener.accept().unwrap();
+            let mut buf = [0; 5];
+            s.read_exact(&mut buf).unwrap();
+            buf
+        });
+        assert_eq!(&buf, b"hello");
+
+        let ret = panic::catch_unwind(|| run(|| panic!("oops")));
+        assert!(ret.is_err());
+
+        // the coroutines left by the closure are canceled
+        let h = run(|| go!(|| crate::coroutine::sleep(Duration::from_secs(1000))));
+        assert!(h.is_done());
+        assert!(h.join().is_err());
+    }
+
+    #[test]
+    fn drop_runtime() {
+        let rt = runtime();
+        let h = unsafe { rt.spawn(|| crate::coroutine::sleep(Duration::from_secs(1000))) };
+        drop(rt);
+        assert!(h.is_done());
+        assert!(h.join().is_err());
+
+        // dropped in a coroutine of the runtime itself
+        let rt = Arc::new(runtime());
+        let (tx, rx) = channel();
+        let rt1 = rt.clone();
+        unsafe {
+            rt.spawn(move || {
+                let _ = rx.recv();
+                drop(rt1);
+            })
+        };
+        let h = unsafe { rt.spawn(|| crate::coroutine::sleep(Duration::from_secs(1000))) };
+        drop(rt);
+        tx.send(()).unwrap();
+        assert!(h.join().is_err());
+    }
+
+    #[test]
+    fn current_thread_block_on() {
+        let rt = RuntimeBuilder::new().current_thread().build().unwrap();
+        let (tx, rx) = channel();
+        // paused until the runtime is driven again
+        let h = unsafe { rt.spawn(move || rx.recv().unwrap()) };
+        rt.block_on(move || tx.send(5).unwrap());
+        assert_eq!(rt.block_on(move || h.join().unwrap()), 5);
+
+        // the live coroutines are canceled
+        let h = unsafe { rt.spawn(|| crate::coroutine::sleep(Duration::from_secs(1000))) };
+        rt.shutdown(Duration::from_millis(10)).unwrap();
+        assert!(h.join().is_err());
+    }
+}
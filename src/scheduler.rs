@@ -2,20 +2,25 @@ use std::cell::Cell;
 #[cfg(feature = "work_steal")]
 use std::cell::UnsafeCell;
 use std::io;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, Once};
+use std::ptr;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crate::config::config;
-use crate::coroutine_impl::{run_coroutine, CoroutineImpl};
+use crate::coroutine_impl::{co_scheduler, run_coroutine, CoroutineImpl};
 use crate::io::{EventLoop, Selector};
 use crate::likely::likely;
+use crate::metrics::Stats;
+use crate::overflow;
 use crate::pool::CoroutinePool;
+use crate::registry::Registry;
 use crate::sync::AtomicOption;
 use crate::timeout_list;
 use crate::yield_now::set_co_para;
 use may_queue::mpsc::Queue;
+use parking_lot::{Mutex, RwLock};
 
 cfg_if::cfg_if! {
     if #[cfg(feature = "crossbeam_queue_steal")] {
@@ -29,61 +34,281 @@ cfg_if::cfg_if! {
 
 // thread id, only workers are normal ones
 thread_local! { pub static WORKER_ID: Cell<usize> = const { Cell::new(usize::MAX) }; }
+// the scheduler that the worker thread or the timer thread belongs to
+thread_local! { pub static WORKER_SCHED: Cell<*const Scheduler> = const { Cell::new(ptr::null()) }; }
+// the scheduler of the current context, see `with_scheduler`
+thread_local! { static CURRENT: Cell<*const Scheduler> = const { Cell::new(ptr::null()) }; }
 
 // here we use Arc<AtomicOption<>> for that in the select implementation
 // other event may try to consume the coroutine while timer thread consume it
 type TimerData = Arc<AtomicOption<CoroutineImpl>>;
 type TimerThread = timeout_list::TimerThread<TimerData>;
 
-static mut SCHED: *const Scheduler = std::ptr::null();
+// the default runtime used by the free functions
+static SCHED: AtomicPtr<Scheduler> = AtomicPtr::new(ptr::null_mut());
+// serialize the default scheduler initialization and shutdown
+static SCHED_LOCK: Mutex<()> = Mutex::new(());
+// addresses of all the schedulers that are not released yet
+static LIVE: RwLock<Vec<usize>> = RwLock::new(Vec::new());
 
-#[inline(never)]
-fn init_scheduler() {
-    let workers = config().get_workers();
-    let b: Box<Scheduler> = Scheduler::new(workers);
-    unsafe { SCHED = Box::into_raw(b) };
+// how long to wait for the canceled coroutines to exit
+const CANCEL_GRACE: Duration = Duration::from_secs(1);
+
+// the scheduler is not `Sync`, this is used to pass it to the threads
+struct SchedPtr(*const Scheduler);
+unsafe impl Send for SchedPtr {}
+
+/// create a scheduler and start its worker threads and timer thread
+///
+/// `stack_size`, `pool_capacity` and `task_dump` follow the global config if
+/// `None`.
+/// a `current_thread` scheduler has a single event loop and no worker
+/// threads, the event loop is driven by the thread that calls `block_on`
+pub(crate) fn start_scheduler(
+    workers: usize,
+    stack_size: Option<usize>,
+    pool_capacity: Option<usize>,
+    pin_cores: bool,
+    current_thread: bool,
+    task_dump: Option<bool>,
+) -> io::Result<*mut Scheduler> {
+    overflow::install();
+    let workers = if current_thread { 1 } else { workers };
+    let mut b: Box<Scheduler> = Scheduler::new(workers, stack_size, pool_capacity)?;
+    b.current_thread = current_thread;
+    b.task_dump = task_dump;
+    // the coroutines and the io objects hold a reference of the scheduler
+    let p = Arc::into_raw(Arc::<Scheduler>::from(b)) as *mut Scheduler;
+    LIVE.write().push(p as usize);
+    let s = unsafe { &*p };
 
+    let mut threads = s.threads.lock();
     // timer thread
-    thread::spawn(move || {
+    let sp = SchedPtr(p);
+    threads.push(thread::spawn(move || {
+        overflow::init_thread();
         // timer function
         let timer_event_handler = |c: Arc<AtomicOption<CoroutineImpl>>| {
             // just re-push the co to the visit list
             if let Some(mut co) = c.take() {
                 // set the timeout result for the coroutine
                 set_co_para(&mut co, io::Error::new(io::ErrorKind::TimedOut, "timeout"));
-                // s.schedule_global(c);
-                run_coroutine(co);
+                let s = co_scheduler(&co);
+                if s.is_current_thread() {
+                    // only the block_on caller can run the coroutine
+                    schedule(co);
+                } else {
+                    run_coroutine(co);
+                }
             }
         };
 
-        let s = unsafe { &*SCHED };
+        let sp = sp;
+        CURRENT.set(sp.0);
+        WORKER_SCHED.set(sp.0);
+        let s = unsafe { &*sp.0 };
         s.timer_thread.run(&timer_event_handler);
-    });
+    }));
 
     let core_ids = core_affinity::get_core_ids().unwrap();
-    let pin_cores = config().get_worker_pin();
+    let workers = if current_thread { 0 } else { workers };
     // io event loop thread
     for (id, core) in (0..workers).zip(core_ids.into_iter().cycle()) {
-        thread::spawn(move || {
+        let sp = SchedPtr(p);
+        threads.push(thread::spawn(move || {
             if pin_cores {
                 core_affinity::set_for_current(core);
             }
-            let s = unsafe { &*SCHED };
-            s.event_loop.run(id);
+            let sp = sp;
+            CURRENT.set(sp.0);
+            let s = unsafe { &*sp.0 };
+            s.event_loop.run(s, id);
+        }));
+    }
+    drop(threads);
+    Ok(p)
+}
+
+/// stop the scheduler after all its coroutines are done
+///
+/// the remaining coroutines are canceled after `timeout`, return a `TimedOut`
+/// error if some of them still don't exit and the scheduler keeps running. else the threads of the scheduler are joined, it's safe to
+/// release it after that
+pub(crate) fn stop_scheduler(s: &Scheduler, timeout: Duration) -> io::Result<()> {
+    if crate::coroutine_impl::is_coroutine() {
+        return Err(io::Error::other(
+            "can't shutdown the runtime in a coroutine",
+        ));
+    }
+
+    s.shutdown.store(true, Ordering::SeqCst);
+    if !s.wait_idle(Some(Instant::now() + timeout)) {
+        s.cancel_all();
+        if !s.wait_idle(Some(Instant::now() + CANCEL_GRACE)) {
+            s.shutdown.store(false, Ordering::SeqCst);
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} coroutines are still alive", s.registry.len()),
+            ));
+        }
+    }
+
+    s.stop();
+    Ok(())
+}
+
+/// cancel all the coroutines of the scheduler and release it after they exit
+///
+/// unlike `stop_scheduler` it never gives up, a coroutine blocked in io can
+/// only be canceled with the `io_cancel` feature, else it's waited until the
+/// io is ready. in a coroutine, which may belong to the scheduler, it's done
+/// by a new thread
+pub(crate) unsafe fn drop_scheduler(p: *mut Scheduler) {
+    if crate::coroutine_impl::is_coroutine() {
+        let sp = SchedPtr(p);
+        thread::spawn(move || {
+            let sp = sp;
+            drop_scheduler(sp.0 as *mut Scheduler)
         });
+        return;
     }
+
+    let s = &*p;
+    s.shutdown.store(true, Ordering::SeqCst);
+    s.cancel_all();
+    s.wait_idle(None);
+    s.stop();
+    free_scheduler(p);
 }
 
+/// release a stopped scheduler
+///
+/// the io objects created on it may still hold a reference, it's dropped
+/// with the last one of them
+pub(crate) unsafe fn free_scheduler(p: *mut Scheduler) {
+    LIVE.write().retain(|&s| s != p as usize);
+    drop(Arc::from_raw(p));
+}
+
+/// run `f` with each scheduler that's not released yet
+pub(crate) fn for_each_live_scheduler<F: FnMut(&Scheduler)>(mut f: F) {
+    let live = LIVE.read();
+    for &p in live.iter() {
+        f(unsafe { &*(p as *const Scheduler) })
+    }
+}
+
+#[inline(never)]
+fn init_scheduler() -> &'static Scheduler {
+    let _guard = SCHED_LOCK.lock();
+    let p = SCHED.load(Ordering::Acquire);
+    if !p.is_null() {
+        return unsafe { &*p };
+    }
+
+    let workers = config().get_workers();
+    let pin_cores = config().get_worker_pin();
+    // the default runtime follows the global config
+    let p = start_scheduler(workers, None, None, pin_cores, false, None)
+        .expect("can't create scheduler");
+    SCHED.store(p, Ordering::Release);
+    unsafe { &*p }
+}
+
+/// run `f` with the scheduler of the current context
+///
+/// in a coroutine it's the one that the coroutine belongs to, which is kept
+/// alive by the coroutine. in a thread it's the entered runtime if any, else
+/// the default one, which is only released by `shutdown`
 #[inline]
-pub fn get_scheduler() -> &'static Scheduler {
-    unsafe {
-        if likely(!SCHED.is_null()) {
-            return &*SCHED;
-        }
+pub(crate) fn with_scheduler<R, F: FnOnce(&Scheduler) -> R>(f: F) -> R {
+    let cur = CURRENT.get();
+    if !cur.is_null() {
+        return f(unsafe { &*cur });
     }
-    static ONCE: Once = Once::new();
-    ONCE.call_once(init_scheduler);
-    unsafe { &*SCHED }
+    let p = SCHED.load(Ordering::Acquire);
+    if likely(!p.is_null()) {
+        return f(unsafe { &*p });
+    }
+    f(init_scheduler())
+}
+
+/// get a counted reference of the scheduler of the current context
+#[inline]
+pub(crate) fn current_scheduler() -> Arc<Scheduler> {
+    with_scheduler(Scheduler::to_arc)
+}
+
+/// put the coroutine to correct queue so that next time it can be scheduled
+///
+/// the coroutine is always scheduled by the runtime that it belongs to
+#[inline]
+pub(crate) fn schedule(co: CoroutineImpl) {
+    let s = co_scheduler(&co) as *const Scheduler;
+    let id = WORKER_ID.get();
+
+    if id != usize::MAX && ptr::eq(WORKER_SCHED.get(), s) {
+        // the worker thread is joined before the scheduler is released
+        unsafe { &*s }.schedule_local(co, id);
+    } else {
+        // the coroutine keeps the scheduler alive until it's pushed
+        unsafe { &*s }.schedule_global(co);
+    }
+}
+
+/// run `f` with the default scheduler if it's started
+///
+/// the scheduler can't be shutdown while `f` is running
+pub(crate) fn with_default_scheduler<R, F: FnOnce(&Scheduler) -> R>(f: F) -> Option<R> {
+    let _guard = SCHED_LOCK.lock();
+    unsafe { SCHED.load(Ordering::Acquire).as_ref() }.map(f)
+}
+
+/// set the scheduler of the current context, return the previous one
+#[inline]
+pub(crate) fn set_current(s: *const Scheduler) -> *const Scheduler {
+    CURRENT.replace(s)
+}
+
+/// Shuts down the default runtime
+///
+/// new spawns are rejected with an error once this is called. the live
+/// coroutines are given `timeout` to finish, after that they are canceled.
+/// when all of them are done the worker threads and the timer thread are joined
+/// and all the resources of the runtime are released, including the selector
+/// descriptors and the coroutine pool. the runtime would be started again by
+/// the next spawn.
+///
+/// if some coroutines still don't exit a `TimedOut` error is returned and the
+/// runtime keeps running.
+///
+/// this function must be called from a thread rather than a coroutine.
+///
+/// # Safety
+///
+/// the default runtime is shared by the whole process, no other threads
+/// should use it during the shutdown, e.g. spawn a coroutine or create an io
+/// object.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// may::go!(|| println!("hello")).join().unwrap();
+/// unsafe { may::shutdown(Duration::from_secs(1)).unwrap() };
+/// ```
+pub unsafe fn shutdown(timeout: Duration) -> io::Result<()> {
+    let _guard = SCHED_LOCK.lock();
+    let p = SCHED.load(Ordering::Acquire);
+    if p.is_null() {
+        return Ok(());
+    }
+
+    stop_scheduler(&*p, timeout)?;
+    SCHED.store(ptr::null_mut(), Ordering::Release);
+    free_scheduler(p);
+    Ok(())
 }
 
 #[repr(align(128))]
@@ -99,10 +324,27 @@ pub struct Scheduler {
     timer_thread: TimerThread,
     pub pool: CoroutinePool,
     pub workers: usize,
+    // the live coroutines
+    pub registry: Registry,
+    pub stats: Stats,
+    // reject new spawns
+    shutdown: AtomicBool,
+    // let the worker threads exit
+    stopped: AtomicBool,
+    // the event loop is driven by the `block_on` caller
+    current_thread: bool,
+    // track the coroutines for the task dump, follow the config if `None`
+    task_dump: Option<bool>,
+    threads: Mutex<Vec<thread::JoinHandle<()>>>,
 }
 
 impl Scheduler {
-    pub fn new(workers: usize) -> Box<Self> {
+    // it's always wrapped in an `Arc` by `start_scheduler`
+    fn new(
+        workers: usize,
+        stack_size: Option<usize>,
+        pool_capacity: Option<usize>,
+    ) -> io::Result<Box<Self>> {
         #[cfg(not(feature = "work_steal"))]
         let local_queues = Vec::from_iter((0..workers).map(|_| Local::new()));
 
@@ -115,16 +357,110 @@ impl Scheduler {
 
         let global_queues = Vec::from_iter((0..workers).map(|_| Queue::new()));
 
-        Box::new(Scheduler {
-            pool: CoroutinePool::new(),
-            event_loop: EventLoop::new(workers).expect("can't create event_loop"),
+        Ok(Box::new(Scheduler {
+            pool: CoroutinePool::new(stack_size, pool_capacity),
+            event_loop: EventLoop::new(workers)?,
             local_queues,
             #[cfg(feature = "work_steal")]
             stealers,
             global_queues,
             timer_thread: TimerThread::new(),
             workers,
-        })
+            registry: Registry::new(),
+            stats: Stats::new(workers),
+            shutdown: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+            current_thread: false,
+            task_dump: None,
+            threads: Mutex::new(Vec::new()),
+        }))
+    }
+
+    /// get a counted reference of the scheduler
+    #[inline]
+    pub fn to_arc(&self) -> Arc<Scheduler> {
+        // all the schedulers are created by `start_scheduler`
+        unsafe {
+            Arc::increment_strong_count(self);
+            Arc::from_raw(self)
+        }
+    }
+
+    /// return true if the scheduler doesn't accept new coroutines
+    #[inline]
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::SeqCst)
+    }
+
+    /// return true if the worker threads should exit
+    #[inline]
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Acquire)
+    }
+
+    /// return the local and global run queue depth of the worker
+    pub fn queue_depth(&self, id: usize) -> (usize, usize) {
+        #[cfg(feature = "work_steal")]
+        let local = self.stealers[id].len();
+        #[cfg(not(feature = "work_steal"))]
+        let local = self.local_queues[id].len();
+        (local, self.global_queues[id].len())
+    }
+
+    /// return the number of pending timers
+    pub fn timer_list_size(&self) -> usize {
+        self.timer_thread.pending()
+    }
+
+    /// return true if the scheduler has no worker threads
+    #[inline]
+    pub fn is_current_thread(&self) -> bool {
+        self.current_thread
+    }
+
+    /// return true if the new coroutines are tracked for the task dump
+    #[inline]
+    pub fn is_task_dump(&self) -> bool {
+        self.task_dump.unwrap_or_else(|| config().get_task_dump())
+    }
+
+    /// run the event loop in the current thread until `stop` returns true
+    ///
+    /// `stop` is checked at least once the `deadline` is reached
+    pub fn run_until<F: Fn() -> bool>(&self, deadline: Option<Instant>, stop: F) {
+        self.event_loop.run_until(self, 0, deadline, stop);
+    }
+
+    // wait until all the coroutines are done, return false if timeout
+    fn wait_idle(&self, deadline: Option<Instant>) -> bool {
+        if self.current_thread {
+            // there is no worker to run the coroutines
+            self.run_until(deadline, || {
+                self.registry.len() == 0 || deadline.is_some_and(|d| Instant::now() >= d)
+            });
+            return self.registry.len() == 0;
+        }
+        self.registry.wait_idle(deadline)
+    }
+
+    // cancel all the live coroutines
+    fn cancel_all(&self) {
+        for co in self.registry.snapshot() {
+            unsafe { co.cancel() };
+        }
+    }
+
+    // let the worker threads and the timer thread exit and join them
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::Release);
+        for id in 0..self.workers {
+            self.get_selector().wakeup(id);
+        }
+        self.timer_thread.stop();
+        let threads = std::mem::take(&mut *self.threads.lock());
+        for t in threads {
+            t.join().ok();
+        }
     }
 
     #[inline]
@@ -170,7 +506,10 @@ impl Scheduler {
                     }
                 };
                 let stealer = self.stealers.get(target).unwrap();
+                let stats = self.stats.worker(id);
+                Stats::inc(&stats.steal_attempts);
                 if let Some(co) = stealer.steal_into(local) {
+                    Stats::inc(&stats.steal_successes);
                     run_coroutine(co);
                     continue 'work;
                 }
@@ -179,29 +518,43 @@ impl Scheduler {
         }
     }
 
-    /// put the coroutine to correct queue so that next time it can be scheduled
+    /// called by selector with known id
     #[inline]
-    pub fn schedule(&self, co: CoroutineImpl) {
-        let id = WORKER_ID.get();
+    pub fn schedule_with_id(&self, co: CoroutineImpl, id: usize) {
+        // the io may be registered in another runtime
+        let s = co_scheduler(&co) as *const Scheduler;
+        if likely(ptr::eq(s, self)) {
+            self.schedule_local(co, id);
+        } else {
+            // the coroutine keeps the scheduler alive until it's pushed
+            unsafe { &*s }.schedule_global(co);
+        }
+    }
 
-        if id != usize::MAX {
-            self.schedule_with_id(co, id);
+    /// run the coroutine in place, called by selector without work steal
+    #[inline]
+    #[cfg(not(feature = "work_steal"))]
+    pub fn run_local(&self, co: CoroutineImpl) {
+        // the io may be registered in another runtime
+        let s = co_scheduler(&co) as *const Scheduler;
+        if likely(ptr::eq(s, self)) {
+            run_coroutine(co);
         } else {
-            self.schedule_global(co);
+            // the coroutine keeps the scheduler alive until it's pushed
+            unsafe { &*s }.schedule_global(co);
         }
     }
 
-    /// called by selector with known id
     #[inline]
     #[cfg(feature = "work_steal")]
-    pub fn schedule_with_id(&self, co: CoroutineImpl, id: usize) {
+    fn schedule_local(&self, co: CoroutineImpl, id: usize) {
         let local = unsafe { &mut *self.local_queues.get_unchecked(id).get() };
         local.push_back(co);
     }
 
     #[inline]
     #[cfg(not(feature = "work_steal"))]
-    pub fn schedule_with_id(&self, co: CoroutineImpl, id: usize) {
+    fn schedule_local(&self, co: CoroutineImpl, id: usize) {
         let local = unsafe { self.local_queues.get_unchecked(id) };
         local.push(co);
     }
@@ -210,20 +563,18 @@ impl Scheduler {
     #[inline]
     pub fn schedule_global(&self, co: CoroutineImpl) {
         static NEXT_THREAD_ID: AtomicUsize = AtomicUsize::new(0);
-        let thread_id = NEXT_THREAD_ID
-            .fetch_add(1, Ordering::Relaxed)
-            .rem_euclid(self.workers);
-        let global = unsafe { self.global_queues.get_unchecked(thread_id) };
-        global.push(co);
-        // signal one waiting thread if any
-        self.get_selector().wakeup(thread_id);
+        let id = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+        self.schedule_global_with_id(co, id);
     }
 
     /// put the coroutine to global queue so that next time it can be scheduled
     #[inline]
     pub fn schedule_global_with_id(&self, co: CoroutineImpl, id: usize) {
+        // the coroutine may be done by a worker as soon as it's pushed, then
+        // the scheduler could be released by the shutdown. its own threads
+        // are joined before that, the other threads keep it alive meanwhile
+        let _sched = (!ptr::eq(WORKER_SCHED.get(), self)).then(|| self.to_arc());
         let thread_id = id.rem_euclid(self.workers);
-        // println!("Scheduling to {thread_id}");
         let global = unsafe { self.global_queues.get_unchecked(thread_id) };
         global.push(co);
         // signal one waiting thread if any
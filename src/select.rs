@@ -0,0 +1,617 @@
+//! native select implementation
+//!
+//! the native arms of `select!` are polled in place without spawning any
+//! coroutine. if no arm is ready, the current thread or coroutine registers
+//! one blocker to all the arms and parks, any arm that may become ready
+//! would unpark it and then all the arms are polled again.
+//!
+//! supported arms are
+//! * `recv(rx) -> res => body`, `rx` implements [`SelectRecv`]
+//! * `send(tx, msg) -> res => body`, `tx` implements [`SelectSend`]
+//! * `wait(handle) => body`, `handle` implements [`SelectWait`]
+//! * `io(obj) -> events => body`, `obj` implements [`AsIoData`], fired when
+//!   the io has pending events, the same as [`WaitIo::wait_io`] (unix only)
+//! * `timeout(dur) => body`, fired if no other arm is ready in time
+//! * `default => body`, fired if no other arm is ready right now
+//!
+//! by default the arms are polled from a random start point so that all
+//! the arms get a fair chance, prefix the arms with `biased;` to always
+//! poll them in the declared order
+//!
+//! [`SelectRecv`]: trait.SelectRecv.html
+//! [`SelectSend`]: trait.SelectSend.html
+//! [`SelectWait`]: trait.SelectWait.html
+//! [`AsIoData`]: ../io/trait.AsIoData.html
+//! [`WaitIo::wait_io`]: ../io/trait.WaitIo.html#tymethod.wait_io
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{fence, AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvError, SendError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use crate::io::{AsIoData, IoData};
+use crate::sync::Blocker;
+
+/// register a select blocker to a primitive
+pub trait Watch {
+    /// register the blocker that would be unparked once the primitive
+    /// may become ready, the blocker could be unparked spuriously
+    fn watch(&self, blocker: &Arc<Blocker>);
+
+    /// remove the registered blocker
+    fn unwatch(&self, blocker: &Arc<Blocker>);
+}
+
+/// a receiver that can be used in the `recv` arm of `select!`
+pub trait SelectRecv: Watch {
+    /// the received data type
+    type Item;
+
+    /// try to receive the data without blocking
+    ///
+    /// return None if it would block, `Some(Err(RecvError))` if the
+    /// channel is disconnected
+    fn try_select_recv(&self) -> Option<Result<Self::Item, RecvError>>;
+}
+
+/// a sender that can be used in the `send` arm of `select!`
+pub trait SelectSend: Watch {
+    /// the sent data type
+    type Item;
+
+    /// try to send the data without blocking
+    ///
+    /// return the data back by `Err` if it would block
+    fn try_select_send(
+        &self,
+        t: Self::Item,
+    ) -> Result<Result<(), SendError<Self::Item>>, Self::Item>;
+}
+
+/// a waitable object that can be used in the `wait` arm of `select!`
+pub trait SelectWait: Watch {
+    /// return true if the wait would not block
+    ///
+    /// it's only called when the arm is polled, a ready object could
+    /// consume the notification that makes it ready, like the permit of
+    /// a `Notify`, since the arm is selected right after
+    fn is_ready(&self) -> bool;
+}
+
+/// the select blockers registered to a primitive
+///
+/// any number of selects could watch the same primitive at the same time,
+/// all of them are unparked by `wakeup`
+#[doc(hidden)]
+#[derive(Default)]
+pub struct Watchers {
+    list: parking_lot::Mutex<Vec<Arc<Blocker>>>,
+    // the number of the registered watchers
+    cnt: AtomicUsize,
+}
+
+impl Watchers {
+    pub const fn new() -> Self {
+        Watchers {
+            list: parking_lot::Mutex::new(Vec::new()),
+            cnt: AtomicUsize::new(0),
+        }
+    }
+
+    /// register the blocker, the watcher should re-check the primitive after
+    pub fn watch(&self, blocker: &Arc<Blocker>) {
+        let mut list = self.list.lock();
+        list.push(blocker.clone());
+        self.cnt.store(list.len(), Ordering::SeqCst);
+        drop(list);
+        // pairs with the fence in `wakeup`
+        fence(Ordering::SeqCst);
+    }
+
+    /// remove the registered blocker
+    pub fn unwatch(&self, blocker: &Arc<Blocker>) {
+        let mut list = self.list.lock();
+        list.retain(|w| !Arc::ptr_eq(w, blocker));
+        self.cnt.store(list.len(), Ordering::SeqCst);
+    }
+
+    /// unpark all the registered blockers, should be called after the
+    /// primitive state is updated
+    pub fn wakeup(&self) {
+        fence(Ordering::SeqCst);
+        if self.cnt.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let list = {
+            let mut list = self.list.lock();
+            self.cnt.store(0, Ordering::SeqCst);
+            std::mem::take(&mut *list)
+        };
+        for w in list {
+            w.unpark();
+        }
+    }
+}
+
+// /////////////////////////////////////////////////////////////////////////////
+// Arms
+// /////////////////////////////////////////////////////////////////////////////
+
+#[doc(hidden)]
+pub trait Arm {
+    // poll the arm without blocking, return true if it's ready
+    fn poll(&mut self) -> bool;
+    fn watch(&self, blocker: &Arc<Blocker>);
+    fn unwatch(&self, blocker: &Arc<Blocker>);
+}
+
+#[doc(hidden)]
+pub struct RecvArm<'a, R: SelectRecv> {
+    rx: &'a R,
+    ret: Option<Result<R::Item, RecvError>>,
+}
+
+impl<'a, R: SelectRecv> RecvArm<'a, R> {
+    pub fn new(rx: &'a R) -> Self {
+        RecvArm { rx, ret: None }
+    }
+
+    pub fn is_selected(&self) -> bool {
+        self.ret.is_some()
+    }
+
+    pub fn take(&mut self) -> Result<R::Item, RecvError> {
+        self.ret.take().expect("recv arm is not selected")
+    }
+}
+
+impl<'a, R: SelectRecv> Arm for RecvArm<'a, R> {
+    fn poll(&mut self) -> bool {
+        self.ret = self.rx.try_select_recv();
+        self.ret.is_some()
+    }
+
+    fn watch(&self, blocker: &Arc<Blocker>) {
+        self.rx.watch(blocker)
+    }
+
+    fn unwatch(&self, blocker: &Arc<Blocker>) {
+        self.rx.unwatch(blocker)
+    }
+}
+
+#[doc(hidden)]
+pub struct SendArm<'a, S: SelectSend> {
+    tx: &'a S,
+    msg: Option<S::Item>,
+    ret: Option<Result<(), SendError<S::Item>>>,
+}
+
+impl<'a, S: SelectSend> SendArm<'a, S> {
+    pub fn new(tx: &'a S, msg: S::Item) -> Self {
+        SendArm {
+            tx,
+            msg: Some(msg),
+            ret: None,
+        }
+    }
+
+    pub fn is_selected(&self) -> bool {
+        self.ret.is_some()
+    }
+
+    pub fn take(&mut self) -> Result<(), SendError<S::Item>> {
+        self.ret.take().expect("send arm is not selected")
+    }
+}
+
+impl<'a, S: SelectSend> Arm for SendArm<'a, S> {
+    fn poll(&mut self) -> bool {
+        let msg = self.msg.take().expect("send arm is already done");
+        match self.tx.try_select_send(msg) {
+            Ok(ret) => {
+                self.ret = Some(ret);
+                true
+            }
+            Err(msg) => {
+                self.msg = Some(msg);
+                false
+            }
+        }
+    }
+
+    fn watch(&self, blocker: &Arc<Blocker>) {
+        self.tx.watch(blocker)
+    }
+
+    fn unwatch(&self, blocker: &Arc<Blocker>) {
+        self.tx.unwatch(blocker)
+    }
+}
+
+#[doc(hidden)]
+pub struct WaitArm<'a, W: SelectWait> {
+    w: &'a W,
+    ready: bool,
+}
+
+impl<'a, W: SelectWait> WaitArm<'a, W> {
+    pub fn new(w: &'a W) -> Self {
+        WaitArm { w, ready: false }
+    }
+
+    pub fn is_selected(&self) -> bool {
+        self.ready
+    }
+}
+
+impl<'a, W: SelectWait> Arm for WaitArm<'a, W> {
+    fn poll(&mut self) -> bool {
+        self.ready = self.w.is_ready();
+        self.ready
+    }
+
+    fn watch(&self, blocker: &Arc<Blocker>) {
+        self.w.watch(blocker)
+    }
+
+    fn unwatch(&self, blocker: &Arc<Blocker>) {
+        self.w.unwatch(blocker)
+    }
+}
+
+#[doc(hidden)]
+#[cfg(unix)]
+pub struct IoArm<'a> {
+    io: &'a IoData,
+    ret: Option<usize>,
+}
+
+#[cfg(unix)]
+impl<'a> IoArm<'a> {
+    pub fn new<T: AsIoData + ?Sized>(io: &'a T) -> Self {
+        IoArm {
+            io: io.as_io_data(),
+            ret: None,
+        }
+    }
+
+    pub fn is_selected(&self) -> bool {
+        self.ret.is_some()
+    }
+
+    pub fn take(&mut self) -> usize {
+        self.ret.take().expect("io arm is not selected")
+    }
+}
+
+#[cfg(unix)]
+impl<'a> Arm for IoArm<'a> {
+    fn poll(&mut self) -> bool {
+        if self.io.io_flag.load(Ordering::Acquire) == 0 {
+            return false;
+        }
+        // consume the events like `WaitIo::wait_io`
+        self.ret = Some(self.io.reset());
+        true
+    }
+
+    fn watch(&self, blocker: &Arc<Blocker>) {
+        self.io.watchers.watch(blocker)
+    }
+
+    fn unwatch(&self, blocker: &Arc<Blocker>) {
+        self.io.watchers.unwatch(blocker)
+    }
+}
+
+// /////////////////////////////////////////////////////////////////////////////
+// select
+// /////////////////////////////////////////////////////////////////////////////
+
+// a cheap xorshift generator that is only used to pick the start arm
+fn rand_index(n: usize) -> usize {
+    thread_local! {
+        // the std hasher keys are randomly seeded per thread
+        static SEED: Cell<u64> = Cell::new(RandomState::new().hash_one(0u64) | 1);
+    }
+    SEED.with(|seed| {
+        let mut x = seed.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        seed.set(x);
+        (x % n as u64) as usize
+    })
+}
+
+// the arms are unwatched even if the park is canceled
+struct Watching<'a, 'b> {
+    arms: &'a mut [&'b mut dyn Arm],
+    blocker: Arc<Blocker>,
+}
+
+impl<'a, 'b> Watching<'a, 'b> {
+    fn new(arms: &'a mut [&'b mut dyn Arm]) -> Self {
+        let blocker = Blocker::current();
+        for arm in arms.iter() {
+            arm.watch(&blocker);
+        }
+        Watching { arms, blocker }
+    }
+}
+
+impl<'a, 'b> Drop for Watching<'a, 'b> {
+    fn drop(&mut self) {
+        for arm in self.arms.iter() {
+            arm.unwatch(&self.blocker);
+        }
+    }
+}
+
+fn poll_arms(arms: &mut [&mut dyn Arm], start: usize) -> Option<usize> {
+    let n = arms.len();
+    (0..n).map(|i| (start + i) % n).find(|&i| arms[i].poll())
+}
+
+/// select one ready arm, block the current thread or coroutine if needed
+///
+/// return the index of the selected arm, or None if no arm is ready
+/// within the timeout. a zero timeout means polling without blocking
+#[doc(hidden)]
+pub fn select_arms(
+    arms: &mut [&mut dyn Arm],
+    biased: bool,
+    timeout: Option<Duration>,
+) -> Option<usize> {
+    let n = arms.len();
+    let start = if biased || n == 0 { 0 } else { rand_index(n) };
+    if let Some(i) = poll_arms(arms, start) {
+        return Some(i);
+    }
+
+    let deadline = timeout.map(|d| Instant::now() + d);
+    loop {
+        let dur = match deadline {
+            None => None,
+            Some(d) => {
+                let now = Instant::now();
+                if now >= d {
+                    return None;
+                }
+                Some(d - now)
+            }
+        };
+
+        let watching = Watching::new(arms);
+        // re-check the arms after registered
+        if let Some(i) = poll_arms(watching.arms, start) {
+            return Some(i);
+        }
+        // the cancel would panic here and the guard would unwatch the arms
+        watching.blocker.park(dur).ok();
+        drop(watching);
+
+        if let Some(i) = poll_arms(arms, start) {
+            return Some(i);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sync::{mpmc, mpsc, oneshot};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn select_recv() {
+        let (tx1, rx1) = mpsc::channel::<i32>();
+        let (_tx2, rx2) = mpmc::channel::<i32>();
+        go!(move || {
+            crate::coroutine::sleep(Duration::from_millis(10));
+            tx1.send(42).unwrap();
+        });
+
+        let v = select! {
+            recv(rx2) -> _ => unreachable!(),
+            recv(rx1) -> v => v.unwrap(),
+        };
+        assert_eq!(v, 42);
+    }
+
+    #[test]
+    fn select_default() {
+        let (tx, rx) = mpsc::channel::<i32>();
+        let r = select! {
+            recv(rx) -> v => v.ok(),
+            default => None,
+        };
+        assert_eq!(r, None);
+
+        tx.send(1).unwrap();
+        let r = select! {
+            recv(rx) -> v => v.ok(),
+            default => None,
+        };
+        assert_eq!(r, Some(1));
+    }
+
+    #[test]
+    fn select_timeout() {
+        let (_tx, rx) = oneshot::channel::<i32>();
+        let h = go!(move || {
+            select! {
+                recv(rx) -> _ => false,
+                timeout(Duration::from_millis(10)) => true,
+            }
+        });
+        assert!(h.join().unwrap());
+    }
+
+    #[test]
+    fn select_disconnected() {
+        let (tx, rx) = mpmc::channel::<i32>();
+        let t = thread::spawn(move || {
+            select! {
+                recv(rx) -> v => v,
+            }
+        });
+        thread::sleep(Duration::from_millis(10));
+        drop(tx);
+        assert!(t.join().unwrap().is_err());
+    }
+
+    #[test]
+    fn select_send() {
+        let (tx, rx) = mpmc::sync_channel::<i32>(1);
+        tx.send(0).unwrap();
+        let h = go!(move || {
+            select! {
+                send(tx, 1) -> r => r.is_ok(),
+                timeout(Duration::from_secs(10)) => false,
+            }
+        });
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(rx.recv(), Ok(0));
+        assert!(h.join().unwrap());
+        assert_eq!(rx.recv(), Ok(1));
+    }
+
+    #[test]
+    fn select_rendezvous() {
+        let (tx, rx) = mpmc::sync_channel::<i32>(0);
+        let h = go!(move || {
+            let mut v = vec![];
+            loop_select! {
+                recv(rx) -> r => match r {
+                    Ok(i) => v.push(i),
+                    Err(_) => break,
+                },
+            }
+            v
+        });
+        for i in 0..10 {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+        assert_eq!(h.join().unwrap(), (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn select_wait() {
+        let h = go!(|| crate::coroutine::sleep(Duration::from_millis(10)));
+        let (_tx, rx) = mpsc::channel::<i32>();
+        let id = select! {
+            biased;
+            recv(rx) -> _ => 0,
+            wait(h) => 1,
+        };
+        assert_eq!(id, 1);
+        assert!(h.is_done());
+    }
+
+    #[test]
+    fn unwatch_other_blocker() {
+        let h = go!(|| crate::coroutine::sleep(Duration::from_millis(100)));
+        thread::scope(|s| {
+            // the first select times out after the second one is registered
+            s.spawn(|| {
+                select! {
+                    wait(h) => unreachable!(),
+                    timeout(Duration::from_millis(20)) => {},
+                }
+            });
+            thread::sleep(Duration::from_millis(10));
+            // it must not be cleared by the first one
+            let t = s.spawn(|| select! { wait(h) => h.is_done() });
+            assert!(t.join().unwrap());
+        });
+    }
+
+    #[test]
+    fn multi_select_wait() {
+        let h = go!(|| crate::coroutine::sleep(Duration::from_millis(20)));
+        thread::scope(|s| {
+            // both the selects are watching the same handle
+            let t1 = s.spawn(|| select! { wait(h) => h.is_done() });
+            let t2 = s.spawn(|| select! { wait(h) => h.is_done() });
+            assert!(t1.join().unwrap());
+            assert!(t2.join().unwrap());
+        });
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn select_io() {
+        use crate::net::{TcpListener, TcpStream};
+        use std::io::{Read, Write};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let h = go!(move || {
+            let mut s = listener.accept().unwrap().0;
+            // drain the initial events
+            let mut buf = [0; 4];
+            let _ = select! { io(s) -> _ => {}, default => {} };
+            let (_tx, rx) = mpsc::channel::<i32>();
+            select! {
+                recv(rx) -> _ => unreachable!(),
+                io(s) -> ev => assert_ne!(ev, 0),
+            }
+            s.read_exact(&mut buf).unwrap();
+            buf
+        });
+        let mut c = TcpStream::connect(addr).unwrap();
+        thread::sleep(Duration::from_millis(10));
+        c.write_all(b"ping").unwrap();
+        assert_eq!(&h.join().unwrap(), b"ping");
+    }
+
+    #[test]
+    fn select_fair() {
+        let (tx1, rx1) = mpsc::channel();
+        let (tx2, rx2) = mpsc::channel();
+        for _ in 0..100 {
+            tx1.send(()).unwrap();
+            tx2.send(()).unwrap();
+        }
+
+        let mut hits = [0; 2];
+        for _ in 0..100 {
+            select! {
+                recv(rx1) -> _ => hits[0] += 1,
+                recv(rx2) -> _ => hits[1] += 1,
+            }
+        }
+        assert!(hits[0] > 0 && hits[1] > 0);
+
+        // biased select always pick the first ready arm
+        for _ in 0..10 {
+            let id = select! {
+                biased;
+                recv(rx1) -> _ => 0,
+                recv(rx2) -> _ => 1,
+            };
+            assert_eq!(id, 0);
+        }
+    }
+
+    #[test]
+    fn select_canceled() {
+        let (tx, rx) = mpsc::channel::<i32>();
+        let h = go!(move || {
+            select! {
+                recv(rx) -> _ => {},
+            }
+        });
+        thread::sleep(Duration::from_millis(10));
+        unsafe { h.coroutine().cancel() };
+        assert!(h.join().is_err());
+        // the receiver is dropped without any watcher left
+        assert!(tx.send(1).is_err());
+    }
+}
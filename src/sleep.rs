@@ -4,8 +4,9 @@ use std::thread;
 use std::time::Duration;
 
 use crate::coroutine_impl::{co_cancel_data, is_coroutine, CoroutineImpl, EventSource};
+use crate::dump::{set_state, CoroutineState};
 use crate::likely::unlikely;
-use crate::scheduler::get_scheduler;
+use crate::scheduler::with_scheduler;
 use crate::yield_now::{get_co_para, yield_with};
 
 struct Sleep {
@@ -18,7 +19,7 @@ impl EventSource for Sleep {
         let cancel = co_cancel_data(&co);
         // put the coroutine into the timer list
         let sleep_co = Arc::new(AtomicOption::some(co));
-        get_scheduler().add_timer(self.dur, sleep_co.clone());
+        with_scheduler(|s| s.add_timer(self.dur, sleep_co.clone()));
 
         // register the cancel data
         cancel.set_co(sleep_co);
@@ -36,6 +37,7 @@ pub fn sleep(dur: Duration) {
     }
 
     let sleeper = Sleep { dur };
+    set_state(CoroutineState::ParkedOnTimer);
     yield_with(&sleeper);
     // consume the timeout error
     get_co_para();
@@ -0,0 +1,195 @@
+//! `tracing` integration
+//!
+//! the span context of `tracing` is thread local, but a coroutine may be
+//! resumed by different workers. so each coroutine keeps its span in the
+//! coroutine local storage and enters it each time it's resumed, the span is
+//! created as a child of the current span of the spawner.
+//!
+//! a span that's entered by the coroutine itself must not be held across a
+//! blocking call, use `Span::in_scope` or a child coroutine instead.
+use std::any::Any;
+
+use crate::coroutine_impl::Coroutine;
+use tracing::Span;
+
+/// create the span of a new coroutine in the context of the spawner
+///
+/// if the span is disabled the coroutine just runs in the parent span
+pub(crate) fn new_span(co: &Coroutine) -> Span {
+    let parent = Span::current();
+    let span = tracing::trace_span!(
+        parent: &parent,
+        "coroutine",
+        id = co.id().as_u64(),
+        name = co.name().unwrap_or("<unnamed>"),
+    );
+    if span.is_disabled() {
+        parent
+    } else {
+        span
+    }
+}
+
+#[inline]
+pub(crate) fn spawned(span: &Span) {
+    tracing::trace!(parent: span, "coroutine spawned");
+}
+
+#[inline]
+pub(crate) fn resumed() {
+    tracing::trace!("coroutine resumed");
+}
+
+#[inline]
+pub(crate) fn parked(state: &'static str) {
+    tracing::trace!(state, "coroutine parked");
+}
+
+#[inline]
+pub(crate) fn finished() {
+    tracing::trace!("coroutine finished");
+}
+
+#[inline]
+pub(crate) fn cancel(co: &Coroutine) {
+    tracing::trace!(id = co.id().as_u64(), "coroutine cancel");
+}
+
+/// the coroutine exits by a panic or cancel
+///
+/// there is no panic data for a canceled coroutine
+pub(crate) fn unwound(panic: Option<&(dyn Any + Send)>) {
+    match panic {
+        Some(p) if p.downcast_ref::<generator::Error>() != Some(&generator::Error::Cancel) => {
+            tracing::trace!("coroutine panicked")
+        }
+        _ => tracing::trace!("coroutine canceled"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::coroutine::{self, Builder};
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id};
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+    use tracing_subscriber::registry::LookupSpan;
+
+    // records the coroutine spans and the events with the span they belong to
+    #[derive(Clone, Default)]
+    struct Recorder {
+        // name, id and parent of the spans
+        spans: Arc<Mutex<Vec<(String, Id, Option<Id>)>>>,
+        events: Arc<Mutex<Vec<(Option<Id>, String)>>>,
+    }
+
+    #[derive(Default)]
+    struct Fields {
+        name: String,
+        message: String,
+    }
+
+    impl Visit for Fields {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            if field.name() == "name" {
+                self.name = value.to_owned();
+            }
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if field.name() == "message" {
+                self.message = format!("{value:?}");
+            }
+        }
+    }
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Recorder {
+        fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let mut fields = Fields::default();
+            attrs.record(&mut fields);
+            let parent = ctx.span(id).unwrap().parent().map(|s| s.id());
+            let span = (fields.name, id.clone(), parent);
+            self.spans.lock().unwrap().push(span);
+        }
+
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            let mut fields = Fields::default();
+            event.record(&mut fields);
+            let span = ctx.event_span(event).map(|s| s.id());
+            self.events.lock().unwrap().push((span, fields.message));
+        }
+    }
+
+    impl Recorder {
+        fn span(&self, name: &str) -> (Id, Option<Id>) {
+            let spans = self.spans.lock().unwrap();
+            let (_, id, parent) = spans.iter().find(|s| s.0 == name).unwrap();
+            (id.clone(), parent.clone())
+        }
+
+        fn events(&self, name: &str) -> Vec<String> {
+            let span = self.span(name).0;
+            let events = self.events.lock().unwrap();
+            events
+                .iter()
+                .filter(|(s, _)| s.as_ref() == Some(&span))
+                .map(|(_, m)| m.clone())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn coroutine_span() {
+        let recorder = Recorder::default();
+        let subscriber = tracing_subscriber::registry().with(recorder.clone());
+        tracing::subscriber::set_global_default(subscriber).unwrap();
+
+        let root = tracing::info_span!("root");
+        let h = root.in_scope(|| {
+            let builder = Builder::new().name("span-parent".to_owned());
+            go!(builder, || {
+                coroutine::sleep(Duration::from_millis(1));
+                let builder = Builder::new().name("span-child".to_owned());
+                go!(builder, coroutine::yield_now).unwrap().join().unwrap();
+            })
+            .unwrap()
+        });
+        h.join().unwrap();
+
+        let (parent, root_id) = recorder.span("span-parent");
+        assert_eq!(root_id, root.id());
+        assert_eq!(recorder.span("span-child").1, Some(parent));
+
+        let events = recorder.events("span-parent");
+        assert_eq!(events.first().unwrap(), "coroutine spawned");
+        assert!(events.contains(&"coroutine resumed".to_owned()));
+        assert!(events.contains(&"coroutine parked".to_owned()));
+        assert!(events.contains(&"coroutine finished".to_owned()));
+        // spawned, resumed, parked by yield, resumed, finished
+        assert_eq!(recorder.events("span-child").len(), 5);
+
+        let builder = Builder::new().name("span-cancel".to_owned());
+        let h = go!(builder, || coroutine::sleep(Duration::from_secs(1000))).unwrap();
+        while !recorder
+            .events("span-cancel")
+            .contains(&"coroutine parked".to_owned())
+        {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        unsafe { h.coroutine().cancel() };
+        assert!(h.join().is_err());
+        let events = recorder.events("span-cancel");
+        assert_eq!(events.last().unwrap(), "coroutine canceled");
+
+        let builder = Builder::new().name("span-panic".to_owned());
+        let h = go!(builder, || panic!("span panic")).unwrap();
+        assert!(h.join().is_err());
+        let events = recorder.events("span-panic");
+        assert_eq!(events.last().unwrap(), "coroutine panicked");
+    }
+}
@@ -0,0 +1,232 @@
+//! compatible with std::sync::barrier except for both thread and coroutine
+//! please ref the doc from std::sync::barrier
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use super::{Condvar, Mutex};
+
+/// Barrier primitive
+///
+/// A barrier enables multiple threads and coroutines to synchronize the
+/// beginning of some computation. the waiting coroutines are parked
+/// without blocking the worker thread.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use may::sync::Barrier;
+///
+/// let n = 10;
+/// let mut handles = Vec::with_capacity(n);
+/// let barrier = Arc::new(Barrier::new(n));
+/// for _ in 0..n {
+///     let c = barrier.clone();
+///     // The same messages will be printed together.
+///     // You will NOT see any interleaving.
+///     handles.push(may::go!(move || {
+///         println!("before wait");
+///         c.wait();
+///         println!("after wait");
+///     }));
+/// }
+/// // Wait for other coroutines to finish.
+/// for handle in handles {
+///     handle.join().unwrap();
+/// }
+/// ```
+pub struct Barrier {
+    lock: Mutex<BarrierState>,
+    cvar: Condvar,
+    num_waiters: usize,
+}
+
+// The inner state of a barrier
+struct BarrierState {
+    // how many waiters arrived in the current generation
+    count: usize,
+    // bumped each time the barrier is released
+    generation_id: usize,
+}
+
+/// A `BarrierWaitResult` is returned by `Barrier::wait` when all the
+/// participants in the barrier have rendezvoused.
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    /// Returns true if this waiter is the "leader"
+    ///
+    /// Only one waiter will have true returned from their result for each
+    /// generation, all other waiters will have false returned.
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl fmt::Debug for BarrierWaitResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("BarrierWaitResult")
+            .field("is_leader", &self.is_leader())
+            .finish()
+    }
+}
+
+impl Barrier {
+    /// Creates a new barrier that can block a given number of waiters.
+    ///
+    /// A barrier will block `n`-1 waiters which call `wait` and then wake up
+    /// all of them at once when the `n`th waiter calls `wait`.
+    pub fn new(n: usize) -> Barrier {
+        Barrier {
+            lock: Mutex::new(BarrierState {
+                count: 0,
+                generation_id: 0,
+            }),
+            cvar: Condvar::new(),
+            num_waiters: n,
+        }
+    }
+
+    fn wait_impl(&self, dur: Option<Duration>) -> Option<BarrierWaitResult> {
+        let deadline = dur.map(|d| Instant::now() + d);
+        let mut lock = self.lock.lock().unwrap();
+        let local_gen = lock.generation_id;
+        lock.count += 1;
+        if lock.count >= self.num_waiters {
+            // this is the leader, release all the others
+            lock.count = 0;
+            lock.generation_id = lock.generation_id.wrapping_add(1);
+            self.cvar.notify_all();
+            return Some(BarrierWaitResult(true));
+        }
+
+        // we need a while loop to guard against spurious wakeups
+        while local_gen == lock.generation_id {
+            match deadline {
+                None => lock = self.cvar.wait(lock).unwrap(),
+                Some(d) => {
+                    let now = Instant::now();
+                    if now >= d {
+                        // give up, the others would wait for a new comer
+                        lock.count -= 1;
+                        return None;
+                    }
+                    lock = self.cvar.wait_timeout(lock, d - now).unwrap().0;
+                }
+            }
+        }
+        Some(BarrierWaitResult(false))
+    }
+
+    /// Blocks the current thread or coroutine until all the participants
+    /// have rendezvoused here.
+    ///
+    /// Barriers are re-usable after all the participants have rendezvoused
+    /// once, and can be used continuously.
+    ///
+    /// A single (arbitrary) waiter will receive a `BarrierWaitResult` that
+    /// returns `true` from `is_leader` when returning from this function,
+    /// and all other waiters will receive a result that will return `false`
+    /// from `is_leader`.
+    pub fn wait(&self) -> BarrierWaitResult {
+        self.wait_impl(None).expect("barrier wait timeout")
+    }
+
+    /// same as `wait` except that with an extra timeout value
+    ///
+    /// return None if timeout happened, the waiter is removed from the
+    /// barrier so that the others would need a new participant to proceed
+    pub fn wait_timeout(&self, dur: Duration) -> Option<BarrierWaitResult> {
+        self.wait_impl(Some(dur))
+    }
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad("Barrier { .. }")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::mpsc::channel;
+    use std::sync::mpsc::TryRecvError;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_barrier() {
+        const N: usize = 10;
+
+        let barrier = Arc::new(Barrier::new(N));
+        let (tx, rx) = channel();
+
+        for _ in 0..N - 1 {
+            let c = barrier.clone();
+            let tx = tx.clone();
+            go!(move || {
+                tx.send(c.wait().is_leader()).unwrap();
+            });
+        }
+
+        // At this point, all spawned coroutines should be blocked,
+        // so we shouldn't get anything from the port
+        assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+
+        let mut leader_found = barrier.wait().is_leader();
+
+        // Now, the barrier is cleared and we should get data.
+        for _ in 0..N - 1 {
+            if rx.recv().unwrap() {
+                assert!(!leader_found);
+                leader_found = true;
+            }
+        }
+        assert!(leader_found);
+    }
+
+    #[test]
+    fn test_barrier_mixed() {
+        const N: usize = 6;
+
+        let barrier = Arc::new(Barrier::new(N));
+        let mut threads = vec![];
+        let mut coroutines = vec![];
+        for _ in 0..N / 2 {
+            let c = barrier.clone();
+            threads.push(thread::spawn(move || {
+                (0..10).filter(|_| c.wait().is_leader()).count()
+            }));
+            let c = barrier.clone();
+            coroutines.push(go!(move || {
+                (0..10).filter(|_| c.wait().is_leader()).count()
+            }));
+        }
+
+        let mut leaders = 0;
+        for t in threads {
+            leaders += t.join().unwrap();
+        }
+        for h in coroutines {
+            leaders += h.join().unwrap();
+        }
+        // one leader for each generation
+        assert_eq!(leaders, 10);
+    }
+
+    #[test]
+    fn test_barrier_timeout() {
+        let barrier = Arc::new(Barrier::new(2));
+        let c = barrier.clone();
+        let h = go!(move || c.wait_timeout(Duration::from_millis(10)).is_none());
+        assert!(h.join().unwrap());
+
+        // the timed out waiter is not counted
+        let c = barrier.clone();
+        let h = go!(move || c.wait().is_leader());
+        thread::sleep(Duration::from_millis(10));
+        let r = barrier.wait_timeout(Duration::from_secs(10)).unwrap();
+        assert!(r.is_leader() ^ h.join().unwrap());
+    }
+}
@@ -0,0 +1,452 @@
+//! broadcast channel implementation
+//!
+//! a multi-producer, multi-consumer channel where each sent value is seen
+//! by all the receivers. the values are stored in a bounded ring buffer,
+//! each receiver tracks its own cursor into the buffer. when a receiver
+//! falls too far behind, the oldest values are overwritten and the next
+//! receive returns `Lagged(n)` with the number of skipped values, then the
+//! receiver continues from the oldest value still in the buffer.
+//!
+//! new receivers are created by `Sender::subscribe`, they would only see
+//! the values sent after the subscription
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::sync::mpsc::SendError;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::Blocker;
+use parking_lot::Mutex;
+
+/// An error returned from the `recv` function on a broadcast `Receiver`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecvError {
+    /// all the senders are dropped and there is no more value to receive
+    Closed,
+
+    /// the receiver lagged too far behind, the number of skipped values is
+    /// returned and the next receive would get the oldest value in the buffer
+    Lagged(u64),
+}
+
+/// An error returned from the `try_recv` function on a broadcast `Receiver`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryRecvError {
+    /// there is no new value in the channel currently
+    Empty,
+
+    /// all the senders are dropped and there is no more value to receive
+    Closed,
+
+    /// the receiver lagged too far behind, the number of skipped values is
+    /// returned and the next receive would get the oldest value in the buffer
+    Lagged(u64),
+}
+
+/// An error returned from the `recv_timeout` function on a broadcast `Receiver`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecvTimeoutError {
+    /// there is no new value before the timeout
+    Timeout,
+
+    /// all the senders are dropped and there is no more value to receive
+    Closed,
+
+    /// the receiver lagged too far behind, the number of skipped values is
+    /// returned and the next receive would get the oldest value in the buffer
+    Lagged(u64),
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecvError::Closed => "channel closed".fmt(f),
+            RecvError::Lagged(n) => write!(f, "channel lagged by {n}"),
+        }
+    }
+}
+
+impl Error for RecvError {}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TryRecvError::Empty => "channel empty".fmt(f),
+            TryRecvError::Closed => "channel closed".fmt(f),
+            TryRecvError::Lagged(n) => write!(f, "channel lagged by {n}"),
+        }
+    }
+}
+
+impl Error for TryRecvError {}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecvTimeoutError::Timeout => "timed out waiting on channel".fmt(f),
+            RecvTimeoutError::Closed => "channel closed".fmt(f),
+            RecvTimeoutError::Lagged(n) => write!(f, "channel lagged by {n}"),
+        }
+    }
+}
+
+impl Error for RecvTimeoutError {}
+
+// /////////////////////////////////////////////////////////////////////////////
+// Shared
+// /////////////////////////////////////////////////////////////////////////////
+struct State<T> {
+    // the ring buffer
+    buf: VecDeque<T>,
+    // the position of the first value in the buffer
+    head: u64,
+    // the blocked receivers
+    to_wake: Vec<Arc<Blocker>>,
+    // The number of tx channels which are currently using this channel
+    tx_ports: usize,
+    // The number of rx channels which are currently using this channel
+    rx_ports: usize,
+}
+
+impl<T> State<T> {
+    // the position of the next value to send
+    #[inline]
+    fn tail(&self) -> u64 {
+        self.head + self.buf.len() as u64
+    }
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    cap: usize,
+}
+
+impl<T> Shared<T> {
+    fn new(cap: usize) -> Self {
+        Shared {
+            state: Mutex::new(State {
+                buf: VecDeque::with_capacity(cap),
+                head: 0,
+                to_wake: Vec::new(),
+                tx_ports: 1,
+                rx_ports: 1,
+            }),
+            cap,
+        }
+    }
+
+    fn send(&self, t: T) -> Result<usize, T> {
+        let mut state = self.state.lock();
+        if state.rx_ports == 0 {
+            return Err(t);
+        }
+
+        if state.buf.len() == self.cap {
+            // overwrite the oldest value
+            state.buf.pop_front();
+            state.head += 1;
+        }
+        state.buf.push_back(t);
+
+        let receivers = state.rx_ports;
+        let to_wake = std::mem::take(&mut state.to_wake);
+        drop(state);
+
+        for w in to_wake {
+            w.unpark();
+        }
+        Ok(receivers)
+    }
+
+    fn drop_tx(&self) {
+        let mut state = self.state.lock();
+        state.tx_ports -= 1;
+        if state.tx_ports == 0 {
+            // should tell all the waited rx to come back
+            let to_wake = std::mem::take(&mut state.to_wake);
+            drop(state);
+            for w in to_wake {
+                w.unpark();
+            }
+        }
+    }
+}
+
+/// The sending half of a broadcast channel
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// The receiving half of a broadcast channel
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    // the position of the next value to receive
+    next: u64,
+}
+
+/// create a broadcast channel with the given ring buffer capacity
+///
+/// # Panics
+///
+/// panic if the capacity is zero
+pub fn channel<T: Clone>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    assert!(cap > 0, "broadcast channel capacity must not be zero");
+    let shared = Arc::new(Shared::new(cap));
+    let rx = Receiver {
+        shared: shared.clone(),
+        next: 0,
+    };
+    (Sender { shared }, rx)
+}
+
+// /////////////////////////////////////////////////////////////////////////////
+// Sender
+// /////////////////////////////////////////////////////////////////////////////
+
+impl<T> Sender<T> {
+    /// send the value to all the active receivers, this would never block
+    ///
+    /// return the number of receivers that would see the value, or the value
+    /// back if there is no active receiver
+    pub fn send(&self, t: T) -> Result<usize, SendError<T>> {
+        self.shared.send(t).map_err(SendError)
+    }
+
+    /// create a new receiver that would see all the values sent after this call
+    pub fn subscribe(&self) -> Receiver<T> {
+        let mut state = self.shared.state.lock();
+        state.rx_ports += 1;
+        let next = state.tail();
+        Receiver {
+            shared: self.shared.clone(),
+            next,
+        }
+    }
+
+    /// return the number of active receivers
+    pub fn receiver_count(&self) -> usize {
+        self.shared.state.lock().rx_ports
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Sender<T> {
+        self.shared.state.lock().tx_ports += 1;
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.shared.drop_tx();
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Sender {{ .. }}")
+    }
+}
+
+// /////////////////////////////////////////////////////////////////////////////
+// Receiver
+// /////////////////////////////////////////////////////////////////////////////
+
+impl<T: Clone> Receiver<T> {
+    // try to receive a value, register the blocker if there is no value
+    fn recv_impl(&mut self, blocker: Option<&Arc<Blocker>>) -> Result<T, TryRecvError> {
+        let mut state = self.shared.state.lock();
+        if self.next < state.head {
+            let lagged = state.head - self.next;
+            self.next = state.head;
+            return Err(TryRecvError::Lagged(lagged));
+        }
+
+        if self.next < state.tail() {
+            let idx = (self.next - state.head) as usize;
+            self.next += 1;
+            return Ok(state.buf[idx].clone());
+        }
+
+        if state.tx_ports == 0 {
+            return Err(TryRecvError::Closed);
+        }
+
+        if let Some(w) = blocker {
+            state.to_wake.push(w.clone());
+        }
+        Err(TryRecvError::Empty)
+    }
+
+    /// try to receive a value without blocking
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.recv_impl(None)
+    }
+
+    /// block until a new value is received
+    pub fn recv(&mut self) -> Result<T, RecvError> {
+        loop {
+            let cur = Blocker::current();
+            match self.recv_impl(Some(&cur)) {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Lagged(n)) => return Err(RecvError::Lagged(n)),
+                Err(TryRecvError::Closed) => return Err(RecvError::Closed),
+                Err(TryRecvError::Empty) => {
+                    cur.park(None).ok();
+                }
+            }
+        }
+    }
+
+    /// same as `recv` except that with an extra timeout value
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let cur = Blocker::current();
+            match self.recv_impl(Some(&cur)) {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Lagged(n)) => return Err(RecvTimeoutError::Lagged(n)),
+                Err(TryRecvError::Closed) => return Err(RecvTimeoutError::Closed),
+                Err(TryRecvError::Empty) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    cur.park(Some(deadline - now)).ok();
+                }
+            }
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    /// the new receiver would start from the same position
+    fn clone(&self) -> Receiver<T> {
+        self.shared.state.lock().rx_ports += 1;
+        Receiver {
+            shared: self.shared.clone(),
+            next: self.next,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.state.lock().rx_ports -= 1;
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Receiver {{ .. }}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn smoke() {
+        let (tx, mut rx) = channel::<i32>(4);
+        assert_eq!(tx.send(1), Ok(1));
+        assert_eq!(rx.recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn every_receiver_sees_every_message() {
+        let (tx, rx) = channel::<i32>(16);
+        let mut handles = vec![];
+        for _ in 0..4 {
+            let mut rx = tx.subscribe();
+            handles.push(go!(move || {
+                let mut v = vec![];
+                while let Ok(i) = rx.recv() {
+                    v.push(i);
+                }
+                v
+            }));
+        }
+        drop(rx);
+        assert_eq!(tx.receiver_count(), 4);
+
+        for i in 0..10 {
+            tx.send(i).unwrap();
+        }
+        drop(tx);
+
+        for h in handles {
+            assert_eq!(h.join().unwrap(), (0..10).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn subscribe_only_see_new_values() {
+        let (tx, mut rx) = channel::<i32>(4);
+        tx.send(1).unwrap();
+        let mut rx1 = tx.subscribe();
+        tx.send(2).unwrap();
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Ok(2));
+        assert_eq!(rx1.try_recv(), Ok(2));
+        assert_eq!(rx1.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[test]
+    fn lagged() {
+        let (tx, mut rx) = channel::<i32>(2);
+        for i in 0..5 {
+            tx.send(i).unwrap();
+        }
+        assert_eq!(rx.recv(), Err(RecvError::Lagged(3)));
+        assert_eq!(rx.recv(), Ok(3));
+        assert_eq!(rx.recv(), Ok(4));
+        drop(tx);
+        assert_eq!(rx.recv(), Err(RecvError::Closed));
+    }
+
+    #[test]
+    fn no_receiver() {
+        let (tx, rx) = channel::<i32>(2);
+        drop(rx);
+        assert_eq!(tx.send(1), Err(SendError(1)));
+        let mut rx = tx.subscribe();
+        assert_eq!(tx.send(2), Ok(1));
+        assert_eq!(rx.recv(), Ok(2));
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let (tx, mut rx) = channel::<i32>(2);
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        let t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            tx.send(1).unwrap();
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_secs(10)), Ok(1));
+        t.join().unwrap();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Closed)
+        );
+    }
+
+    #[test]
+    fn sender_drop_wakeup_receiver() {
+        let (tx, mut rx) = channel::<i32>(2);
+        let tx1 = tx.clone();
+        let h = go!(move || rx.recv());
+        thread::sleep(Duration::from_millis(10));
+        drop(tx);
+        drop(tx1);
+        assert_eq!(h.join().unwrap(), Err(RecvError::Closed));
+    }
+}
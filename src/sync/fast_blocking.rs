@@ -7,11 +7,12 @@ use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use super::{blocking::ThreadPark, AtomicOption};
+use crate::dump::{set_state, CoroutineState};
 use crate::coroutine_impl::{
     co_cancel_data, is_coroutine, run_coroutine, CoroutineImpl, EventSource,
 };
 use crate::park::ParkError;
-use crate::scheduler::get_scheduler;
+use crate::scheduler::schedule;
 use crate::yield_now::{get_co_para, yield_with};
 
 pub struct Park {
@@ -40,6 +41,7 @@ impl Park {
             return Ok(());
         }
         unsafe { *self.container.get() = Some(container) };
+        set_state(CoroutineState::ParkedOnLock);
         yield_with(self);
 
         if let Some(err) = get_co_para() {
@@ -58,7 +60,7 @@ impl Park {
     pub fn unpark(&self) {
         self.state.store(true, Ordering::Release);
         if let Some(co) = self.wait_co.take() {
-            get_scheduler().schedule(co);
+            schedule(co);
         }
     }
 }
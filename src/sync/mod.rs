@@ -1,7 +1,10 @@
 mod atomic_option;
+mod barrier;
 mod blocking;
 mod condvar;
 mod mutex;
+mod notify;
+mod once;
 mod poison;
 mod rwlock;
 mod semphore;
@@ -11,13 +14,21 @@ pub(crate) mod atomic_dur;
 #[cfg(not(unix))]
 pub(crate) mod delay_drop;
 // pub(crate) mod fast_blocking;
+pub mod broadcast;
 pub mod mpmc;
 pub mod mpsc;
+pub mod oneshot;
 pub mod spsc;
+pub mod watch;
 pub use self::atomic_option::AtomicOption;
+pub use self::barrier::{Barrier, BarrierWaitResult};
 pub use self::blocking::{Blocker, FastBlocker};
 pub use self::condvar::{Condvar, WaitTimeoutResult};
 pub use self::mutex::{Mutex, MutexGuard};
+pub use self::notify::Notify;
+pub use self::once::{Lazy, OnceCell};
 pub use self::rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
-pub use self::semphore::Semphore;
+pub use self::semphore::{
+    AcquireError, OwnedSemphorePermit, Semphore, SemphorePermit, TryAcquireError,
+};
 pub use self::sync_flag::SyncFlag;
@@ -5,16 +5,111 @@
 //! it's almost the same as `mpsc` except that we support multi receivers
 //! each receiver would consume one data each time so that other receivers
 //! would not see that the same data any more
+//!
+//! a bounded queue is created by `sync_channel`, the `SyncSender` would
+//! block if the queue is full until some receiver consume the data.
+//! a zero capacity queue is a rendezvous channel, the sender would block
+//! until there is a receiver waiting for the data
 
+use std::collections::VecDeque;
+use std::error::Error;
 use std::fmt;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
 use std::sync::Arc;
 use std::time::Duration;
 
-use super::Semphore;
+use super::{AtomicOption, Blocker, Semphore};
+use crate::cancel::trigger_cancel_panic;
+use crate::park::ParkError;
+use crate::select::{SelectRecv, SelectSend, Watch};
 use crossbeam::queue::SegQueue;
 
+/// An error returned from the `SyncSender::send_timeout` method.
+///
+/// The data that could not be sent is returned back to the caller
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum SendTimeoutError<T> {
+    /// The data could not be sent on the channel because the channel is
+    /// currently full and sending would require blocking longer than the
+    /// given timeout.
+    Timeout(T),
+
+    /// The data could not be sent because all the receivers are disconnected.
+    Disconnected(T),
+}
+
+impl<T> fmt::Debug for SendTimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SendTimeoutError::Timeout(..) => "Timeout(..)".fmt(f),
+            SendTimeoutError::Disconnected(..) => "Disconnected(..)".fmt(f),
+        }
+    }
+}
+
+impl<T> fmt::Display for SendTimeoutError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SendTimeoutError::Timeout(..) => "timed out waiting on send operation".fmt(f),
+            SendTimeoutError::Disconnected(..) => "sending on a disconnected channel".fmt(f),
+        }
+    }
+}
+
+impl<T> Error for SendTimeoutError<T> {}
+
+// /////////////////////////////////////////////////////////////////////////////
+// Rendezvous
+// /////////////////////////////////////////////////////////////////////////////
+
+// a blocked sender or receiver of a rendezvous channel
+struct Waiter<T> {
+    // the data of the sender, or the data handed to the receiver
+    data: AtomicOption<T>,
+    blocker: Blocker,
+}
+
+impl<T> Waiter<T> {
+    fn new(data: Option<T>) -> Arc<Self> {
+        let waiter = Waiter {
+            data: AtomicOption::none(),
+            blocker: Blocker::new(false),
+        };
+        if let Some(t) = data {
+            waiter.data.store(t);
+        }
+        Arc::new(waiter)
+    }
+}
+
+// the blocked senders and receivers of a rendezvous channel, the data is
+// handed from one side to the other directly
+struct Waiters<T> {
+    senders: VecDeque<Arc<Waiter<T>>>,
+    receivers: VecDeque<Arc<Waiter<T>>>,
+}
+
+impl<T> Waiters<T> {
+    fn new() -> Self {
+        Waiters {
+            senders: VecDeque::new(),
+            receivers: VecDeque::new(),
+        }
+    }
+}
+
+// remove the waiter from the queue, return false if it's already taken
+fn remove_waiter<T>(queue: &mut VecDeque<Arc<Waiter<T>>>, w: &Arc<Waiter<T>>) -> bool {
+    match queue.iter().position(|x| Arc::ptr_eq(x, w)) {
+        Some(i) => {
+            queue.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
 /// /////////////////////////////////////////////////////////////////////////////
 /// InnerQueue
 /// /////////////////////////////////////////////////////////////////////////////
@@ -22,6 +117,13 @@ struct InnerQueue<T> {
     queue: SegQueue<T>,
     // thread/coroutine for wake up
     sem: Semphore,
+    // free slots of a bounded queue, the blocked senders wait on it
+    slots: Semphore,
+    // the blocked senders and receivers of a zero capacity queue, the select
+    // watchers are still registered on `sem` and `slots`
+    waiters: parking_lot::Mutex<Waiters<T>>,
+    // the capacity of the queue, None means unbounded
+    cap: Option<usize>,
     // The number of tx channels which are currently using this queue.
     tx_ports: AtomicUsize,
     // if rx is dropped
@@ -29,15 +131,23 @@ struct InnerQueue<T> {
 }
 
 impl<T> InnerQueue<T> {
-    pub fn new() -> InnerQueue<T> {
+    pub fn new(cap: Option<usize>) -> InnerQueue<T> {
         InnerQueue {
             queue: SegQueue::new(),
             sem: Semphore::new(0),
+            slots: Semphore::new(cap.unwrap_or(0)),
+            waiters: parking_lot::Mutex::new(Waiters::new()),
+            cap,
             tx_ports: AtomicUsize::new(1),
             rx_ports: AtomicUsize::new(1),
         }
     }
 
+    #[inline]
+    fn is_rendezvous(&self) -> bool {
+        self.cap == Some(0)
+    }
+
     pub fn send(&self, t: T) -> Result<(), SendError<T>> {
         if self.rx_ports.load(Ordering::Acquire) == 0 {
             return Err(SendError(t));
@@ -48,7 +158,111 @@ impl<T> InnerQueue<T> {
         Ok(())
     }
 
+    // wait for a free slot before push the data
+    pub fn sync_send(&self, t: T, dur: Option<Duration>) -> Result<(), SendTimeoutError<T>> {
+        if self.rx_ports.load(Ordering::Acquire) == 0 {
+            return Err(SendTimeoutError::Disconnected(t));
+        }
+
+        if self.is_rendezvous() {
+            return self.send_rendezvous(t, dur);
+        }
+
+        match dur {
+            None => self.slots.wait(),
+            Some(d) => {
+                if !self.slots.wait_timeout(d) {
+                    return Err(SendTimeoutError::Timeout(t));
+                }
+            }
+        }
+
+        self.push_slot(t).map_err(SendTimeoutError::Disconnected)
+    }
+
+    pub fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
+        if self.rx_ports.load(Ordering::Acquire) == 0 {
+            return Err(TrySendError::Disconnected(t));
+        }
+
+        if self.is_rendezvous() {
+            return match self.send_rendezvous(t, Some(Duration::ZERO)) {
+                Ok(()) => Ok(()),
+                Err(SendTimeoutError::Timeout(t)) => Err(TrySendError::Full(t)),
+                Err(SendTimeoutError::Disconnected(t)) => Err(TrySendError::Disconnected(t)),
+            };
+        }
+
+        if !self.slots.try_wait() {
+            return Err(TrySendError::Full(t));
+        }
+
+        self.push_slot(t).map_err(TrySendError::Disconnected)
+    }
+
+    // hand the data to a waiting receiver, or wait for one to take it
+    fn send_rendezvous(&self, t: T, dur: Option<Duration>) -> Result<(), SendTimeoutError<T>> {
+        let mut waiters = self.waiters.lock();
+        // checked with the lock held, see `drop_rx`
+        if self.rx_ports.load(Ordering::Acquire) == 0 {
+            return Err(SendTimeoutError::Disconnected(t));
+        }
+        if let Some(rx) = waiters.receivers.pop_front() {
+            rx.data.store(t);
+            drop(waiters);
+            rx.blocker.unpark();
+            return Ok(());
+        }
+        if dur == Some(Duration::ZERO) {
+            return Err(SendTimeoutError::Timeout(t));
+        }
+
+        let me = Waiter::new(Some(t));
+        waiters.senders.push_back(me.clone());
+        drop(waiters);
+        // tell the selecting receivers that a sender is waiting
+        self.sem.wakeup_watchers();
+
+        let ret = me.blocker.park(dur);
+        if remove_waiter(&mut self.waiters.lock().senders, &me) {
+            let t = me.data.take().expect("rendezvous sender lost the data");
+            if ret == Err(ParkError::Canceled) {
+                trigger_cancel_panic();
+            }
+            return Err(SendTimeoutError::Timeout(t));
+        }
+        // the data is left if the sender is woken up by the last dropped receiver
+        match me.data.take() {
+            None => Ok(()),
+            Some(t) => Err(SendTimeoutError::Disconnected(t)),
+        }
+    }
+
+    // push the data after a slot is acquired
+    fn push_slot(&self, t: T) -> Result<(), T> {
+        // the receivers may be dropped when we are waiting
+        if self.rx_ports.load(Ordering::Acquire) == 0 {
+            return Err(t);
+        }
+
+        self.queue.push(t);
+        self.sem.post();
+        Ok(())
+    }
+
+    // give back the slot after the data is consumed
+    #[inline]
+    fn release_slot(&self) {
+        if self.cap.is_some() {
+            self.slots.post();
+        }
+    }
+
     pub fn recv(&self, dur: Option<Duration>) -> Result<T, RecvTimeoutError> {
+        if self.is_rendezvous() {
+            return self.recv_rendezvous(dur);
+        }
+
         match self.try_recv() {
             Ok(data) => return Ok(data),
             Err(TryRecvError::Empty) => {}
@@ -65,7 +279,10 @@ impl<T> InnerQueue<T> {
         }
 
         match self.queue.pop() {
-            Some(data) => Ok(data),
+            Some(data) => {
+                self.release_slot();
+                Ok(data)
+            }
             None => match self.tx_ports.load(Ordering::Acquire) {
                 0 => Err(RecvTimeoutError::Disconnected),
                 _n => unreachable!("mpmc recv found no data"),
@@ -73,7 +290,52 @@ impl<T> InnerQueue<T> {
         }
     }
 
+    // take the data from a waiting sender, or wait for one to hand it over
+    fn recv_rendezvous(&self, dur: Option<Duration>) -> Result<T, RecvTimeoutError> {
+        let mut waiters = self.waiters.lock();
+        if let Some(tx) = waiters.senders.pop_front() {
+            let t = tx.data.take().expect("rendezvous sender has no data");
+            drop(waiters);
+            tx.blocker.unpark();
+            return Ok(t);
+        }
+        // checked with the lock held, see `drop_tx`
+        if self.tx_ports.load(Ordering::Acquire) == 0 {
+            return Err(RecvTimeoutError::Disconnected);
+        }
+        if dur == Some(Duration::ZERO) {
+            return Err(RecvTimeoutError::Timeout);
+        }
+
+        let me = Waiter::new(None);
+        waiters.receivers.push_back(me.clone());
+        drop(waiters);
+        // tell the selecting senders that a receiver is waiting
+        self.slots.wakeup_watchers();
+
+        let ret = me.blocker.park(dur);
+        if remove_waiter(&mut self.waiters.lock().receivers, &me) {
+            if ret == Err(ParkError::Canceled) {
+                trigger_cancel_panic();
+            }
+            return match self.tx_ports.load(Ordering::Acquire) {
+                0 => Err(RecvTimeoutError::Disconnected),
+                _ => Err(RecvTimeoutError::Timeout),
+            };
+        }
+        // no data is handed if it's woken up by the last dropped sender
+        me.data.take().ok_or(RecvTimeoutError::Disconnected)
+    }
+
     pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        if self.is_rendezvous() {
+            return match self.recv_rendezvous(Some(Duration::ZERO)) {
+                Ok(data) => Ok(data),
+                Err(RecvTimeoutError::Timeout) => Err(TryRecvError::Empty),
+                Err(RecvTimeoutError::Disconnected) => Err(TryRecvError::Disconnected),
+            };
+        }
+
         if !self.sem.try_wait() {
             return match self.tx_ports.load(Ordering::Acquire) {
                 0 => Err(TryRecvError::Disconnected),
@@ -82,7 +344,10 @@ impl<T> InnerQueue<T> {
         }
 
         match self.queue.pop() {
-            Some(data) => Ok(data),
+            Some(data) => {
+                self.release_slot();
+                Ok(data)
+            }
             None => match self.tx_ports.load(Ordering::Acquire) {
                 0 => Err(TryRecvError::Disconnected),
                 _ => unreachable!("mpmc try_recv found no data"),
@@ -99,8 +364,16 @@ impl<T> InnerQueue<T> {
             1 => {
                 // there is no tx port any more
                 // should tell all the waited rx to come back
-                while self.sem.get_value() == 0 {
-                    self.sem.post();
+                if self.is_rendezvous() {
+                    let receivers = std::mem::take(&mut self.waiters.lock().receivers);
+                    for rx in receivers {
+                        rx.blocker.unpark();
+                    }
+                    self.sem.wakeup_watchers();
+                } else {
+                    while self.sem.get_value() == 0 {
+                        self.sem.post();
+                    }
                 }
             }
             n if n > 1 => {}
@@ -117,6 +390,17 @@ impl<T> InnerQueue<T> {
             1 => {
                 // there is no receiver any more, clear the data
                 while self.queue.pop().is_some() {}
+                // should tell all the blocked senders to come back
+                if self.is_rendezvous() {
+                    let senders = std::mem::take(&mut self.waiters.lock().senders);
+                    for tx in senders {
+                        tx.blocker.unpark();
+                    }
+                    self.slots.wakeup_watchers();
+                } else if self.cap.is_some() {
+                    // the blocked senders and the late comers see the close
+                    self.slots.close();
+                }
             }
             n if n > 1 => {}
             n => panic!("bad number of rx_ports left {n}"),
@@ -157,11 +441,32 @@ pub struct Sender<T> {
 unsafe impl<T: Send> Send for Sender<T> {}
 // impl<T> !Sync for Sender<T> {}
 
+/// The sending half of a bounded mpmc channel created by [`sync_channel`]
+///
+/// the data is sent only when a slot is free, or a receiver is ready to take
+/// it for a rendezvous channel, otherwise the sender is blocked
+pub struct SyncSender<T> {
+    inner: Arc<InnerQueue<T>>,
+}
+
+unsafe impl<T: Send> Send for SyncSender<T> {}
+
+/// create an unbounded mpmc channel
 pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
-    let a = Arc::new(InnerQueue::new());
+    let a = Arc::new(InnerQueue::new(None));
     (Sender::new(a.clone()), Receiver::new(a))
 }
 
+/// create a bounded mpmc channel with the given capacity
+///
+/// the `SyncSender` would block when the queue is full, if the capacity
+/// is zero, the channel becomes a rendezvous channel that each send would
+/// block until a receiver is ready to take the data
+pub fn sync_channel<T>(cap: usize) -> (SyncSender<T>, Receiver<T>) {
+    let a = Arc::new(InnerQueue::new(Some(cap)));
+    (SyncSender::new(a.clone()), Receiver::new(a))
+}
+
 /// /////////////////////////////////////////////////////////////////////////////
 /// Sender
 /// /////////////////////////////////////////////////////////////////////////////
@@ -200,6 +505,59 @@ impl<T> fmt::Debug for Sender<T> {
     }
 }
 
+/// /////////////////////////////////////////////////////////////////////////////
+/// SyncSender
+/// /////////////////////////////////////////////////////////////////////////////
+
+impl<T> SyncSender<T> {
+    fn new(inner: Arc<InnerQueue<T>>) -> SyncSender<T> {
+        SyncSender { inner }
+    }
+
+    /// send data to the channel, would block if the channel is full
+    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        match self.inner.sync_send(t, None) {
+            Ok(()) => Ok(()),
+            Err(SendTimeoutError::Disconnected(t)) => Err(SendError(t)),
+            Err(SendTimeoutError::Timeout(_)) => unreachable!("mpmc send timeout"),
+        }
+    }
+
+    /// same as `send` except that it would give up if the data can't be sent in time
+    pub fn send_timeout(&self, t: T, timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+        self.inner.sync_send(t, Some(timeout))
+    }
+
+    /// try to send data to the channel without blocking
+    pub fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
+        self.inner.try_send(t)
+    }
+
+    /// return how many elements in the queue that are not consumed by receivers
+    pub fn pressure(&self) -> usize {
+        self.inner.sem.get_value()
+    }
+}
+
+impl<T> Clone for SyncSender<T> {
+    fn clone(&self) -> SyncSender<T> {
+        self.inner.clone_tx();
+        SyncSender::new(self.inner.clone())
+    }
+}
+
+impl<T> Drop for SyncSender<T> {
+    fn drop(&mut self) {
+        self.inner.drop_tx();
+    }
+}
+
+impl<T> fmt::Debug for SyncSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SyncSender {{ .. }}")
+    }
+}
+
 /// /////////////////////////////////////////////////////////////////////////////
 /// Receiver
 /// /////////////////////////////////////////////////////////////////////////////
@@ -293,6 +651,68 @@ impl<T> fmt::Debug for Receiver<T> {
     }
 }
 
+// /////////////////////////////////////////////////////////////////////////////
+// Select
+// /////////////////////////////////////////////////////////////////////////////
+
+impl<T> Watch for Receiver<T> {
+    fn watch(&self, blocker: &Arc<Blocker>) {
+        self.inner.sem.watch(blocker);
+    }
+
+    fn unwatch(&self, blocker: &Arc<Blocker>) {
+        self.inner.sem.unwatch(blocker);
+    }
+}
+
+impl<T> SelectRecv for Receiver<T> {
+    type Item = T;
+
+    fn try_select_recv(&self) -> Option<Result<T, RecvError>> {
+        match self.inner.try_recv() {
+            Ok(data) => Some(Ok(data)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(RecvError)),
+        }
+    }
+}
+
+impl<T> Watch for Sender<T> {
+    // the unbounded sender never blocks
+    fn watch(&self, _blocker: &Arc<Blocker>) {}
+    fn unwatch(&self, _blocker: &Arc<Blocker>) {}
+}
+
+impl<T> SelectSend for Sender<T> {
+    type Item = T;
+
+    fn try_select_send(&self, t: T) -> Result<Result<(), SendError<T>>, T> {
+        Ok(self.send(t))
+    }
+}
+
+impl<T> Watch for SyncSender<T> {
+    fn watch(&self, blocker: &Arc<Blocker>) {
+        self.inner.slots.watch(blocker);
+    }
+
+    fn unwatch(&self, blocker: &Arc<Blocker>) {
+        self.inner.slots.unwatch(blocker);
+    }
+}
+
+impl<T> SelectSend for SyncSender<T> {
+    type Item = T;
+
+    fn try_select_send(&self, t: T) -> Result<Result<(), SendError<T>>, T> {
+        match self.inner.try_send(t) {
+            Ok(()) => Ok(Ok(())),
+            Err(TrySendError::Full(t)) => Err(t),
+            Err(TrySendError::Disconnected(t)) => Ok(Err(SendError(t))),
+        }
+    }
+}
+
 #[cfg(test)]
 #[allow(clippy::redundant_clone)]
 mod tests {
@@ -1035,4 +1455,174 @@ mod tests {
         }
         assert!(rx1.try_recv().is_err());
     }
+
+    #[test]
+    fn sync_smoke() {
+        let (tx, rx) = sync_channel::<i32>(1);
+        tx.send(1).unwrap();
+        assert_eq!(rx.recv().unwrap(), 1);
+    }
+
+    #[test]
+    fn sync_try_send_full() {
+        let (tx, rx) = sync_channel::<i32>(2);
+        assert_eq!(tx.try_send(1), Ok(()));
+        assert_eq!(tx.try_send(2), Ok(()));
+        assert_eq!(tx.try_send(3), Err(TrySendError::Full(3)));
+        assert_eq!(tx.pressure(), 2);
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert_eq!(tx.try_send(3), Ok(()));
+        assert_eq!(rx.recv().unwrap(), 2);
+        assert_eq!(rx.recv().unwrap(), 3);
+        drop(rx);
+        assert_eq!(tx.try_send(4), Err(TrySendError::Disconnected(4)));
+    }
+
+    #[test]
+    fn sync_send_timeout() {
+        let (tx, rx) = sync_channel::<i32>(1);
+        tx.send(1).unwrap();
+        assert_eq!(
+            tx.send_timeout(2, Duration::from_millis(10)),
+            Err(SendTimeoutError::Timeout(2))
+        );
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert_eq!(tx.send_timeout(2, Duration::from_millis(10)), Ok(()));
+        assert_eq!(rx.recv().unwrap(), 2);
+    }
+
+    #[test]
+    fn sync_send_block_coroutine() {
+        let (tx, rx) = sync_channel::<i32>(1);
+        let h = go!(move || {
+            for i in 0..100 {
+                tx.send(i).unwrap();
+            }
+        });
+        for i in 0..100 {
+            assert_eq!(rx.recv().unwrap(), i);
+        }
+        h.join().unwrap();
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn sync_send_block_thread() {
+        let (tx, rx) = sync_channel::<i32>(3);
+        let t = thread::spawn(move || {
+            for i in 0..100 {
+                tx.send(i).unwrap();
+            }
+        });
+        let h = go!(move || {
+            for i in 0..100 {
+                assert_eq!(rx.recv().unwrap(), i);
+            }
+        });
+        t.join().unwrap();
+        h.join().unwrap();
+    }
+
+    #[test]
+    fn sync_port_gone_wakeup_sender() {
+        let (tx, rx) = sync_channel::<i32>(1);
+        tx.send(1).unwrap();
+        let h = go!(move || tx.send(2));
+        thread::sleep(Duration::from_millis(10));
+        drop(rx);
+        assert_eq!(h.join().unwrap(), Err(SendError(2)));
+    }
+
+    #[test]
+    fn rendezvous_try() {
+        let (tx, rx) = sync_channel::<i32>(0);
+        assert_eq!(tx.try_send(1), Err(TrySendError::Full(1)));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        // the slot offered by the timed out receiver is revoked
+        assert_eq!(tx.try_send(1), Err(TrySendError::Full(1)));
+        assert_eq!(
+            tx.send_timeout(1, Duration::from_millis(10)),
+            Err(SendTimeoutError::Timeout(1))
+        );
+        drop(tx);
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn rendezvous_coroutine() {
+        let (tx, rx) = sync_channel::<i32>(0);
+        let rx1 = rx.clone();
+        let h1 = go!(move || rx1.iter().count());
+        let h2 = go!(move || rx.iter().count());
+        let t = thread::spawn(move || {
+            for i in 0..100 {
+                tx.send(i).unwrap();
+            }
+        });
+        t.join().unwrap();
+        assert_eq!(h1.join().unwrap() + h2.join().unwrap(), 100);
+    }
+
+    #[test]
+    fn rendezvous_timeout_race() {
+        let (tx, rx) = sync_channel::<i32>(0);
+        let t = thread::spawn(move || {
+            let timeout = Duration::from_micros(50);
+            (0..2000)
+                .filter(|&i| tx.send_timeout(i, timeout).is_ok())
+                .count()
+        });
+        let mut received = 0;
+        loop {
+            match rx.recv_timeout(Duration::from_micros(50)) {
+                Ok(_) => received += 1,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+        assert_eq!(t.join().unwrap(), received);
+    }
+
+    #[test]
+    fn rendezvous_sender_wait_receiver() {
+        let (tx, rx) = sync_channel::<i32>(0);
+        let h = go!(move || {
+            tx.send(1).unwrap();
+            tx.send(2).unwrap();
+        });
+        thread::sleep(Duration::from_millis(10));
+        // the sender is still blocked since no receiver is waiting
+        assert!(!h.is_done());
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert_eq!(rx.recv().unwrap(), 2);
+        h.join().unwrap();
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn rendezvous_handoff() {
+        let (tx, rx) = sync_channel::<i32>(0);
+        let tx1 = tx.clone();
+        let h = thread::spawn(move || tx1.send(1));
+        // the data is taken from the blocked sender
+        let data = loop {
+            match rx.try_recv() {
+                Ok(data) => break data,
+                Err(TryRecvError::Empty) => thread::sleep(Duration::from_millis(1)),
+                Err(TryRecvError::Disconnected) => unreachable!(),
+            }
+        };
+        assert_eq!(data, 1);
+        h.join().unwrap().unwrap();
+
+        // the blocked sender is woken up by the dropped receiver
+        let h = go!(move || tx.send(2));
+        thread::sleep(Duration::from_millis(10));
+        drop(rx);
+        assert_eq!(h.join().unwrap(), Err(SendError(2)));
+    }
 }
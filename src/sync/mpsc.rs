@@ -9,6 +9,7 @@ use std::time::{Duration, Instant};
 
 use super::{AtomicOption, Blocker};
 use crate::likely::{likely, unlikely};
+use crate::select::{SelectRecv, SelectSend, Watch, Watchers};
 
 use may_queue::mpsc::Queue;
 
@@ -20,6 +21,8 @@ struct InnerQueue<T> {
     queue: Queue<T>,
     // thread/coroutine for wake up
     to_wake: AtomicOption<Arc<Blocker>>,
+    // the selects that are watching the receiver
+    watchers: Watchers,
     // The number of tx channels which are currently using this queue.
     channels: AtomicUsize,
     // if rx is dropped
@@ -31,6 +34,7 @@ impl<T> InnerQueue<T> {
         InnerQueue {
             queue: Queue::new(),
             to_wake: AtomicOption::none(),
+            watchers: Watchers::new(),
             channels: AtomicUsize::new(1),
             port_dropped: AtomicBool::new(false),
         }
@@ -44,6 +48,7 @@ impl<T> InnerQueue<T> {
         if let Some(w) = self.to_wake.take() {
             w.unpark();
         }
+        self.watchers.wakeup();
         Ok(())
     }
 
@@ -93,7 +98,12 @@ impl<T> InnerQueue<T> {
 
     pub fn drop_chan(&self) {
         match self.channels.fetch_sub(1, Ordering::AcqRel) {
-            1 => self.to_wake.take().map(|w| w.unpark()).unwrap_or(()),
+            1 => {
+                if let Some(w) = self.to_wake.take() {
+                    w.unpark();
+                }
+                self.watchers.wakeup();
+            }
             n if n > 1 => {}
             n => panic!("bad number of channels left {n}"),
         }
@@ -290,6 +300,46 @@ impl<T> fmt::Debug for Receiver<T> {
     }
 }
 
+// /////////////////////////////////////////////////////////////////////////////
+// Select
+// /////////////////////////////////////////////////////////////////////////////
+
+impl<T> Watch for Receiver<T> {
+    fn watch(&self, blocker: &Arc<Blocker>) {
+        self.inner.watchers.watch(blocker);
+    }
+
+    fn unwatch(&self, blocker: &Arc<Blocker>) {
+        self.inner.watchers.unwatch(blocker);
+    }
+}
+
+impl<T> SelectRecv for Receiver<T> {
+    type Item = T;
+
+    fn try_select_recv(&self) -> Option<Result<T, RecvError>> {
+        match self.inner.try_recv() {
+            Ok(data) => Some(Ok(data)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(RecvError)),
+        }
+    }
+}
+
+impl<T> Watch for Sender<T> {
+    // the unbounded sender never blocks
+    fn watch(&self, _blocker: &Arc<Blocker>) {}
+    fn unwatch(&self, _blocker: &Arc<Blocker>) {}
+}
+
+impl<T> SelectSend for Sender<T> {
+    type Item = T;
+
+    fn try_select_send(&self, t: T) -> Result<Result<(), SendError<T>>, T> {
+        Ok(self.send(t))
+    }
+}
+
 #[cfg(test)]
 #[allow(clippy::redundant_clone)]
 mod tests {
@@ -0,0 +1,435 @@
+//! notify primitive implementation
+//!
+//! a `Notify` is used to wake up waiters without any data, it's lighter
+//! than a `Condvar` which needs a `Mutex`, and unlike `SyncFlag` it can
+//! be used again and again.
+//!
+//! `notify_one` stores a permit if there is no waiter, so a notification
+//! that happens before the `notified` call is not lost. `notify_waiters`
+//! wakes up all the current waiters and doesn't store any permit.
+//!
+//! a `Notify` can be used in the `wait` arm of `select!`, the arm is
+//! selected by consuming the stored permit, so it's only woken up by
+//! `notify_one` when there is no other waiter.
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::{Blocker, FastBlocker};
+use crate::coroutine_impl::is_coroutine;
+use crate::select::{SelectWait, Watch, Watchers};
+use parking_lot::Mutex;
+
+// the waiter is not notified yet
+const WAITING: usize = 0;
+// the waiter is notified by `notify_one`
+const NOTIFIED_ONE: usize = 1;
+// the waiter is notified by `notify_waiters`
+const NOTIFIED_ALL: usize = 2;
+
+enum Parker {
+    Coroutine(FastBlocker),
+    Thread(Blocker),
+}
+
+struct Waiter {
+    state: AtomicUsize,
+    parker: Parker,
+}
+
+impl Waiter {
+    fn new() -> Self {
+        let parker = if is_coroutine() {
+            Parker::Coroutine(FastBlocker::new())
+        } else {
+            Parker::Thread(Blocker::new(false))
+        };
+        Waiter {
+            state: AtomicUsize::new(WAITING),
+            parker,
+        }
+    }
+
+    #[inline]
+    fn is_notified(&self) -> bool {
+        self.state.load(Ordering::Acquire) != WAITING
+    }
+
+    #[inline]
+    fn park(&self, dur: Option<Duration>) {
+        // the cancel would panic inside the park
+        let _ = match self.parker {
+            Parker::Coroutine(ref co) => co.park(dur),
+            Parker::Thread(ref t) => t.park(dur),
+        };
+    }
+
+    #[inline]
+    fn unpark(&self) {
+        match self.parker {
+            Parker::Coroutine(ref co) => co.unpark(),
+            Parker::Thread(ref t) => t.unpark(),
+        }
+    }
+}
+
+struct State {
+    // a stored notification that is not consumed yet
+    permit: bool,
+    // the waiters in fifo order
+    waiters: VecDeque<Arc<Waiter>>,
+}
+
+impl State {
+    // pick up the first waiter or store a permit
+    // the returned waiter must be unparked after the lock is released
+    fn notify_one(&mut self) -> Option<Arc<Waiter>> {
+        match self.waiters.pop_front() {
+            Some(w) => {
+                w.state.store(NOTIFIED_ONE, Ordering::Release);
+                Some(w)
+            }
+            None => {
+                self.permit = true;
+                None
+            }
+        }
+    }
+}
+
+/// Notify primitive
+///
+/// Notify allows threads and coroutines to wake up each other without
+/// sending any data. the waiting coroutines are parked without blocking
+/// the worker thread.
+///
+/// a canceled waiter would pass the notification it got to the next waiter,
+/// so a notification is not lost when the waiting coroutine is canceled.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use may::sync::Notify;
+///
+/// let notify = Arc::new(Notify::new());
+/// let notify2 = notify.clone();
+///
+/// let h = may::go!(move || {
+///     notify2.notified();
+///     println!("got notified");
+/// });
+///
+/// notify.notify_one();
+/// h.join().unwrap();
+///
+/// // the stored permit would select the `wait` arm
+/// notify.notify_one();
+/// let (_tx, rx) = may::sync::mpsc::channel::<()>();
+/// may::select! {
+///     recv(rx) -> _ => unreachable!(),
+///     wait(*notify) => println!("got notified"),
+/// }
+/// ```
+pub struct Notify {
+    state: Mutex<State>,
+    // the selects that are waiting for the permit
+    watchers: Watchers,
+}
+
+// make sure the waiter is removed or the notification is passed on
+struct WaitGuard<'a> {
+    notify: &'a Notify,
+    waiter: &'a Arc<Waiter>,
+}
+
+impl<'a> Drop for WaitGuard<'a> {
+    fn drop(&mut self) {
+        let w = {
+            let mut state = self.notify.state.lock();
+            match self.waiter.state.load(Ordering::Acquire) {
+                WAITING => {
+                    state.waiters.retain(|w| !Arc::ptr_eq(w, self.waiter));
+                    return;
+                }
+                NOTIFIED_ONE => state.notify_one(),
+                _ => return,
+            }
+        };
+        match w {
+            Some(w) => w.unpark(),
+            None => self.notify.watchers.wakeup(),
+        }
+    }
+}
+
+impl Notify {
+    /// create a new Notify without any stored permit
+    pub const fn new() -> Self {
+        Notify {
+            state: Mutex::new(State {
+                permit: false,
+                waiters: VecDeque::new(),
+            }),
+            watchers: Watchers::new(),
+        }
+    }
+
+    /// wake up the first waiter
+    ///
+    /// if there is no waiter, a permit is stored and the next `notified`
+    /// call would return immediately. at most one permit is stored.
+    pub fn notify_one(&self) {
+        let w = self.state.lock().notify_one();
+        match w {
+            Some(w) => w.unpark(),
+            // the permit is stored, let the selects race for it
+            None => self.watchers.wakeup(),
+        }
+    }
+
+    /// wake up all the current waiters
+    ///
+    /// no permit is stored, the following `notified` calls would block
+    pub fn notify_waiters(&self) {
+        let waiters = {
+            let mut state = self.state.lock();
+            let waiters = std::mem::take(&mut state.waiters);
+            for w in waiters.iter() {
+                w.state.store(NOTIFIED_ALL, Ordering::Release);
+            }
+            waiters
+        };
+        for w in waiters {
+            w.unpark();
+        }
+    }
+
+    // return false if timeout
+    fn notified_impl(&self, dur: Option<Duration>) -> bool {
+        let deadline = dur.map(|d| Instant::now() + d);
+        let waiter = {
+            let mut state = self.state.lock();
+            if state.permit {
+                state.permit = false;
+                return true;
+            }
+            let w = Arc::new(Waiter::new());
+            state.waiters.push_back(w.clone());
+            w
+        };
+
+        let guard = WaitGuard {
+            notify: self,
+            waiter: &waiter,
+        };
+
+        while !waiter.is_notified() {
+            let timeout = match deadline {
+                None => None,
+                Some(d) => {
+                    let now = Instant::now();
+                    if now >= d {
+                        // the guard would pass on a notification that
+                        // comes after the check, so just give up here
+                        let mut state = self.state.lock();
+                        if waiter.is_notified() {
+                            break;
+                        }
+                        state.waiters.retain(|w| !Arc::ptr_eq(w, &waiter));
+                        drop(state);
+                        std::mem::forget(guard);
+                        return false;
+                    }
+                    Some(d - now)
+                }
+            };
+            waiter.park(timeout);
+        }
+
+        std::mem::forget(guard);
+        true
+    }
+
+    /// wait for a notification
+    ///
+    /// return immediately if there is a stored permit and consume it,
+    /// otherwise block until `notify_one` or `notify_waiters` is called
+    pub fn notified(&self) {
+        self.notified_impl(None);
+    }
+
+    /// same as `notified` except that with an extra timeout value
+    /// return false if timeout happened
+    pub fn notified_timeout(&self, dur: Duration) -> bool {
+        self.notified_impl(Some(dur))
+    }
+}
+
+impl Watch for Notify {
+    fn watch(&self, blocker: &Arc<Blocker>) {
+        self.watchers.watch(blocker);
+    }
+
+    fn unwatch(&self, blocker: &Arc<Blocker>) {
+        self.watchers.unwatch(blocker);
+    }
+}
+
+impl SelectWait for Notify {
+    // consume the stored permit
+    fn is_ready(&self) -> bool {
+        let mut state = self.state.lock();
+        std::mem::replace(&mut state.permit, false)
+    }
+}
+
+impl Default for Notify {
+    fn default() -> Self {
+        Notify::new()
+    }
+}
+
+impl fmt::Debug for Notify {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Notify {{ .. }}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sync::mpsc::channel;
+    use std::thread;
+
+    #[test]
+    fn stored_permit() {
+        let notify = Notify::new();
+        notify.notify_one();
+        notify.notify_one();
+        // only one permit is stored
+        notify.notified();
+        assert!(!notify.notified_timeout(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn notify_one_in_order() {
+        let notify = Arc::new(Notify::new());
+        let (tx, rx) = channel();
+        let mut handles = vec![];
+        for i in 0..5 {
+            let n = notify.clone();
+            let tx = tx.clone();
+            handles.push(go!(move || {
+                n.notified();
+                tx.send(i).unwrap();
+            }));
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        for i in 0..5 {
+            notify.notify_one();
+            assert_eq!(rx.recv().unwrap(), i);
+        }
+        for h in handles {
+            h.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn notify_waiters() {
+        let notify = Arc::new(Notify::new());
+        let mut handles = vec![];
+        for _ in 0..10 {
+            let n = notify.clone();
+            handles.push(go!(move || n.notified()));
+        }
+        let n = notify.clone();
+        let t = thread::spawn(move || n.notified());
+        thread::sleep(Duration::from_millis(20));
+
+        notify.notify_waiters();
+        for h in handles {
+            h.join().unwrap();
+        }
+        t.join().unwrap();
+        // no permit is stored
+        assert!(!notify.notified_timeout(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn notified_timeout() {
+        let notify = Arc::new(Notify::new());
+        let n = notify.clone();
+        let h = go!(move || n.notified_timeout(Duration::from_millis(10)));
+        assert!(!h.join().unwrap());
+
+        // the timed out waiter doesn't consume the notification
+        let n = notify.clone();
+        let h = go!(move || n.notified_timeout(Duration::from_secs(10)));
+        thread::sleep(Duration::from_millis(10));
+        notify.notify_one();
+        assert!(h.join().unwrap());
+    }
+
+    #[test]
+    fn canceled_waiter() {
+        let notify = Arc::new(Notify::new());
+        let n = notify.clone();
+        let h1 = go!(move || n.notified());
+        thread::sleep(Duration::from_millis(10));
+        let n = notify.clone();
+        let h2 = go!(move || n.notified());
+        thread::sleep(Duration::from_millis(10));
+
+        unsafe { h1.coroutine().cancel() };
+        h1.join().unwrap_err();
+        notify.notify_one();
+        h2.join().unwrap();
+    }
+
+    #[test]
+    fn select_notify() {
+        let n1 = Arc::new(Notify::new());
+        let n2 = Arc::new(Notify::new());
+        let n = n1.clone();
+        go!(move || {
+            crate::coroutine::sleep(Duration::from_millis(10));
+            n.notify_one();
+        });
+
+        let id = select! {
+            wait(*n2) => 2,
+            wait(*n1) => 1,
+        };
+        assert_eq!(id, 1);
+        // the permit is consumed by the select
+        assert!(!n1.notified_timeout(Duration::from_millis(10)));
+        // the losing arm doesn't leave a watcher behind
+        n2.notify_one();
+        n2.notified();
+    }
+
+    #[test]
+    fn select_notify_one_permit() {
+        let notify = Arc::new(Notify::new());
+        let (tx, rx) = channel();
+        for _ in 0..2 {
+            let n = notify.clone();
+            let tx = tx.clone();
+            go!(move || {
+                select! {
+                    wait(*n) => tx.send(()).unwrap(),
+                    timeout(Duration::from_millis(100)) => {},
+                }
+            });
+        }
+        thread::sleep(Duration::from_millis(20));
+        // only one of the selects could take the permit
+        notify.notify_one();
+        drop(tx);
+        assert_eq!(rx.iter().count(), 1);
+    }
+}
@@ -0,0 +1,413 @@
+//! `OnceCell` and `Lazy` for both thread and coroutine
+//!
+//! unlike `std::sync::Once`, the waiters are parked instead of blocking
+//! the worker thread, so it's safe to do coroutine io in the initialization.
+//! if the initialization panics, the cell is poisoned just like the
+//! `Mutex`, any later access would panic. a canceled coroutine would not
+//! poison the cell, the next caller would run the initialization again
+use std::cell::{Cell, UnsafeCell};
+use std::fmt;
+use std::mem::MaybeUninit;
+use std::ops::Deref;
+use std::panic::{RefUnwindSafe, UnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use super::{poison, Blocker};
+use parking_lot::Mutex;
+
+// the value is not initialized
+const INCOMPLETE: usize = 0;
+// some one is running the initialization
+const RUNNING: usize = 1;
+// the value is initialized
+const COMPLETE: usize = 2;
+
+/// A cell which can be written to only once
+///
+/// # Examples
+///
+/// ```rust
+/// use may::sync::OnceCell;
+///
+/// static CELL: OnceCell<String> = OnceCell::new();
+///
+/// let h = may::go!(|| {
+///     let value = CELL.get_or_init(|| "hello".to_owned());
+///     assert_eq!(value, "hello");
+/// });
+/// h.join().unwrap();
+/// assert_eq!(CELL.get().map(|s| s.as_str()), Some("hello"));
+/// ```
+pub struct OnceCell<T> {
+    state: AtomicUsize,
+    // the blocked waiters when the initialization is running
+    to_wake: Mutex<Vec<Arc<Blocker>>>,
+    poison: poison::Flag,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T: Send> Send for OnceCell<T> {}
+unsafe impl<T: Send + Sync> Sync for OnceCell<T> {}
+impl<T: UnwindSafe> UnwindSafe for OnceCell<T> {}
+impl<T: RefUnwindSafe + UnwindSafe> RefUnwindSafe for OnceCell<T> {}
+
+// update the state and wake up all the waiters
+// when the initialization is finished or panicked
+struct InitGuard<'a, T> {
+    cell: &'a OnceCell<T>,
+    poison: poison::Guard,
+    complete: bool,
+}
+
+impl<'a, T> Drop for InitGuard<'a, T> {
+    fn drop(&mut self) {
+        let cell = self.cell;
+        cell.poison.done(&self.poison);
+        let state = if self.complete { COMPLETE } else { INCOMPLETE };
+        cell.state.store(state, Ordering::Release);
+        let to_wake = std::mem::take(&mut *cell.to_wake.lock());
+        for w in to_wake {
+            w.unpark();
+        }
+    }
+}
+
+impl<T> OnceCell<T> {
+    /// Creates a new empty cell.
+    pub const fn new() -> OnceCell<T> {
+        OnceCell {
+            state: AtomicUsize::new(INCOMPLETE),
+            to_wake: Mutex::new(Vec::new()),
+            poison: poison::Flag::new(),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    #[inline]
+    fn is_initialized(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+
+    #[inline]
+    unsafe fn get_unchecked(&self) -> &T {
+        (*self.value.get()).assume_init_ref()
+    }
+
+    /// Gets the reference to the underlying value.
+    ///
+    /// Returns `None` if the cell is empty, or being initialized.
+    pub fn get(&self) -> Option<&T> {
+        if self.is_initialized() {
+            Some(unsafe { self.get_unchecked() })
+        } else {
+            None
+        }
+    }
+
+    /// Gets the mutable reference to the underlying value.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if self.is_initialized() {
+            Some(unsafe { (*self.value.get()).assume_init_mut() })
+        } else {
+            None
+        }
+    }
+
+    /// Sets the contents of this cell to `value`.
+    ///
+    /// Returns `Err(value)` if the cell is already initialized.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        self.get_or_init(|| value.take().unwrap());
+        match value {
+            None => Ok(()),
+            Some(value) => Err(value),
+        }
+    }
+
+    // wait until the running initialization is finished
+    fn wait(&self) {
+        let cur = Blocker::current();
+        {
+            let mut to_wake = self.to_wake.lock();
+            // re-check the state with the lock held
+            if self.state.load(Ordering::Acquire) != RUNNING {
+                return;
+            }
+            to_wake.push(cur.clone());
+        }
+        cur.park(None).ok();
+    }
+
+    /// Gets the contents of the cell, initializing it with `f` if the cell
+    /// was empty.
+    ///
+    /// Many threads and coroutines may call `get_or_init` concurrently with
+    /// different initializing functions, but it is guaranteed that only one
+    /// function will be executed, the others are parked until it's done.
+    ///
+    /// # Panics
+    ///
+    /// If `f` panics, the panic is propagated to the caller, and the cell is
+    /// poisoned, any later call of `get_or_init` would panic.
+    pub fn get_or_init<F>(&self, f: F) -> &T
+    where
+        F: FnOnce() -> T,
+    {
+        if let Some(v) = self.get() {
+            return v;
+        }
+
+        let mut f = Some(f);
+        loop {
+            if self.poison.get() {
+                panic!("OnceCell instance has previously been poisoned");
+            }
+
+            match self.state.compare_exchange(
+                INCOMPLETE,
+                RUNNING,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let poison = match self.poison.borrow() {
+                        Ok(guard) => guard,
+                        Err(err) => err.into_inner(),
+                    };
+                    let mut guard = InitGuard {
+                        cell: self,
+                        poison,
+                        complete: false,
+                    };
+                    let f = f.take().expect("OnceCell init function is lost");
+                    let value = f();
+                    unsafe { (*self.value.get()).write(value) };
+                    guard.complete = true;
+                    drop(guard);
+                    return unsafe { self.get_unchecked() };
+                }
+                Err(COMPLETE) => return unsafe { self.get_unchecked() },
+                Err(_) => self.wait(),
+            }
+        }
+    }
+
+    /// Return true if the initialization panicked
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.get()
+    }
+
+    /// Consumes the cell, returning the wrapped value.
+    pub fn into_inner(mut self) -> Option<T> {
+        self.take()
+    }
+
+    /// Takes the value out of this cell, moving it back to an uninitialized state.
+    pub fn take(&mut self) -> Option<T> {
+        if self.is_initialized() {
+            self.state.store(INCOMPLETE, Ordering::Relaxed);
+            Some(unsafe { (*self.value.get()).assume_init_read() })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T> Default for OnceCell<T> {
+    fn default() -> OnceCell<T> {
+        OnceCell::new()
+    }
+}
+
+impl<T> Drop for OnceCell<T> {
+    fn drop(&mut self) {
+        if self.is_initialized() {
+            unsafe { (*self.value.get()).assume_init_drop() };
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OnceCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.get() {
+            Some(v) => write!(f, "OnceCell {{ data: {v:?} }}"),
+            None => write!(f, "OnceCell {{ <uninit> }}"),
+        }
+    }
+}
+
+/// A value which is initialized on the first access.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::collections::HashMap;
+/// use may::sync::Lazy;
+///
+/// static MAP: Lazy<HashMap<u32, &str>> = Lazy::new(|| {
+///     let mut m = HashMap::new();
+///     m.insert(1, "hello");
+///     m
+/// });
+///
+/// let h = may::go!(|| MAP.get(&1).copied());
+/// assert_eq!(h.join().unwrap(), Some("hello"));
+/// ```
+pub struct Lazy<T, F = fn() -> T> {
+    cell: OnceCell<T>,
+    init: Cell<Option<F>>,
+}
+
+// the init function is only accessed by the one who runs the initialization
+unsafe impl<T: Send + Sync, F: Send> Sync for Lazy<T, F> {}
+impl<T: RefUnwindSafe + UnwindSafe, F: UnwindSafe> RefUnwindSafe for Lazy<T, F> {}
+
+impl<T, F> Lazy<T, F> {
+    /// Creates a new lazy value with the given initializing function.
+    pub const fn new(f: F) -> Lazy<T, F> {
+        Lazy {
+            cell: OnceCell::new(),
+            init: Cell::new(Some(f)),
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+    /// Forces the evaluation of this lazy value and returns a reference to
+    /// the result.
+    ///
+    /// # Panics
+    ///
+    /// If the initialization panicked, any later access would panic.
+    pub fn force(this: &Lazy<T, F>) -> &T {
+        this.cell.get_or_init(|| match this.init.take() {
+            Some(f) => f(),
+            None => panic!("Lazy instance has previously been poisoned"),
+        })
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for Lazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        Lazy::force(self)
+    }
+}
+
+impl<T: Default> Default for Lazy<T> {
+    fn default() -> Lazy<T> {
+        Lazy::new(T::default)
+    }
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for Lazy<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.cell.get() {
+            Some(v) => write!(f, "Lazy {{ data: {v:?} }}"),
+            None => write!(f, "Lazy {{ <uninit> }}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic;
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn smoke() {
+        let cell = OnceCell::new();
+        assert_eq!(cell.get(), None);
+        assert_eq!(*cell.get_or_init(|| 1), 1);
+        assert_eq!(*cell.get_or_init(|| 2), 1);
+        assert_eq!(cell.set(3), Err(3));
+        assert_eq!(cell.into_inner(), Some(1));
+    }
+
+    #[test]
+    fn init_once_with_coroutine_io() {
+        static CNT: AtomicUsize = AtomicUsize::new(0);
+        let cell = Arc::new(OnceCell::new());
+        let mut handles = vec![];
+        for i in 0..10 {
+            let cell = cell.clone();
+            handles.push(go!(move || {
+                *cell.get_or_init(|| {
+                    CNT.fetch_add(1, Ordering::Relaxed);
+                    // the other waiters would be parked
+                    crate::coroutine::sleep(Duration::from_millis(50));
+                    i
+                })
+            }));
+        }
+        let t = {
+            let cell = cell.clone();
+            thread::spawn(move || *cell.get_or_init(|| 100))
+        };
+
+        let v = t.join().unwrap();
+        for h in handles {
+            assert_eq!(h.join().unwrap(), v);
+        }
+        assert_eq!(CNT.load(Ordering::Relaxed), usize::from(v != 100));
+    }
+
+    #[test]
+    fn poison() {
+        let cell = Arc::new(OnceCell::<i32>::new());
+        let c = cell.clone();
+        let h = go!(move || {
+            c.get_or_init(|| panic!("init failed"));
+        });
+        assert!(h.join().is_err());
+        assert!(cell.is_poisoned());
+        assert_eq!(cell.get(), None);
+
+        let c = cell.clone();
+        let h = go!(move || {
+            c.get_or_init(|| 1);
+        });
+        assert!(h.join().is_err());
+    }
+
+    #[test]
+    fn cancel_not_poison() {
+        let cell = Arc::new(OnceCell::<i32>::new());
+        let c = cell.clone();
+        let h = go!(move || {
+            c.get_or_init(|| {
+                crate::coroutine::park();
+                1
+            });
+        });
+        thread::sleep(Duration::from_millis(10));
+        unsafe { h.coroutine().cancel() };
+        assert!(h.join().is_err());
+        assert!(!cell.is_poisoned());
+        assert_eq!(*cell.get_or_init(|| 2), 2);
+    }
+
+    #[test]
+    fn lazy() {
+        static LAZY: Lazy<Vec<i32>> = Lazy::new(|| vec![1, 2, 3]);
+        let h = go!(|| LAZY.len());
+        assert_eq!(h.join().unwrap(), 3);
+        assert_eq!(*LAZY, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn lazy_poison() {
+        let lazy = Arc::new(Lazy::<i32, _>::new(|| panic!("init failed")));
+        let l = lazy.clone();
+        assert!(go!(move || **l).join().is_err());
+        let r = panic::catch_unwind(|| **lazy);
+        assert!(r.is_err());
+    }
+}
@@ -0,0 +1,412 @@
+//! oneshot channel implementation
+//!
+//! a oneshot channel is used to send a single value between a pair of
+//! `Sender` and `Receiver`, it's the light weight version of a `mpsc`
+//! channel for the request/response pattern.
+//! the sender is consumed by `send`, and the receiver would get a
+//! `RecvError` if the sender is dropped without sending any value.
+//! the receiver can be used in `select!` and `cqueue` just like other
+//! channels, it's ok to call `recv` on a shared reference, only one of the
+//! receiving calls would get the value
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::Blocker;
+use crate::select::{SelectRecv, Watch, Watchers};
+
+// the value is ready to be taken
+const DATA: usize = 1;
+// the sender is dropped
+const TX_CLOSED: usize = 2;
+// the receiver is dropped
+const RX_CLOSED: usize = 4;
+
+// /////////////////////////////////////////////////////////////////////////////
+// Inner
+// /////////////////////////////////////////////////////////////////////////////
+struct Inner<T> {
+    // the value that sent by the sender
+    data: UnsafeCell<Option<T>>,
+    // the channel state bits
+    state: AtomicUsize,
+    // the waiting threads/coroutines and selects
+    waiters: Watchers,
+}
+
+unsafe impl<T: Send> Send for Inner<T> {}
+unsafe impl<T: Send> Sync for Inner<T> {}
+
+// remove the waiter even if the park is canceled
+struct WaitGuard<'a> {
+    waiters: &'a Watchers,
+    blocker: Arc<Blocker>,
+}
+
+impl<'a> Drop for WaitGuard<'a> {
+    fn drop(&mut self) {
+        self.waiters.unwatch(&self.blocker);
+    }
+}
+
+impl<T> Inner<T> {
+    fn new() -> Inner<T> {
+        Inner {
+            data: UnsafeCell::new(None),
+            state: AtomicUsize::new(0),
+            waiters: Watchers::new(),
+        }
+    }
+
+    #[inline]
+    fn wake_up(&self) {
+        self.waiters.wakeup();
+    }
+
+    fn send(&self, t: T) -> Result<(), T> {
+        if self.state.load(Ordering::Acquire) & RX_CLOSED != 0 {
+            return Err(t);
+        }
+
+        // only the sender would write the data, and the receiver
+        // would not touch it until the DATA bit is set
+        unsafe { *self.data.get() = Some(t) };
+        if self.state.fetch_or(DATA, Ordering::AcqRel) & RX_CLOSED != 0 {
+            // the receiver is gone before it could see the data
+            let t = unsafe { (*self.data.get()).take() };
+            return Err(t.expect("oneshot data is lost"));
+        }
+
+        self.wake_up();
+        Ok(())
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        let state = self.state.load(Ordering::Acquire);
+        if state & DATA != 0 {
+            // claim the data, only one receive could succeed
+            if self.state.fetch_and(!DATA, Ordering::AcqRel) & DATA != 0 {
+                let t = unsafe { (*self.data.get()).take() };
+                return Ok(t.expect("oneshot data is lost"));
+            }
+            return Err(TryRecvError::Disconnected);
+        }
+
+        if state & TX_CLOSED != 0 {
+            // the sender is dropped or the data is already consumed
+            Err(TryRecvError::Disconnected)
+        } else {
+            Err(TryRecvError::Empty)
+        }
+    }
+
+    fn recv(&self, dur: Option<Duration>) -> Result<T, TryRecvError> {
+        let guard = WaitGuard {
+            waiters: &self.waiters,
+            blocker: Blocker::current(),
+        };
+        // register the waiter
+        self.waiters.watch(&guard.blocker);
+        // re-check the state
+        match self.try_recv() {
+            Err(TryRecvError::Empty) => {
+                guard.blocker.park(dur).ok();
+            }
+            // no need to park, contention with send
+            data => return data,
+        }
+
+        // the guard would remove the waiter when timeout
+        drop(guard);
+        // after come back try recv again
+        self.try_recv()
+    }
+
+    fn drop_tx(&self) {
+        self.state.fetch_or(TX_CLOSED, Ordering::AcqRel);
+        self.wake_up();
+    }
+
+    fn drop_rx(&self) {
+        if self.state.fetch_or(RX_CLOSED, Ordering::AcqRel) & DATA != 0 {
+            // release the data that nobody would receive
+            unsafe { (*self.data.get()).take() };
+        }
+    }
+}
+
+/// The sending half of a oneshot channel
+pub struct Sender<T> {
+    inner: Arc<Inner<T>>,
+}
+
+/// The receiving half of a oneshot channel
+pub struct Receiver<T> {
+    inner: Arc<Inner<T>>,
+}
+
+/// create a oneshot channel
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let a = Arc::new(Inner::new());
+    (Sender { inner: a.clone() }, Receiver { inner: a })
+}
+
+// /////////////////////////////////////////////////////////////////////////////
+// Sender
+// /////////////////////////////////////////////////////////////////////////////
+
+impl<T> Sender<T> {
+    /// send the value to the receiver, this would never block
+    ///
+    /// return the value back if the receiver is already dropped
+    pub fn send(self, t: T) -> Result<(), SendError<T>> {
+        self.inner.send(t).map_err(SendError)
+    }
+
+    /// return true if the receiver is dropped
+    pub fn is_closed(&self) -> bool {
+        self.inner.state.load(Ordering::Acquire) & RX_CLOSED != 0
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.inner.drop_tx();
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Sender {{ .. }}")
+    }
+}
+
+// /////////////////////////////////////////////////////////////////////////////
+// Receiver
+// /////////////////////////////////////////////////////////////////////////////
+
+impl<T> Receiver<T> {
+    /// try to receive the value without blocking
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.inner.try_recv()
+    }
+
+    /// block until the value is received
+    ///
+    /// return `RecvError` if the sender is dropped without sending the value
+    /// or the value is already received
+    pub fn recv(&self) -> Result<T, RecvError> {
+        loop {
+            match self.inner.recv(None) {
+                Err(TryRecvError::Empty) => {}
+                data => return data.map_err(|_| RecvError),
+            }
+        }
+    }
+
+    /// same as `recv` except that with an extra timeout value
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        match self.try_recv() {
+            Ok(data) => return Ok(data),
+            Err(TryRecvError::Empty) => {}
+            Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            match self.inner.recv(Some(deadline - now)) {
+                Ok(t) => return Ok(t),
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+            }
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.inner.drop_rx();
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Receiver {{ .. }}")
+    }
+}
+
+// /////////////////////////////////////////////////////////////////////////////
+// Select
+// /////////////////////////////////////////////////////////////////////////////
+
+impl<T> Watch for Receiver<T> {
+    fn watch(&self, blocker: &Arc<Blocker>) {
+        self.inner.waiters.watch(blocker);
+    }
+
+    fn unwatch(&self, blocker: &Arc<Blocker>) {
+        self.inner.waiters.unwatch(blocker);
+    }
+}
+
+impl<T> SelectRecv for Receiver<T> {
+    type Item = T;
+
+    fn try_select_recv(&self) -> Option<Result<T, RecvError>> {
+        match self.inner.try_recv() {
+            Ok(data) => Some(Ok(data)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(RecvError)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn smoke() {
+        let (tx, rx) = channel::<i32>();
+        tx.send(1).unwrap();
+        assert_eq!(rx.recv().unwrap(), 1);
+        assert_eq!(rx.recv(), Err(RecvError));
+    }
+
+    #[test]
+    fn smoke_threads() {
+        let (tx, rx) = channel::<i32>();
+        let _t = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            tx.send(1).unwrap();
+        });
+        assert_eq!(rx.recv().unwrap(), 1);
+    }
+
+    #[test]
+    fn smoke_coroutine() {
+        let (tx, rx) = channel::<Box<i32>>();
+        let h = go!(move || *rx.recv().unwrap());
+        thread::sleep(Duration::from_millis(10));
+        tx.send(Box::new(10)).unwrap();
+        assert_eq!(h.join().unwrap(), 10);
+    }
+
+    #[test]
+    fn port_gone() {
+        let (tx, rx) = channel::<Box<i32>>();
+        assert!(!tx.is_closed());
+        drop(rx);
+        assert!(tx.is_closed());
+        assert_eq!(tx.send(Box::new(1)), Err(SendError(Box::new(1))));
+    }
+
+    #[test]
+    fn chan_gone() {
+        let (tx, rx) = channel::<i32>();
+        let h = go!(move || rx.recv());
+        thread::sleep(Duration::from_millis(10));
+        drop(tx);
+        assert_eq!(h.join().unwrap(), Err(RecvError));
+    }
+
+    #[test]
+    fn try_recv_states() {
+        let (tx, rx) = channel::<i32>();
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Empty));
+        tx.send(1).unwrap();
+        assert_eq!(rx.try_recv(), Ok(1));
+        assert_eq!(rx.try_recv(), Err(TryRecvError::Disconnected));
+    }
+
+    #[test]
+    fn recv_timeout() {
+        let (tx, rx) = channel::<i32>();
+        assert_eq!(
+            rx.recv_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+        let h = go!(move || rx.recv_timeout(Duration::from_secs(10)));
+        tx.send(1).unwrap();
+        assert_eq!(h.join().unwrap(), Ok(1));
+    }
+
+    #[test]
+    fn send_recv_stress() {
+        for i in 0..1000 {
+            let (tx, rx) = channel::<usize>();
+            let _t = go!(move || {
+                tx.send(i).unwrap();
+            });
+            assert_eq!(rx.recv().unwrap(), i);
+        }
+    }
+
+    #[test]
+    fn select_oneshot() {
+        let (tx1, rx1) = channel::<i32>();
+        let (_tx2, rx2) = channel::<i32>();
+        go!(move || {
+            crate::coroutine::sleep(Duration::from_millis(10));
+            tx1.send(42).unwrap();
+        });
+
+        let v = select! {
+            recv(rx2) -> _ => unreachable!(),
+            recv(rx1) -> v => v,
+        };
+        assert_eq!(v, Ok(42));
+    }
+
+    #[test]
+    fn shared_recv() {
+        let (tx, rx) = channel::<i32>();
+        let rx = Arc::new(rx);
+        let handles: Vec<_> = (0..2)
+            .map(|_| {
+                let rx = rx.clone();
+                go!(move || rx.recv())
+            })
+            .collect();
+        thread::sleep(Duration::from_millis(10));
+        tx.send(1).unwrap();
+        // only one of the receivers would get the value
+        let rets: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(rets.iter().filter(|r| r.is_ok()).count(), 1);
+        assert!(rets.contains(&Ok(1)));
+    }
+
+    #[test]
+    fn cqueue_oneshot() {
+        let (tx1, rx1) = channel::<i32>();
+        let (_tx2, rx2) = channel::<i32>();
+        go!(move || {
+            crate::coroutine::sleep(Duration::from_millis(10));
+            tx1.send(42).unwrap();
+        });
+
+        let id = crate::cqueue::scope(|cqueue| {
+            // the same as `cqueue_add_oneshot!`
+            cqueue.add(0, |es| {
+                let _ = rx2.recv();
+                es.send(es.get_token());
+            });
+            cqueue.add(1, |es| {
+                assert_eq!(rx1.recv(), Ok(42));
+                es.send(es.get_token());
+            });
+            cqueue.poll(None).unwrap().token
+        });
+        assert_eq!(id, 1);
+        // the canceled receiver doesn't leave a waiter behind
+        assert_eq!(rx2.try_recv(), Err(TryRecvError::Empty));
+    }
+}
@@ -1,13 +1,58 @@
+use std::error::Error;
 use std::fmt;
-use std::sync::atomic::{AtomicIsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicIsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 
 use super::blocking::SyncBlocker;
+use super::{Blocker, Mutex};
 use crate::cancel::trigger_cancel_panic;
 use crate::park::ParkError;
+use crate::select::Watchers;
 use crossbeam::queue::SegQueue;
 
+/// Error returned from the `Semphore::acquire` family
+///
+/// the semphore is closed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AcquireError;
+
+impl fmt::Display for AcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        "semphore closed".fmt(f)
+    }
+}
+
+impl Error for AcquireError {}
+
+/// Error returned from the `Semphore::try_acquire` family
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TryAcquireError {
+    /// the semphore is closed
+    Closed,
+
+    /// there is not enough permits available currently
+    NoPermits,
+}
+
+impl fmt::Display for TryAcquireError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TryAcquireError::Closed => "semphore closed".fmt(f),
+            TryAcquireError::NoPermits => "no permits available".fmt(f),
+        }
+    }
+}
+
+impl Error for TryAcquireError {}
+
+// the reasons that a wait is failed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum WaitError {
+    Timeout,
+    Closed,
+}
+
 /// Semphore primitive
 ///
 /// semaphores allow threads and coroutines to synchronize their actions.
@@ -19,6 +64,10 @@ use crossbeam::queue::SegQueue;
 /// zero, then a wait() operation will block until the value becomes
 /// greater than zero.
 ///
+/// The `acquire` family returns a permit guard that would release the
+/// permits back to the semphore when dropped, so that a panic between
+/// acquire and release would not leak the capacity.
+///
 /// # Examples
 ///
 /// ```rust
@@ -38,6 +87,13 @@ use crossbeam::queue::SegQueue;
 ///
 /// // wait for the coroutine to start up
 /// sem.wait();
+///
+/// // hold the permit until the guard is dropped
+/// let sem = Semphore::new(2);
+/// let permit = sem.acquire_many(2).unwrap();
+/// assert_eq!(sem.get_value(), 0);
+/// drop(permit);
+/// assert_eq!(sem.get_value(), 2);
 /// ```
 pub struct Semphore {
     // track how many resources available for the semphore
@@ -45,6 +101,13 @@ pub struct Semphore {
     cnt: AtomicIsize,
     // the waiting blocker list, must be mpmc
     to_wake: SegQueue<Arc<SyncBlocker>>,
+    // serialize the multi permits acquirers, so that they would
+    // not deadlock with each other by holding partial permits
+    many_lock: Mutex<()>,
+    // if the semphore is closed
+    closed: AtomicBool,
+    // the select blockers that would be woken up by `post`
+    watchers: Watchers,
 }
 
 impl Semphore {
@@ -54,6 +117,9 @@ impl Semphore {
         Semphore {
             to_wake: SegQueue::new(),
             cnt: AtomicIsize::new(init as isize),
+            many_lock: Mutex::new(()),
+            closed: AtomicBool::new(false),
+            watchers: Watchers::new(),
         }
     }
 
@@ -70,24 +136,18 @@ impl Semphore {
             .expect("got null blocker!");
     }
 
-    // return false if timeout
-    fn wait_timeout_impl(&self, dur: Option<Duration>) -> bool {
+    fn wait_impl(&self, dur: Option<Duration>) -> Result<(), WaitError> {
         // try wait first
-        if self.try_wait() {
-            return true;
-        }
-
-        let cur = SyncBlocker::current();
-        // register blocker first
-        self.to_wake.push(cur.clone());
-        // dec the cnt, if it's positive, unpark one waiter
-        if self.cnt.fetch_sub(1, Ordering::SeqCst) > 0 {
-            self.wakeup_one();
-        }
+        if !self.try_wait() {
+            let cur = SyncBlocker::current();
+            // register blocker first
+            self.to_wake.push(cur.clone());
+            // dec the cnt, if it's positive, unpark one waiter
+            if self.cnt.fetch_sub(1, Ordering::SeqCst) > 0 {
+                self.wakeup_one();
+            }
 
-        match cur.park(dur) {
-            Ok(_) => true,
-            Err(err) => {
+            if let Err(err) = cur.park(dur) {
                 // check the unpark status
                 if cur.is_unparked() {
                     self.post();
@@ -104,9 +164,21 @@ impl Semphore {
                 if err == ParkError::Canceled {
                     trigger_cancel_panic();
                 }
-                false
+                return Err(WaitError::Timeout);
             }
         }
+
+        if self.closed.load(Ordering::SeqCst) {
+            // give back the resource so that others could see the close
+            self.post();
+            return Err(WaitError::Closed);
+        }
+        Ok(())
+    }
+
+    // return false if timeout
+    fn wait_timeout_impl(&self, dur: Option<Duration>) -> bool {
+        self.wait_impl(dur) != Err(WaitError::Timeout)
     }
 
     /// wait for a semphore
@@ -125,13 +197,19 @@ impl Semphore {
     /// return false if would block
     /// return true if successfully acquire one semphore resource
     pub fn try_wait(&self) -> bool {
+        self.try_wait_many(1)
+    }
+
+    // try to take n resources at once without blocking
+    fn try_wait_many(&self, n: usize) -> bool {
         // we not register ourself at all
         // just manipulate the cnt is enough
+        let n = n as isize;
         let mut cnt = self.cnt.load(Ordering::SeqCst);
-        while cnt > 0 {
+        while cnt >= n {
             match self
                 .cnt
-                .compare_exchange(cnt, cnt - 1, Ordering::SeqCst, Ordering::SeqCst)
+                .compare_exchange(cnt, cnt - n, Ordering::SeqCst, Ordering::SeqCst)
             {
                 Ok(_) => return true,
                 Err(x) => cnt = x,
@@ -150,6 +228,26 @@ impl Semphore {
         if cnt < 0 {
             self.wakeup_one();
         }
+
+        self.wakeup_watchers();
+    }
+
+    /// register a select blocker that would be unparked by the next `post`
+    ///
+    /// the watcher doesn't consume any resource, it should re-check the
+    /// semphore by `try_wait` after registered
+    pub(crate) fn watch(&self, blocker: &Arc<Blocker>) {
+        self.watchers.watch(blocker);
+    }
+
+    /// remove the registered select blocker
+    pub(crate) fn unwatch(&self, blocker: &Arc<Blocker>) {
+        self.watchers.unwatch(blocker);
+    }
+
+    /// unpark all the registered select blockers
+    pub(crate) fn wakeup_watchers(&self) {
+        self.watchers.wakeup();
     }
 
     /// return the current semphore value
@@ -160,6 +258,144 @@ impl Semphore {
         }
         0
     }
+
+    /// add `n` permits to the semphore, would wakeup the waiters if any
+    pub fn add_permits(&self, n: usize) {
+        for _ in 0..n {
+            self.post();
+        }
+    }
+
+    /// close the semphore
+    ///
+    /// all the waiters of the `acquire` family are woken up with an error,
+    /// and the following acquires would fail immediately. the `wait` family
+    /// would return immediately after the semphore is closed.
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        // wakeup all the waiters, and left at least one resource
+        // so that the late comers would not block
+        while self.cnt.load(Ordering::SeqCst) <= 0 {
+            self.post();
+        }
+    }
+
+    /// return true if the semphore is closed
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    // take n resources, the partial resources are given back when failed
+    fn acquire_many_impl(&self, n: usize) -> Result<(), AcquireError> {
+        assert!(n < isize::MAX as usize);
+        if self.is_closed() {
+            return Err(AcquireError);
+        }
+        if self.try_wait_many(n) {
+            return Ok(());
+        }
+
+        // give back the partial resources when panic
+        struct Partial<'a>(&'a Semphore, usize);
+        impl<'a> Drop for Partial<'a> {
+            fn drop(&mut self) {
+                self.0.add_permits(self.1);
+            }
+        }
+
+        // the waiters queue is fifo, so the large request would take
+        // the resources one by one without being starved by the small ones
+        let _g = self.many_lock.lock();
+        let mut partial = Partial(self, 0);
+        while partial.1 < n {
+            match self.wait_impl(None) {
+                Ok(()) => partial.1 += 1,
+                Err(_) => return Err(AcquireError),
+            }
+        }
+        std::mem::forget(partial);
+        Ok(())
+    }
+
+    /// acquire one permit from the semphore
+    ///
+    /// the permit is released when the returned guard is dropped,
+    /// return `AcquireError` if the semphore is closed
+    pub fn acquire(&self) -> Result<SemphorePermit<'_>, AcquireError> {
+        self.acquire_many(1)
+    }
+
+    /// acquire `n` permits from the semphore at once
+    ///
+    /// the requests are served in order, so a large request would not be
+    /// starved by the small ones
+    pub fn acquire_many(&self, n: usize) -> Result<SemphorePermit<'_>, AcquireError> {
+        self.acquire_many_impl(n)?;
+        Ok(SemphorePermit {
+            sem: self,
+            permits: n,
+        })
+    }
+
+    /// try to acquire one permit without blocking
+    pub fn try_acquire(&self) -> Result<SemphorePermit<'_>, TryAcquireError> {
+        self.try_acquire_many(1)
+    }
+
+    /// try to acquire `n` permits at once without blocking
+    pub fn try_acquire_many(&self, n: usize) -> Result<SemphorePermit<'_>, TryAcquireError> {
+        if self.is_closed() {
+            return Err(TryAcquireError::Closed);
+        }
+        if !self.try_wait_many(n) {
+            return Err(TryAcquireError::NoPermits);
+        }
+        Ok(SemphorePermit {
+            sem: self,
+            permits: n,
+        })
+    }
+
+    /// same as `acquire` except that the permit holds an `Arc` of the
+    /// semphore, so that it can be moved into a spawned coroutine
+    pub fn acquire_owned(self: Arc<Self>) -> Result<OwnedSemphorePermit, AcquireError> {
+        self.acquire_many_owned(1)
+    }
+
+    /// same as `acquire_many` except that the permit holds an `Arc` of the
+    /// semphore, so that it can be moved into a spawned coroutine
+    pub fn acquire_many_owned(
+        self: Arc<Self>,
+        n: usize,
+    ) -> Result<OwnedSemphorePermit, AcquireError> {
+        self.acquire_many_impl(n)?;
+        Ok(OwnedSemphorePermit {
+            sem: self,
+            permits: n,
+        })
+    }
+
+    /// same as `try_acquire` except that the permit holds an `Arc` of the semphore
+    pub fn try_acquire_owned(self: Arc<Self>) -> Result<OwnedSemphorePermit, TryAcquireError> {
+        self.try_acquire_many_owned(1)
+    }
+
+    /// same as `try_acquire_many` except that the permit holds an `Arc` of the semphore
+    pub fn try_acquire_many_owned(
+        self: Arc<Self>,
+        n: usize,
+    ) -> Result<OwnedSemphorePermit, TryAcquireError> {
+        if self.is_closed() {
+            return Err(TryAcquireError::Closed);
+        }
+        if !self.try_wait_many(n) {
+            return Err(TryAcquireError::NoPermits);
+        }
+        Ok(OwnedSemphorePermit {
+            sem: self,
+            permits: n,
+        })
+    }
 }
 
 impl fmt::Debug for Semphore {
@@ -169,6 +405,81 @@ impl fmt::Debug for Semphore {
     }
 }
 
+/// A permit guard of the semphore
+///
+/// the permits are released back to the semphore when the guard is dropped
+#[must_use]
+pub struct SemphorePermit<'a> {
+    sem: &'a Semphore,
+    permits: usize,
+}
+
+impl<'a> SemphorePermit<'a> {
+    /// return the number of permits held by the guard
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+
+    /// forget the permits without releasing them back to the semphore
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+}
+
+impl<'a> Drop for SemphorePermit<'a> {
+    fn drop(&mut self) {
+        self.sem.add_permits(self.permits);
+    }
+}
+
+impl<'a> fmt::Debug for SemphorePermit<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SemphorePermit")
+            .field("permits", &self.permits)
+            .finish()
+    }
+}
+
+/// An owned permit guard of the semphore
+///
+/// the permits are released back to the semphore when the guard is dropped
+#[must_use]
+pub struct OwnedSemphorePermit {
+    sem: Arc<Semphore>,
+    permits: usize,
+}
+
+impl OwnedSemphorePermit {
+    /// return the number of permits held by the guard
+    pub fn num_permits(&self) -> usize {
+        self.permits
+    }
+
+    /// forget the permits without releasing them back to the semphore
+    pub fn forget(mut self) {
+        self.permits = 0;
+    }
+
+    /// return the semphore that the permits belong to
+    pub fn semphore(&self) -> &Arc<Semphore> {
+        &self.sem
+    }
+}
+
+impl Drop for OwnedSemphorePermit {
+    fn drop(&mut self) {
+        self.sem.add_permits(self.permits);
+    }
+}
+
+impl fmt::Debug for OwnedSemphorePermit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OwnedSemphorePermit")
+            .field("permits", &self.permits)
+            .finish()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -311,4 +622,118 @@ mod tests {
         sem1.post();
         h2.join().unwrap();
     }
+
+    #[test]
+    fn test_permit_guard() {
+        let sem = Arc::new(Semphore::new(2));
+        let p = sem.acquire().unwrap();
+        assert_eq!(p.num_permits(), 1);
+        assert_eq!(sem.get_value(), 1);
+
+        // the permit is released when the coroutine panics
+        let p1 = sem.clone().acquire_owned().unwrap();
+        let h = go!(move || {
+            let _p = p1;
+            panic!("panic with permit");
+        });
+        h.join().unwrap_err();
+        assert_eq!(sem.get_value(), 1);
+
+        drop(p);
+        assert_eq!(sem.get_value(), 2);
+        sem.acquire().unwrap().forget();
+        assert_eq!(sem.get_value(), 1);
+    }
+
+    #[test]
+    fn test_try_acquire() {
+        let sem = Semphore::new(2);
+        assert_eq!(
+            sem.try_acquire_many(3).unwrap_err(),
+            TryAcquireError::NoPermits
+        );
+        let p = sem.try_acquire_many(2).unwrap();
+        assert_eq!(sem.try_acquire().unwrap_err(), TryAcquireError::NoPermits);
+        drop(p);
+        sem.add_permits(1);
+        assert_eq!(sem.get_value(), 3);
+    }
+
+    #[test]
+    fn test_acquire_many_not_starved() {
+        let sem = Arc::new(Semphore::new(3));
+        let (tx, rx) = channel();
+
+        // hold all the permits
+        let p = sem.clone().acquire_many_owned(3).unwrap();
+
+        let s = sem.clone();
+        let tx1 = tx.clone();
+        let big = go!(move || {
+            let _p = s.acquire_many(3).unwrap();
+            tx1.send(3).unwrap();
+        });
+        sleep_ms(20);
+
+        // the small requests come later
+        let mut small = vec![];
+        for _ in 0..10 {
+            let s = sem.clone();
+            let tx = tx.clone();
+            small.push(go!(move || {
+                let _p = s.acquire().unwrap();
+                tx.send(1).unwrap();
+            }));
+        }
+        sleep_ms(20);
+
+        drop(p);
+        // the big request is served first
+        assert_eq!(rx.recv().unwrap(), 3);
+        big.join().unwrap();
+        for h in small {
+            h.join().unwrap();
+        }
+        assert_eq!(sem.get_value(), 3);
+    }
+
+    #[test]
+    fn test_acquire_many_canceled() {
+        let sem = Arc::new(Semphore::new(2));
+        let s = sem.clone();
+        let h = go!(move || {
+            let _p = s.acquire_many(3).unwrap();
+        });
+        sleep_ms(20);
+        unsafe { h.coroutine().cancel() };
+        h.join().unwrap_err();
+        // the partial permits are given back
+        assert_eq!(sem.get_value(), 2);
+    }
+
+    #[test]
+    fn test_close() {
+        let sem = Arc::new(Semphore::new(0));
+        let mut handles = vec![];
+        for i in 0..5 {
+            let s = sem.clone();
+            handles.push(go!(move || s.acquire_many(i + 1).map(|_| ())));
+        }
+        let s = sem.clone();
+        let t = thread::spawn(move || s.acquire_owned().map(|_| ()));
+        sleep_ms(20);
+
+        sem.close();
+        assert!(sem.is_closed());
+        for h in handles {
+            assert_eq!(h.join().unwrap(), Err(AcquireError));
+        }
+        assert_eq!(t.join().unwrap(), Err(AcquireError));
+        assert_eq!(sem.acquire().unwrap_err(), AcquireError);
+        assert_eq!(sem.try_acquire().unwrap_err(), TryAcquireError::Closed);
+    }
+
+    fn sleep_ms(ms: u64) {
+        thread::sleep(Duration::from_millis(ms));
+    }
 }
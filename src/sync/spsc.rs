@@ -9,8 +9,9 @@ use std::thread::Thread;
 
 use super::AtomicOption;
 use crate::coroutine_impl::{is_coroutine, run_coroutine, CoroutineImpl, EventSource};
+use crate::dump::{set_state, CoroutineState};
 use crate::likely::{likely, unlikely};
-use crate::scheduler::get_scheduler;
+use crate::scheduler::schedule;
 use crate::yield_now::{yield_now, yield_with};
 
 use may_queue::spsc::Queue;
@@ -106,7 +107,7 @@ impl Blocker {
     fn unpark(self) {
         if (self.handle.get() & 1) == 0 {
             let co = self.into_coroutine();
-            get_scheduler().schedule(co);
+            schedule(co);
         } else {
             let thread = self.into_thread();
             thread.unpark();
@@ -164,6 +165,7 @@ impl<T> InnerQueue<T> {
             Err(TryRecvError::Empty) => {
                 if is_coroutine() {
                     let park = Park::new(self);
+                    set_state(CoroutineState::ParkedOnLock);
                     yield_with(&park);
                 } else {
                     let blocker = Blocker::new_thread(std::thread::current());
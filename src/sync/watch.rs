@@ -0,0 +1,408 @@
+//! watch channel implementation
+//!
+//! a single-producer, multi-consumer channel that only keeps the latest
+//! value. it's useful to propagate configuration changes to many
+//! coroutines where only the newest value matters.
+//!
+//! each published value bumps the channel version, a receiver remembers
+//! the version it has seen, and `changed` would block until a newer version
+//! is published. the current value is accessed by `borrow` which returns a
+//! read guard of the underlying `RwLock`
+use std::error::Error;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::SendError;
+use std::sync::{Arc, PoisonError};
+use std::time::{Duration, Instant};
+
+use super::{Blocker, RwLock, RwLockReadGuard};
+use parking_lot::Mutex;
+
+/// An error returned from the `changed` function on a watch `Receiver`
+///
+/// the sender is dropped and there will be no new value any more
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RecvError;
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        "channel closed".fmt(f)
+    }
+}
+
+impl Error for RecvError {}
+
+/// An error returned from the `changed_timeout` function on a watch `Receiver`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RecvTimeoutError {
+    /// there is no new value published before the timeout
+    Timeout,
+
+    /// the sender is dropped and there will be no new value any more
+    Closed,
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RecvTimeoutError::Timeout => "timed out waiting on channel".fmt(f),
+            RecvTimeoutError::Closed => "channel closed".fmt(f),
+        }
+    }
+}
+
+impl Error for RecvTimeoutError {}
+
+// /////////////////////////////////////////////////////////////////////////////
+// Shared
+// /////////////////////////////////////////////////////////////////////////////
+struct Shared<T> {
+    // the latest value
+    value: RwLock<T>,
+    // the version of the latest value
+    version: AtomicUsize,
+    // the blocked receivers
+    to_wake: Mutex<Vec<Arc<Blocker>>>,
+    // The number of rx channels which are currently using this channel
+    rx_ports: AtomicUsize,
+    // if the sender is dropped
+    tx_closed: AtomicBool,
+}
+
+impl<T> Shared<T> {
+    // bump the version and wake up all the blocked receivers
+    fn notify(&self) {
+        // the version must be updated before take the waiters
+        self.version.fetch_add(1, Ordering::AcqRel);
+        let to_wake = std::mem::take(&mut *self.to_wake.lock());
+        for w in to_wake {
+            w.unpark();
+        }
+    }
+}
+
+/// A read guard of the watched value
+///
+/// holding the guard would block the sender from publishing new values,
+/// so don't hold it for too long
+pub struct Ref<'a, T> {
+    guard: RwLockReadGuard<'a, T>,
+}
+
+impl<'a, T> Deref for Ref<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<'a, T: fmt::Debug> fmt::Debug for Ref<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+/// The sending half of a watch channel
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// The receiving half of a watch channel
+pub struct Receiver<T> {
+    shared: Arc<Shared<T>>,
+    // the version that the receiver has seen
+    version: usize,
+}
+
+/// create a watch channel with the initial value
+///
+/// the initial value is treated as seen by the returned receiver
+pub fn channel<T>(init: T) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Shared {
+        value: RwLock::new(init),
+        version: AtomicUsize::new(0),
+        to_wake: Mutex::new(Vec::new()),
+        rx_ports: AtomicUsize::new(1),
+        tx_closed: AtomicBool::new(false),
+    });
+    let rx = Receiver {
+        shared: shared.clone(),
+        version: 0,
+    };
+    (Sender { shared }, rx)
+}
+
+// /////////////////////////////////////////////////////////////////////////////
+// Sender
+// /////////////////////////////////////////////////////////////////////////////
+
+impl<T> Sender<T> {
+    /// publish a new value and notify all the receivers
+    ///
+    /// return the value back if there is no active receiver
+    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        if self.shared.rx_ports.load(Ordering::Acquire) == 0 {
+            return Err(SendError(t));
+        }
+        self.send_modify(|v| *v = t);
+        Ok(())
+    }
+
+    /// modify the value in place and notify all the receivers
+    ///
+    /// the value is modified even if there is no active receiver
+    pub fn send_modify<F: FnOnce(&mut T)>(&self, f: F) {
+        {
+            let mut v = self
+                .shared
+                .value
+                .write()
+                .unwrap_or_else(PoisonError::into_inner);
+            f(&mut v);
+        }
+        self.shared.notify();
+    }
+
+    /// get a read guard of the latest value
+    pub fn borrow(&self) -> Ref<'_, T> {
+        let guard = self
+            .shared
+            .value
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        Ref { guard }
+    }
+
+    /// create a new receiver that has seen the current value
+    pub fn subscribe(&self) -> Receiver<T> {
+        self.shared.rx_ports.fetch_add(1, Ordering::AcqRel);
+        Receiver {
+            shared: self.shared.clone(),
+            version: self.shared.version.load(Ordering::Acquire),
+        }
+    }
+
+    /// return the number of active receivers
+    pub fn receiver_count(&self) -> usize {
+        self.shared.rx_ports.load(Ordering::Acquire)
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.shared.tx_closed.store(true, Ordering::Release);
+        // wake up all the receivers to see the close
+        self.shared.notify();
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Sender {{ .. }}")
+    }
+}
+
+// /////////////////////////////////////////////////////////////////////////////
+// Receiver
+// /////////////////////////////////////////////////////////////////////////////
+
+impl<T> Receiver<T> {
+    /// get a read guard of the latest value without marking it as seen
+    pub fn borrow(&self) -> Ref<'_, T> {
+        let guard = self
+            .shared
+            .value
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        Ref { guard }
+    }
+
+    /// get a read guard of the latest value and mark it as seen
+    pub fn borrow_and_update(&mut self) -> Ref<'_, T> {
+        let guard = self
+            .shared
+            .value
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        // the version can't change when we hold the read guard
+        self.version = self.shared.version.load(Ordering::Acquire);
+        Ref { guard }
+    }
+
+    /// return true if there is a new value that is not seen yet
+    ///
+    /// return `RecvError` if the sender is dropped
+    pub fn has_changed(&self) -> Result<bool, RecvError> {
+        if self.shared.tx_closed.load(Ordering::Acquire) {
+            return Err(RecvError);
+        }
+        Ok(self.shared.version.load(Ordering::Acquire) != self.version)
+    }
+
+    // return true if a new version is seen
+    fn check_changed(&mut self) -> Result<bool, RecvError> {
+        if self.shared.tx_closed.load(Ordering::Acquire) {
+            return Err(RecvError);
+        }
+        let version = self.shared.version.load(Ordering::Acquire);
+        if version != self.version {
+            self.version = version;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn changed_impl(&mut self, dur: Option<Duration>) -> Result<(), RecvTimeoutError> {
+        let deadline = dur.map(|d| Instant::now() + d);
+        loop {
+            if self.check_changed().map_err(|_| RecvTimeoutError::Closed)? {
+                return Ok(());
+            }
+
+            let cur = Blocker::current();
+            // register the waiter
+            self.shared.to_wake.lock().push(cur.clone());
+            // re-check the version
+            if self.check_changed().map_err(|_| RecvTimeoutError::Closed)? {
+                return Ok(());
+            }
+
+            let timeout = match deadline {
+                None => None,
+                Some(d) => {
+                    let now = Instant::now();
+                    if now >= d {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+                    Some(d - now)
+                }
+            };
+            cur.park(timeout).ok();
+        }
+    }
+
+    /// block until a new value is published, and mark it as seen
+    ///
+    /// return `RecvError` if the sender is dropped
+    pub fn changed(&mut self) -> Result<(), RecvError> {
+        self.changed_impl(None).map_err(|_| RecvError)
+    }
+
+    /// same as `changed` except that with an extra timeout value
+    pub fn changed_timeout(&mut self, timeout: Duration) -> Result<(), RecvTimeoutError> {
+        self.changed_impl(Some(timeout))
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    /// the new receiver has seen the same version
+    fn clone(&self) -> Receiver<T> {
+        self.shared.rx_ports.fetch_add(1, Ordering::AcqRel);
+        Receiver {
+            shared: self.shared.clone(),
+            version: self.version,
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.rx_ports.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Receiver {{ .. }}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn smoke() {
+        let (tx, mut rx) = channel(1);
+        assert_eq!(*rx.borrow(), 1);
+        assert_eq!(rx.has_changed(), Ok(false));
+        tx.send(2).unwrap();
+        assert_eq!(rx.has_changed(), Ok(true));
+        rx.changed().unwrap();
+        assert_eq!(*rx.borrow(), 2);
+        assert_eq!(rx.has_changed(), Ok(false));
+    }
+
+    #[test]
+    fn send_modify() {
+        let (tx, mut rx) = channel(vec![1]);
+        tx.send_modify(|v| v.push(2));
+        assert_eq!(*tx.borrow(), vec![1, 2]);
+        assert_eq!(*rx.borrow_and_update(), vec![1, 2]);
+        assert_eq!(rx.has_changed(), Ok(false));
+    }
+
+    #[test]
+    fn only_latest_value() {
+        let (tx, mut rx) = channel(0);
+        for i in 1..10 {
+            tx.send(i).unwrap();
+        }
+        rx.changed().unwrap();
+        assert_eq!(*rx.borrow(), 9);
+        assert_eq!(
+            rx.changed_timeout(Duration::from_millis(10)),
+            Err(RecvTimeoutError::Timeout)
+        );
+    }
+
+    #[test]
+    fn many_receivers() {
+        let (tx, rx) = channel(0);
+        let mut handles = vec![];
+        for _ in 0..100 {
+            let mut rx = rx.clone();
+            handles.push(go!(move || {
+                while rx.changed().is_ok() {
+                    if *rx.borrow() == 10 {
+                        return true;
+                    }
+                }
+                false
+            }));
+        }
+        drop(rx);
+
+        thread::sleep(Duration::from_millis(10));
+        for i in 1..=10 {
+            tx.send(i).unwrap();
+        }
+        for h in handles {
+            assert!(h.join().unwrap());
+        }
+    }
+
+    #[test]
+    fn sender_drop() {
+        let (tx, mut rx) = channel(0);
+        let h = go!(move || rx.changed());
+        thread::sleep(Duration::from_millis(10));
+        drop(tx);
+        assert_eq!(h.join().unwrap(), Err(RecvError));
+    }
+
+    #[test]
+    fn no_receiver() {
+        let (tx, rx) = channel(0);
+        drop(rx);
+        assert_eq!(tx.send(1), Err(SendError(1)));
+        let mut rx = tx.subscribe();
+        assert_eq!(tx.receiver_count(), 1);
+        tx.send(2).unwrap();
+        rx.changed().unwrap();
+        assert_eq!(*rx.borrow(), 2);
+    }
+}
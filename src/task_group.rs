@@ -0,0 +1,348 @@
+//! structured coroutine group
+//!
+//! a `TaskGroup` owns the coroutines spawned by it, the results are
+//! collected in completion order by `join_next`. once a member panics all
+//! the other running members are canceled, and when the group is dropped
+//! the remaining members are canceled and waited.
+use std::any::Any;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crate::coroutine_impl::spawn;
+use crate::join::JoinHandle;
+use crate::sync::mpsc::{channel, Receiver, Sender};
+use parking_lot::Mutex;
+
+/// The error returned by `TaskGroup::join_all`
+///
+/// it contains all the panics of the failed members
+pub struct GroupError {
+    panics: Vec<Box<dyn Any + Send>>,
+    canceled: usize,
+}
+
+impl GroupError {
+    /// return the panic payloads of the members, canceled members are not included
+    pub fn panics(&self) -> &[Box<dyn Any + Send>] {
+        &self.panics
+    }
+
+    /// consume the error and return the panic payloads
+    pub fn into_panics(self) -> Vec<Box<dyn Any + Send>> {
+        self.panics
+    }
+
+    /// return the number of members that are canceled
+    pub fn canceled(&self) -> usize {
+        self.canceled
+    }
+}
+
+impl fmt::Debug for GroupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GroupError")
+            .field("panics", &self.panics.len())
+            .field("canceled", &self.canceled)
+            .finish()
+    }
+}
+
+impl fmt::Display for GroupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} coroutines panicked, {} coroutines canceled",
+            self.panics.len(),
+            self.canceled
+        )
+    }
+}
+
+impl Error for GroupError {}
+
+// return true if the panic is caused by the coroutine cancel
+fn is_cancel(e: &(dyn Any + Send)) -> bool {
+    e.downcast_ref::<generator::Error>() == Some(&generator::Error::Cancel)
+}
+
+struct Tasks<T> {
+    // the members that are not joined yet
+    handles: HashMap<usize, JoinHandle<T>>,
+    // each member would send its id when exit
+    tx: Sender<usize>,
+    // id for the next member
+    next_id: usize,
+}
+
+struct Shared<T> {
+    tasks: Mutex<Tasks<T>>,
+    // set when any member failed
+    failed: AtomicBool,
+}
+
+impl<T> Shared<T> {
+    // cancel all the running members except the current one
+    fn cancel_others(&self, cur: Option<usize>) {
+        let tasks = self.tasks.lock();
+        for (id, h) in tasks.handles.iter() {
+            if Some(*id) != cur && !h.is_done() {
+                unsafe { h.coroutine().cancel() };
+            }
+        }
+    }
+}
+
+// tell the group that the member is done, even if it panics
+struct Member<T> {
+    id: usize,
+    shared: Arc<Shared<T>>,
+    tx: Sender<usize>,
+}
+
+impl<T> Drop for Member<T> {
+    fn drop(&mut self) {
+        if thread::panicking() && !self.shared.failed.swap(true, Ordering::AcqRel) {
+            // the first failed member cancel all the others
+            self.shared.cancel_others(Some(self.id));
+        }
+        self.tx.send(self.id).ok();
+    }
+}
+
+/// A group of coroutines that are joined together
+///
+/// # Examples
+///
+/// ```rust
+/// use may::coroutine::TaskGroup;
+///
+/// let mut group = TaskGroup::new();
+/// for i in 0..10 {
+///     may::go!(&group, move || i * 2);
+/// }
+///
+/// let mut sum = 0;
+/// while let Some(ret) = group.join_next() {
+///     sum += ret.unwrap();
+/// }
+/// assert_eq!(sum, 90);
+/// ```
+pub struct TaskGroup<T> {
+    shared: Arc<Shared<T>>,
+    rx: Receiver<usize>,
+}
+
+impl<T: Send + 'static> TaskGroup<T> {
+    /// create an empty group
+    pub fn new() -> Self {
+        let (tx, rx) = channel();
+        let tasks = Tasks {
+            handles: HashMap::new(),
+            tx,
+            next_id: 0,
+        };
+        TaskGroup {
+            shared: Arc::new(Shared {
+                tasks: Mutex::new(tasks),
+                failed: AtomicBool::new(false),
+            }),
+            rx,
+        }
+    }
+
+    /// spawn a coroutine as a member of the group
+    ///
+    /// # Safety
+    ///
+    /// the same as [`coroutine::spawn`], besides the coroutine could be
+    /// canceled by the group when any other member panics or the group is
+    /// dropped
+    ///
+    /// [`coroutine::spawn`]: fn.spawn.html
+    pub unsafe fn spawn<F>(&self, f: F)
+    where
+        F: FnOnce() -> T + Send + 'static,
+    {
+        // hold the lock so that the member can't see an incomplete map
+        let mut tasks = self.shared.tasks.lock();
+        let id = tasks.next_id;
+        tasks.next_id += 1;
+        let member = Member {
+            id,
+            shared: self.shared.clone(),
+            tx: tasks.tx.clone(),
+        };
+        let h = spawn(move || {
+            let _member = member;
+            f()
+        });
+        if self.shared.failed.load(Ordering::Acquire) {
+            // the group is already failed, don't let the new member run
+            h.coroutine().cancel();
+        }
+        tasks.handles.insert(id, h);
+    }
+}
+
+impl<T> TaskGroup<T> {
+    /// return the number of members that are not joined yet
+    pub fn len(&self) -> usize {
+        self.shared.tasks.lock().handles.len()
+    }
+
+    /// return true if there is no member to join
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// cancel all the running members
+    ///
+    /// the canceled members are still needed to be joined
+    pub fn cancel_all(&self) {
+        self.shared.cancel_others(None);
+    }
+
+    /// wait for the next finished member and return its result
+    ///
+    /// return None if there is no member in the group. if a member panics
+    /// all the other running members are canceled
+    pub fn join_next(&mut self) -> Option<thread::Result<T>> {
+        if self.is_empty() {
+            return None;
+        }
+        // the group holds a sender, the channel would never disconnect
+        let id = self.rx.recv().expect("task group channel closed");
+        let h = self.shared.tasks.lock().handles.remove(&id);
+        Some(h.expect("unknown task group member").join())
+    }
+
+    /// join all the members and return the results in completion order
+    ///
+    /// return `GroupError` that contains all the panics if any member failed
+    pub fn join_all(mut self) -> Result<Vec<T>, GroupError> {
+        let mut rets = Vec::new();
+        let mut panics = Vec::new();
+        let mut canceled = 0;
+        while let Some(ret) = self.join_next() {
+            match ret {
+                Ok(v) => rets.push(v),
+                Err(e) if is_cancel(&*e) => canceled += 1,
+                Err(e) => panics.push(e),
+            }
+        }
+
+        if panics.is_empty() && canceled == 0 {
+            Ok(rets)
+        } else {
+            Err(GroupError { panics, canceled })
+        }
+    }
+}
+
+impl<T: Send + 'static> Default for TaskGroup<T> {
+    fn default() -> Self {
+        TaskGroup::new()
+    }
+}
+
+impl<T> Drop for TaskGroup<T> {
+    fn drop(&mut self) {
+        // cancel the remaining members and wait for them
+        self.cancel_all();
+        let handles = std::mem::take(&mut self.shared.tasks.lock().handles);
+        for (_, h) in handles {
+            h.join().ok();
+        }
+    }
+}
+
+impl<T> fmt::Debug for TaskGroup<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TaskGroup {{ len: {} }}", self.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coroutine::sleep;
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    #[test]
+    fn join_in_completion_order() {
+        let mut group = TaskGroup::new();
+        for i in (0..5).rev() {
+            go!(&group, move || {
+                sleep(Duration::from_millis(i * 20));
+                i
+            });
+        }
+        assert_eq!(group.len(), 5);
+
+        let mut rets = vec![];
+        while let Some(ret) = group.join_next() {
+            rets.push(ret.unwrap());
+        }
+        assert_eq!(rets, vec![0, 1, 2, 3, 4]);
+        assert!(group.join_next().is_none());
+    }
+
+    #[test]
+    fn panic_cancel_others() {
+        let group = TaskGroup::new();
+        let done = Arc::new(AtomicUsize::new(0));
+        for _ in 0..10 {
+            let done = done.clone();
+            go!(&group, move || {
+                sleep(Duration::from_secs(10));
+                done.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        go!(&group, || {
+            sleep(Duration::from_millis(10));
+            panic!("member failed");
+        });
+
+        let err = group.join_all().unwrap_err();
+        assert_eq!(err.panics().len(), 1);
+        assert_eq!(err.canceled(), 10);
+        assert_eq!(
+            err.panics()[0].downcast_ref::<&str>(),
+            Some(&"member failed")
+        );
+        assert_eq!(done.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn drop_cancel_members() {
+        let done = Arc::new(AtomicUsize::new(0));
+        {
+            let group = TaskGroup::new();
+            for _ in 0..10 {
+                let done = done.clone();
+                go!(&group, move || {
+                    sleep(Duration::from_secs(10));
+                    done.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+            sleep(Duration::from_millis(10));
+        }
+        assert_eq!(done.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn join_all_ok() {
+        let group = TaskGroup::new();
+        for i in 0..100 {
+            go!(&group, move || i);
+        }
+        let mut rets = group.join_all().unwrap();
+        rets.sort();
+        assert_eq!(rets, (0..100).collect::<Vec<_>>());
+    }
+}
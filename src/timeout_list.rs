@@ -1,7 +1,7 @@
 use std::cmp;
 use std::collections::{BinaryHeap, HashMap};
 use std::mem;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
@@ -269,6 +269,10 @@ pub struct TimerThread<T> {
     remove_list: Queue<TimeoutHandle<T>>,
     // the timer thread wakeup handler
     wakeup: AtomicOption<thread::Thread>,
+    // set to let the timer thread exit
+    stop: AtomicBool,
+    // number of the timers that are not expired or removed
+    pending: AtomicUsize,
 }
 
 impl<T> TimerThread<T> {
@@ -277,10 +281,26 @@ impl<T> TimerThread<T> {
             timer_list: TimeOutList::new(),
             remove_list: Queue::new(),
             wakeup: AtomicOption::none(),
+            stop: AtomicBool::new(false),
+            pending: AtomicUsize::new(0),
         }
     }
 
+    // let the timer thread exit, the pending timers are discarded
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(t) = self.wakeup.take() {
+            t.unpark();
+        }
+    }
+
+    /// return the number of pending timers
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::Relaxed)
+    }
+
     pub fn add_timer(&self, dur: Duration, data: T) -> TimeoutHandle<T> {
+        self.pending.fetch_add(1, Ordering::Relaxed);
         let (h, is_recal) = self.timer_list.add_timer(dur, data);
         // wake up the timer thread if it's a new queue
         if is_recal {
@@ -301,21 +321,33 @@ impl<T> TimerThread<T> {
     // the timer thread function
     pub fn run<F: Fn(T)>(&self, f: &F) {
         let current_thread = thread::current();
+        let f = |data: T| {
+            self.pending.fetch_sub(1, Ordering::Relaxed);
+            f(data)
+        };
         loop {
             while let Some(h) = self.remove_list.pop() {
-                h.remove();
+                // it's already expired if not removed
+                if h.remove().is_some() {
+                    self.pending.fetch_sub(1, Ordering::Relaxed);
+                }
             }
             // we must register the thread handle first
             // or there will be no signal to wakeup the timer thread
             unsafe { self.wakeup.unsync_store(current_thread.clone()) };
 
+            // check it after the registration so that the stop signal is not lost
+            if self.stop.load(Ordering::SeqCst) {
+                return;
+            }
+
             if !self.remove_list.is_empty() {
                 if let Some(t) = self.wakeup.take() {
                     t.unpark();
                 }
             }
 
-            match self.timer_list.schedule_timer(now(), f) {
+            match self.timer_list.schedule_timer(now(), &f) {
                 Some(time) => thread::park_timeout(ns_to_dur(time)),
                 None => thread::park(),
             }
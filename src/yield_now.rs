@@ -1,7 +1,8 @@
 use crate::coroutine_impl::{current_cancel_data, is_coroutine};
 use crate::coroutine_impl::{CoroutineImpl, EventResult, EventSource, EventSubscriber};
+use crate::dump::{set_state, CoroutineState};
 use crate::likely::{likely, unlikely};
-use crate::scheduler::get_scheduler;
+use crate::scheduler::schedule;
 
 use generator::{co_get_yield, co_set_para, co_yield_with};
 
@@ -10,10 +11,19 @@ struct Yield {}
 impl EventSource for Yield {
     fn subscribe(&mut self, co: CoroutineImpl) {
         // just re-push the coroutine to the ready list
-        get_scheduler().schedule(co);
+        schedule(co);
     }
 }
 
+/// erase the lifetime of the `EventSource` ref so that it can be
+/// carried by the `EventSubscriber`, the resource is guaranteed to
+/// be alive until the coroutine is resumed
+#[inline]
+fn erase_lifetime<T: EventSource>(resource: &T) -> *mut dyn EventSource {
+    let r = resource as &dyn EventSource as *const (dyn EventSource + '_);
+    unsafe { std::mem::transmute(r) }
+}
+
 /// yield internal `EventSource` ref
 /// it's ok to return a ref of object on the generator's stack
 /// just like return the ref of a struct member
@@ -30,7 +40,7 @@ pub fn yield_with<T: EventSource>(resource: &T) {
         return resource.yield_back(cancel);
     }
 
-    let r = resource as &dyn EventSource as *const _ as *mut _;
+    let r = erase_lifetime(resource);
     let es = EventSubscriber::new(r);
     co_yield_with(es);
 
@@ -41,17 +51,18 @@ pub fn yield_with<T: EventSource>(resource: &T) {
 #[inline]
 pub fn yield_with_io<T: EventSource>(resource: &T, is_coroutine: bool) {
     if likely(is_coroutine) {
+        set_state(CoroutineState::ParkedOnIo);
         #[cfg(feature = "io_cancel")]
         yield_with(resource);
         #[cfg(not(feature = "io_cancel"))]
         {
-            let r = resource as &dyn EventSource as *const _ as *mut _;
+            let r = erase_lifetime(resource);
             let es = EventSubscriber::new(r);
             co_yield_with(es);
         }
     } else {
         // for thread is only park the thread
-        let r = resource as &dyn EventSource as *const _ as *mut _;
+        let r = erase_lifetime(resource);
         let es = EventSubscriber::new(r);
         crate::io::thread::PROXY_CO_SENDER.with(|tx| {
             tx.send(es).unwrap();
@@ -78,6 +89,7 @@ pub fn yield_now() {
     if unlikely(!is_coroutine()) {
         return std::thread::yield_now();
     }
+    set_state(CoroutineState::Ready);
     let y = Yield {};
     // it's safe to use the stack value here
     yield_with(&y);
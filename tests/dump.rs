@@ -0,0 +1,43 @@
+#![cfg(unix)]
+#[macro_use]
+extern crate may;
+
+use std::process::Command;
+use std::time::Duration;
+
+use may::coroutine;
+
+// run in a child process, the dump is printed to its stderr
+#[test]
+fn dump_signal_child() {
+    if std::env::var_os("MAY_DUMP_CHILD").is_none() {
+        return;
+    }
+    coroutine::dump_on_signal(libc::SIGUSR2).unwrap();
+    let builder = coroutine::Builder::new().name("dump-signal".to_owned());
+    let h = go!(builder, coroutine::park).unwrap();
+    // the process is not killed by the signal
+    unsafe { libc::raise(libc::SIGUSR2) };
+    std::thread::sleep(Duration::from_millis(200));
+    h.coroutine().unpark();
+    h.join().unwrap();
+}
+
+#[test]
+fn dump_on_signal() {
+    let out = Command::new(std::env::current_exe().unwrap())
+        .args([
+            "dump_signal_child",
+            "--exact",
+            "--nocapture",
+            "--test-threads=1",
+        ])
+        .env("MAY_DUMP_CHILD", "1")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+
+    assert!(out.status.success(), "{stderr}");
+    assert!(stderr.contains("live coroutines"), "{stderr}");
+    assert!(stderr.contains("\"dump-signal\""), "{stderr}");
+}
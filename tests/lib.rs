@@ -100,6 +100,26 @@ fn coroutine_result() {
     assert_eq!(j.join().unwrap(), 100);
 }
 
+#[test]
+fn coroutine_id() {
+    assert_eq!(coroutine::current_id(), None);
+    let j1 = go!(|| coroutine::current_id().unwrap());
+    let j2 = go!(|| coroutine::current().id());
+    let (id1, id2) = (j1.id(), j2.id());
+    assert_ne!(id1, id2);
+    assert!(id1 < id2);
+    assert_eq!(j1.coroutine().id(), id1);
+    assert_eq!(j1.join().unwrap(), id1);
+    assert_eq!(j2.join().unwrap(), id2);
+
+    let j = go!(coroutine::Builder::new().name("named".to_owned()), || ()).unwrap();
+    let s = format!("{:?}", j.coroutine());
+    assert!(s.contains(&format!("{:?}", j.id())));
+    assert!(s.contains("named"));
+    assert!(format!("{j:?}").contains(&j.id().as_u64().to_string()));
+    j.join().unwrap();
+}
+
 #[test]
 fn multi_coroutine() {
     for i in 0..10 {
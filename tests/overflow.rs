@@ -0,0 +1,55 @@
+#![cfg(unix)]
+#[macro_use]
+extern crate may;
+
+use std::hint::black_box;
+use std::process::Command;
+
+use may::coroutine;
+
+#[allow(unconditional_recursion)]
+fn recurse(n: usize) -> usize {
+    let buf = black_box([n as u8; 256]);
+    recurse(n + 1) + buf[0] as usize
+}
+
+// run in a child process, it's aborted by the overflow
+#[test]
+fn overflow_child() {
+    if std::env::var_os("MAY_OVERFLOW_CHILD").is_none() {
+        return;
+    }
+    let builder = coroutine::Builder::new()
+        .name("overflow".to_owned())
+        .stack_size(0x2000);
+    go!(builder, || recurse(0)).unwrap().join().ok();
+    unreachable!("the process should be aborted");
+}
+
+#[test]
+fn stack_overflow_report() {
+    let out = Command::new(std::env::current_exe().unwrap())
+        .args([
+            "overflow_child",
+            "--exact",
+            "--nocapture",
+            "--test-threads=1",
+        ])
+        .env("MAY_OVERFLOW_CHILD", "1")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+
+    use std::os::unix::process::ExitStatusExt;
+    assert_eq!(out.status.signal(), Some(libc::SIGABRT), "{stderr}");
+    assert!(
+        stderr.contains("'overflow' has overflowed its stack, stack size = 0x2000 words"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("increase it by"), "{stderr}");
+    // at least the faulting instruction is reported
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    assert!(stderr.contains("backtrace (image base = 0x"), "{stderr}");
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    assert!(stderr.contains("  #0  0x"), "{stderr}");
+}
@@ -0,0 +1,78 @@
+#[macro_use]
+extern crate may;
+
+use std::process::Command;
+
+use may::coroutine;
+
+// run in a child process, the panic hook is global
+#[test]
+fn panic_hook_child() {
+    if std::env::var_os("MAY_PANIC_HOOK_CHILD").is_none() {
+        return;
+    }
+    // the test harness replaces the hook to capture the output, so a
+    // custom hook is installed first to prove it's still called
+    std::panic::set_hook(Box::new(|_| eprintln!("previous hook")));
+    coroutine::install_panic_hook();
+    let builder = coroutine::Builder::new().name("boom".to_owned());
+    let h = go!(builder, || panic!("boom")).unwrap();
+    let id = h.id();
+    assert!(h.join().is_err());
+    eprintln!("joined {id}");
+}
+
+#[test]
+fn panic_hook_report() {
+    let out = Command::new(std::env::current_exe().unwrap())
+        .args([
+            "panic_hook_child",
+            "--exact",
+            "--nocapture",
+            "--test-threads=1",
+        ])
+        .env("MAY_PANIC_HOOK_CHILD", "1")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+
+    assert!(out.status.success(), "{stderr}");
+    let line = stderr
+        .lines()
+        .find(|l| l.ends_with("'boom' panicked"))
+        .unwrap_or_else(|| panic!("{stderr}"));
+    // the id is the same as the one of the join handle
+    let id = line
+        .trim_start_matches("coroutine #")
+        .split(' ')
+        .next()
+        .unwrap();
+    assert!(stderr.contains(&format!("joined {id}")), "{stderr}");
+    assert!(stderr.contains("previous hook"), "{stderr}");
+}
+
+#[test]
+fn no_panic_hook_by_default() {
+    let out = Command::new(std::env::current_exe().unwrap())
+        .args([
+            "--exact",
+            "--nocapture",
+            "--test-threads=1",
+            "no_hook_child",
+        ])
+        .env("MAY_PANIC_HOOK_CHILD", "1")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "{stderr}");
+    assert!(!stderr.contains("'quiet' panicked"), "{stderr}");
+}
+
+#[test]
+fn no_hook_child() {
+    if std::env::var_os("MAY_PANIC_HOOK_CHILD").is_none() {
+        return;
+    }
+    let builder = coroutine::Builder::new().name("quiet".to_owned());
+    assert!(go!(builder, || panic!("boom")).unwrap().join().is_err());
+}
@@ -0,0 +1,59 @@
+#[macro_use]
+extern crate may;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use may::coroutine;
+
+#[cfg(target_os = "linux")]
+fn open_fds() -> usize {
+    std::fs::read_dir("/proc/self/fd").unwrap().count()
+}
+
+// all the cases share the global runtime, so they are run in one test
+#[test]
+fn shutdown_runtime() {
+    #[cfg(target_os = "linux")]
+    let fds = open_fds();
+
+    // no runtime yet
+    unsafe { may::shutdown(Duration::from_secs(1)).unwrap() };
+
+    // can't be called in a coroutine
+    let h = go!(|| unsafe { may::shutdown(Duration::from_secs(1)) });
+    assert!(h.join().unwrap().is_err());
+
+    // the live coroutines are drained
+    let done = Arc::new(AtomicBool::new(false));
+    let d = done.clone();
+    go!(move || {
+        coroutine::sleep(Duration::from_millis(50));
+        d.store(true, Ordering::SeqCst);
+    });
+    let start = Instant::now();
+    unsafe { may::shutdown(Duration::from_secs(10)).unwrap() };
+    assert!(done.load(Ordering::SeqCst));
+    assert!(start.elapsed() < Duration::from_secs(10));
+
+    // the runtime is restarted by the next spawn
+    assert_eq!(go!(|| 42).join().unwrap(), 42);
+
+    // the coroutines that block the thread can't be canceled in time
+    let h = go!(|| std::thread::sleep(Duration::from_millis(1500)));
+    let err = unsafe { may::shutdown(Duration::from_millis(50)) }.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::TimedOut);
+    // the runtime keeps running
+    h.join().unwrap();
+
+    // the live coroutines are canceled after the timeout
+    let h = go!(|| coroutine::sleep(Duration::from_secs(1000)));
+    unsafe { may::shutdown(Duration::from_millis(50)).unwrap() };
+    assert!(h.is_done());
+    assert!(h.join().is_err());
+
+    // all the descriptors of the runtime are closed
+    #[cfg(target_os = "linux")]
+    assert_eq!(open_fds(), fds);
+}